@@ -0,0 +1,26 @@
+-- Initial schema. Uses IF NOT EXISTS so databases created before the
+-- migration engine existed are adopted as version 1 without changes.
+
+CREATE TABLE IF NOT EXISTS projects (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS todos (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    text TEXT NOT NULL,
+    completed INTEGER NOT NULL DEFAULT 0,
+    time INTEGER NOT NULL DEFAULT 25,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    project_id INTEGER REFERENCES projects(id) DEFAULT 1
+);
+
+CREATE TABLE IF NOT EXISTS excalidraw_data (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    elements TEXT NOT NULL,
+    app_state TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    project_id INTEGER REFERENCES projects(id) DEFAULT 1
+);
@@ -0,0 +1,19 @@
+-- Time-tracking log: one row per focus run on a todo.
+-- ended_at and outcome stay NULL while the run is in progress; paused_at is set
+-- while it is paused and folded into paused_seconds on resume or end. The time
+-- logged on a todo or project goes with it when it is deleted.
+
+CREATE TABLE focus_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    todo_id INTEGER NOT NULL REFERENCES todos(id) ON DELETE CASCADE,
+    project_id INTEGER NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    started_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ended_at TEXT,
+    paused_at TEXT,
+    paused_seconds INTEGER NOT NULL DEFAULT 0,
+    outcome TEXT CHECK (outcome IN ('completed', 'skipped', 'abandoned'))
+);
+
+CREATE INDEX idx_focus_sessions_todo ON focus_sessions(todo_id);
+CREATE INDEX idx_focus_sessions_project ON focus_sessions(project_id, started_at);
+CREATE INDEX idx_focus_sessions_started ON focus_sessions(started_at);
@@ -0,0 +1,6 @@
+-- Subtasks: a todo may belong to a parent todo in the same project.
+-- NULL parent_id means a top-level todo.
+
+ALTER TABLE todos ADD COLUMN parent_id INTEGER REFERENCES todos(id);
+
+CREATE INDEX idx_todos_parent ON todos(parent_id);
@@ -0,0 +1,17 @@
+-- Manual ordering: todos are listed by position within their project.
+-- Positions are fractional ranks, so a todo can be moved between two others by
+-- taking the midpoint; they are only NULL for a moment while a move rewrites them.
+
+ALTER TABLE todos ADD COLUMN position REAL;
+
+-- Keep the existing creation order, 1024 apart to leave room for moves
+UPDATE todos SET position = (
+    SELECT ranked.rank * 1024.0
+    FROM (
+        SELECT id, ROW_NUMBER() OVER (PARTITION BY project_id ORDER BY created_at, id) AS rank
+        FROM todos
+    ) ranked
+    WHERE ranked.id = todos.id
+);
+
+CREATE UNIQUE INDEX idx_todos_position ON todos(project_id, position);
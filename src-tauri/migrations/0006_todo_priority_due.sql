@@ -0,0 +1,6 @@
+-- Priorities and due dates drive which todo the focus timer picks next.
+-- Priority runs from 0 (P0, most urgent) to 3 (P3); existing todos become P2.
+-- Due times are stored in UTC like the other timestamps.
+
+ALTER TABLE todos ADD COLUMN priority INTEGER NOT NULL DEFAULT 2 CHECK (priority BETWEEN 0 AND 3);
+ALTER TABLE todos ADD COLUMN due_at DATETIME;
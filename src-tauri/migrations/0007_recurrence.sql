@@ -0,0 +1,5 @@
+-- Recurring todos: `recurrence` holds the rule as JSON (see types::Recurrence).
+-- Only the newest instance of a series carries the rule; it is handed to the next
+-- instance when that one is created.
+
+ALTER TABLE todos ADD COLUMN recurrence TEXT;
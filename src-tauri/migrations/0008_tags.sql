@@ -0,0 +1,21 @@
+-- Labels shared across projects. Names are unique regardless of case.
+
+CREATE TABLE tags (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE todo_tags (
+    todo_id INTEGER NOT NULL REFERENCES todos(id),
+    tag_id INTEGER NOT NULL REFERENCES tags(id),
+    PRIMARY KEY (todo_id, tag_id)
+);
+
+CREATE INDEX idx_todo_tags_tag ON todo_tags(tag_id);
+
+-- Foreign keys aren't enforced on these connections, so drop the links of deleted todos here
+CREATE TRIGGER todo_tags_delete_todo AFTER DELETE ON todos
+BEGIN
+    DELETE FROM todo_tags WHERE todo_id = OLD.id;
+END;
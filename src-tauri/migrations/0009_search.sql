@@ -0,0 +1,87 @@
+-- Full-text search over todos, projects and whiteboard text.
+-- One row per searchable record; `title` is weighted above `body` when ranking.
+-- Rowids encode the source so triggers can find their row without a scan:
+-- todo id * 4 + 1, project id * 4 + 2, excalidraw_data id * 4 + 3.
+
+CREATE VIRTUAL TABLE search_index USING fts5(
+    title,
+    body,
+    kind UNINDEXED,       -- 'todo', 'project' or 'whiteboard'
+    ref_id UNINDEXED,     -- id in the source table
+    project_id UNINDEXED, -- owning project
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER search_todos_insert AFTER INSERT ON todos
+BEGIN
+    INSERT INTO search_index (rowid, title, body, kind, ref_id, project_id)
+    VALUES (NEW.id * 4 + 1, NEW.text, '', 'todo', NEW.id, NEW.project_id);
+END;
+
+CREATE TRIGGER search_todos_update AFTER UPDATE OF text, project_id ON todos
+BEGIN
+    UPDATE search_index SET title = NEW.text, project_id = NEW.project_id WHERE rowid = NEW.id * 4 + 1;
+END;
+
+CREATE TRIGGER search_todos_delete AFTER DELETE ON todos
+BEGIN
+    DELETE FROM search_index WHERE rowid = OLD.id * 4 + 1;
+END;
+
+CREATE TRIGGER search_projects_insert AFTER INSERT ON projects
+BEGIN
+    INSERT INTO search_index (rowid, title, body, kind, ref_id, project_id)
+    VALUES (NEW.id * 4 + 2, NEW.name, COALESCE(NEW.description, ''), 'project', NEW.id, NEW.id);
+END;
+
+CREATE TRIGGER search_projects_update AFTER UPDATE OF name, description ON projects
+BEGIN
+    UPDATE search_index SET title = NEW.name, body = COALESCE(NEW.description, '') WHERE rowid = NEW.id * 4 + 2;
+END;
+
+CREATE TRIGGER search_projects_delete AFTER DELETE ON projects
+BEGIN
+    DELETE FROM search_index WHERE rowid = OLD.id * 4 + 2;
+END;
+
+-- Text of the live text elements in an Excalidraw elements array; invalid JSON
+-- indexes as empty rather than failing the save
+CREATE TRIGGER search_whiteboard_insert AFTER INSERT ON excalidraw_data
+BEGIN
+    INSERT INTO search_index (rowid, title, body, kind, ref_id, project_id)
+    SELECT NEW.id * 4 + 3, '', COALESCE(group_concat(json_extract(value, '$.text'), ' '), ''), 'whiteboard', NEW.id, NEW.project_id
+    FROM json_each(CASE WHEN json_valid(NEW.elements) THEN NEW.elements ELSE '[]' END)
+    WHERE json_extract(value, '$.type') = 'text' AND COALESCE(json_extract(value, '$.isDeleted'), 0) = 0;
+END;
+
+CREATE TRIGGER search_whiteboard_update AFTER UPDATE OF elements, project_id ON excalidraw_data
+BEGIN
+    UPDATE search_index SET
+        body = (
+            SELECT COALESCE(group_concat(json_extract(value, '$.text'), ' '), '')
+            FROM json_each(CASE WHEN json_valid(NEW.elements) THEN NEW.elements ELSE '[]' END)
+            WHERE json_extract(value, '$.type') = 'text' AND COALESCE(json_extract(value, '$.isDeleted'), 0) = 0
+        ),
+        project_id = NEW.project_id
+    WHERE rowid = NEW.id * 4 + 3;
+END;
+
+CREATE TRIGGER search_whiteboard_delete AFTER DELETE ON excalidraw_data
+BEGIN
+    DELETE FROM search_index WHERE rowid = OLD.id * 4 + 3;
+END;
+
+-- Index what is already there
+INSERT INTO search_index (rowid, title, body, kind, ref_id, project_id)
+SELECT id * 4 + 1, text, '', 'todo', id, project_id FROM todos;
+
+INSERT INTO search_index (rowid, title, body, kind, ref_id, project_id)
+SELECT id * 4 + 2, name, COALESCE(description, ''), 'project', id, id FROM projects;
+
+INSERT INTO search_index (rowid, title, body, kind, ref_id, project_id)
+SELECT e.id * 4 + 3, '', (
+    SELECT COALESCE(group_concat(json_extract(value, '$.text'), ' '), '')
+    FROM json_each(CASE WHEN json_valid(e.elements) THEN e.elements ELSE '[]' END)
+    WHERE json_extract(value, '$.type') = 'text' AND COALESCE(json_extract(value, '$.isDeleted'), 0) = 0
+), 'whiteboard', e.id, e.project_id
+FROM excalidraw_data e;
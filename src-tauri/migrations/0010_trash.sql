@@ -0,0 +1,9 @@
+-- Soft delete: deleting a project, todo or whiteboard stamps `deleted_at` instead of
+-- removing the row, and the scheduler purges rows that stayed in the trash too long.
+-- Everything deleted in one go shares the same stamp, so it can be restored together.
+
+ALTER TABLE projects ADD COLUMN deleted_at DATETIME;
+ALTER TABLE todos ADD COLUMN deleted_at DATETIME;
+ALTER TABLE excalidraw_data ADD COLUMN deleted_at DATETIME;
+
+CREATE INDEX idx_todos_deleted_at ON todos(deleted_at);
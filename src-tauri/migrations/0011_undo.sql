@@ -0,0 +1,166 @@
+-- Undo/redo journal. While a journaled operation runs, `undo_recording.entry_id`
+-- names its entry and the triggers below store, for every row it changes, the SQL
+-- statement that changes the row back. Undoing an entry runs its statements newest
+-- first while recording their inverses as a redo entry, and the other way round.
+-- The triggers list every column, so a migration that adds one must recreate them.
+
+CREATE TABLE undo_entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    stack TEXT NOT NULL CHECK (stack IN ('undo', 'redo')),
+    label TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE undo_changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entry_id INTEGER NOT NULL REFERENCES undo_entries(id),
+    statement TEXT NOT NULL
+);
+
+CREATE INDEX idx_undo_changes_entry ON undo_changes(entry_id);
+
+CREATE TABLE undo_recording (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    entry_id INTEGER
+);
+
+INSERT INTO undo_recording (id, entry_id) VALUES (1, NULL);
+
+-- todos
+
+CREATE TRIGGER undo_todos_insert AFTER INSERT ON todos
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'DELETE FROM todos WHERE id = ' || NEW.id FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_todos_update AFTER UPDATE ON todos
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'UPDATE todos SET text = ' || quote(OLD.text)
+        || ', completed = ' || quote(OLD.completed)
+        || ', time = ' || quote(OLD.time)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ', project_id = ' || quote(OLD.project_id)
+        || ', parent_id = ' || quote(OLD.parent_id)
+        || ', position = ' || quote(OLD.position)
+        || ', priority = ' || quote(OLD.priority)
+        || ', due_at = ' || quote(OLD.due_at)
+        || ', recurrence = ' || quote(OLD.recurrence)
+        || ', deleted_at = ' || quote(OLD.deleted_at)
+        || ' WHERE id = ' || OLD.id
+    FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_todos_delete AFTER DELETE ON todos
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'INSERT INTO todos (id, text, completed, time, created_at, project_id, parent_id, position, priority, due_at, recurrence, deleted_at) VALUES ('
+        || OLD.id
+        || ', ' || quote(OLD.text)
+        || ', ' || quote(OLD.completed)
+        || ', ' || quote(OLD.time)
+        || ', ' || quote(OLD.created_at)
+        || ', ' || quote(OLD.project_id)
+        || ', ' || quote(OLD.parent_id)
+        || ', ' || quote(OLD.position)
+        || ', ' || quote(OLD.priority)
+        || ', ' || quote(OLD.due_at)
+        || ', ' || quote(OLD.recurrence)
+        || ', ' || quote(OLD.deleted_at)
+        || ')'
+    FROM undo_recording;
+END;
+
+-- projects
+
+CREATE TRIGGER undo_projects_insert AFTER INSERT ON projects
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'DELETE FROM projects WHERE id = ' || NEW.id FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_projects_update AFTER UPDATE ON projects
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'UPDATE projects SET name = ' || quote(OLD.name)
+        || ', description = ' || quote(OLD.description)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ', deleted_at = ' || quote(OLD.deleted_at)
+        || ' WHERE id = ' || OLD.id
+    FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_projects_delete AFTER DELETE ON projects
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'INSERT INTO projects (id, name, description, created_at, deleted_at) VALUES ('
+        || OLD.id
+        || ', ' || quote(OLD.name)
+        || ', ' || quote(OLD.description)
+        || ', ' || quote(OLD.created_at)
+        || ', ' || quote(OLD.deleted_at)
+        || ')'
+    FROM undo_recording;
+END;
+
+-- excalidraw_data
+
+CREATE TRIGGER undo_excalidraw_insert AFTER INSERT ON excalidraw_data
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'DELETE FROM excalidraw_data WHERE id = ' || NEW.id FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_excalidraw_update AFTER UPDATE ON excalidraw_data
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'UPDATE excalidraw_data SET elements = ' || quote(OLD.elements)
+        || ', app_state = ' || quote(OLD.app_state)
+        || ', updated_at = ' || quote(OLD.updated_at)
+        || ', project_id = ' || quote(OLD.project_id)
+        || ', deleted_at = ' || quote(OLD.deleted_at)
+        || ' WHERE id = ' || OLD.id
+    FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_excalidraw_delete AFTER DELETE ON excalidraw_data
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'INSERT INTO excalidraw_data (id, elements, app_state, updated_at, project_id, deleted_at) VALUES ('
+        || OLD.id
+        || ', ' || quote(OLD.elements)
+        || ', ' || quote(OLD.app_state)
+        || ', ' || quote(OLD.updated_at)
+        || ', ' || quote(OLD.project_id)
+        || ', ' || quote(OLD.deleted_at)
+        || ')'
+    FROM undo_recording;
+END;
+
+-- todo_tags, so copies and restored todos keep their tags
+
+CREATE TRIGGER undo_todo_tags_insert AFTER INSERT ON todo_tags
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'DELETE FROM todo_tags WHERE todo_id = ' || NEW.todo_id || ' AND tag_id = ' || NEW.tag_id
+    FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_todo_tags_delete AFTER DELETE ON todo_tags
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'INSERT INTO todo_tags (todo_id, tag_id) VALUES (' || OLD.todo_id || ', ' || OLD.tag_id || ')'
+    FROM undo_recording;
+END;
@@ -0,0 +1,295 @@
+-- ON DELETE actions for every reference, so removing a project or todo takes what
+-- belongs to it along instead of leaving orphans behind. SQLite can't alter a
+-- foreign key, so the referencing tables are rebuilt
+-- (https://sqlite.org/lang_altertable.html#otheralter); the migration runner turns
+-- enforcement off around this and checks every reference before committing.
+-- Todos and whiteboards whose project is NULL or missing first move to a
+-- "Recovered items" project, so every row is copied over; AUTOINCREMENT counters
+-- carry over so IDs held in the undo journal are never handed out again.
+-- Copies the triggers from 0009 and 0011 on the rebuilt tables.
+
+-- Orphans: "Recovered items" is created, or brought back from the trash, only when
+-- there is something to put in it
+
+INSERT OR IGNORE INTO projects (name, description)
+SELECT 'Recovered items', 'Todos and whiteboards whose project was missing'
+WHERE EXISTS (SELECT 1 FROM todos WHERE project_id IS NULL OR project_id NOT IN (SELECT id FROM projects))
+   OR EXISTS (SELECT 1 FROM excalidraw_data WHERE project_id IS NULL OR project_id NOT IN (SELECT id FROM projects));
+
+UPDATE projects SET deleted_at = NULL
+WHERE name = 'Recovered items'
+  AND (EXISTS (SELECT 1 FROM todos WHERE project_id IS NULL OR project_id NOT IN (SELECT id FROM projects))
+    OR EXISTS (SELECT 1 FROM excalidraw_data WHERE project_id IS NULL OR project_id NOT IN (SELECT id FROM projects)));
+
+-- Moved todos go after whatever the project already holds, in ID order
+UPDATE todos
+SET project_id = (SELECT id FROM projects WHERE name = 'Recovered items'),
+    position = (
+        SELECT COALESCE(MAX(position), 0) FROM todos
+        WHERE project_id = (SELECT id FROM projects WHERE name = 'Recovered items')
+    ) + id * 1024.0
+WHERE project_id IS NULL OR project_id NOT IN (SELECT id FROM projects);
+
+-- A subtask only stays under a parent in the same project
+UPDATE todos SET parent_id = NULL
+WHERE parent_id IS NOT NULL
+  AND project_id IS NOT (SELECT parent.project_id FROM todos parent WHERE parent.id = todos.parent_id);
+
+UPDATE excalidraw_data
+SET project_id = (SELECT id FROM projects WHERE name = 'Recovered items')
+WHERE project_id IS NULL OR project_id NOT IN (SELECT id FROM projects);
+
+-- Sessions are logged against the project of their todo
+UPDATE focus_sessions
+SET project_id = (SELECT project_id FROM todos WHERE todos.id = focus_sessions.todo_id)
+WHERE project_id NOT IN (SELECT id FROM projects) AND todo_id IN (SELECT id FROM todos);
+
+-- The log of a todo that is gone went with it under the cascade declared in 0002;
+-- only rows written while enforcement was off are left to clear
+DELETE FROM focus_sessions WHERE todo_id NOT IN (SELECT id FROM todos);
+
+-- todos: go with their project; subtasks of a removed parent become top-level
+
+CREATE TABLE new_todos (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    text TEXT NOT NULL,
+    completed INTEGER NOT NULL DEFAULT 0,
+    time INTEGER NOT NULL DEFAULT 25,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    project_id INTEGER REFERENCES projects(id) ON DELETE CASCADE DEFAULT 1,
+    parent_id INTEGER REFERENCES todos(id) ON DELETE SET NULL,
+    position REAL,
+    priority INTEGER NOT NULL DEFAULT 2 CHECK (priority BETWEEN 0 AND 3),
+    due_at DATETIME,
+    recurrence TEXT,
+    deleted_at DATETIME
+);
+
+INSERT INTO new_todos (id, text, completed, time, created_at, project_id, parent_id, position, priority, due_at, recurrence, deleted_at)
+SELECT id, text, completed, time, created_at, project_id, parent_id, position, priority, due_at, recurrence, deleted_at
+FROM todos;
+
+UPDATE new_todos SET parent_id = NULL
+WHERE parent_id IS NOT NULL AND parent_id NOT IN (SELECT id FROM new_todos);
+
+DELETE FROM sqlite_sequence WHERE name = 'new_todos';
+INSERT INTO sqlite_sequence (name, seq) SELECT 'new_todos', seq FROM sqlite_sequence WHERE name = 'todos';
+
+-- Also drops todo_tags_delete_todo, which the cascade on todo_tags replaces
+DROP TABLE todos;
+ALTER TABLE new_todos RENAME TO todos;
+
+CREATE INDEX idx_todos_parent ON todos(parent_id);
+CREATE UNIQUE INDEX idx_todos_position ON todos(project_id, position);
+CREATE INDEX idx_todos_deleted_at ON todos(deleted_at);
+
+CREATE TRIGGER search_todos_insert AFTER INSERT ON todos
+BEGIN
+    INSERT INTO search_index (rowid, title, body, kind, ref_id, project_id)
+    VALUES (NEW.id * 4 + 1, NEW.text, '', 'todo', NEW.id, NEW.project_id);
+END;
+
+CREATE TRIGGER search_todos_update AFTER UPDATE OF text, project_id ON todos
+BEGIN
+    UPDATE search_index SET title = NEW.text, project_id = NEW.project_id WHERE rowid = NEW.id * 4 + 1;
+END;
+
+CREATE TRIGGER search_todos_delete AFTER DELETE ON todos
+BEGIN
+    DELETE FROM search_index WHERE rowid = OLD.id * 4 + 1;
+END;
+
+CREATE TRIGGER undo_todos_insert AFTER INSERT ON todos
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'DELETE FROM todos WHERE id = ' || NEW.id FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_todos_update AFTER UPDATE ON todos
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'UPDATE todos SET text = ' || quote(OLD.text)
+        || ', completed = ' || quote(OLD.completed)
+        || ', time = ' || quote(OLD.time)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ', project_id = ' || quote(OLD.project_id)
+        || ', parent_id = ' || quote(OLD.parent_id)
+        || ', position = ' || quote(OLD.position)
+        || ', priority = ' || quote(OLD.priority)
+        || ', due_at = ' || quote(OLD.due_at)
+        || ', recurrence = ' || quote(OLD.recurrence)
+        || ', deleted_at = ' || quote(OLD.deleted_at)
+        || ' WHERE id = ' || OLD.id
+    FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_todos_delete AFTER DELETE ON todos
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'INSERT INTO todos (id, text, completed, time, created_at, project_id, parent_id, position, priority, due_at, recurrence, deleted_at) VALUES ('
+        || OLD.id
+        || ', ' || quote(OLD.text)
+        || ', ' || quote(OLD.completed)
+        || ', ' || quote(OLD.time)
+        || ', ' || quote(OLD.created_at)
+        || ', ' || quote(OLD.project_id)
+        || ', ' || quote(OLD.parent_id)
+        || ', ' || quote(OLD.position)
+        || ', ' || quote(OLD.priority)
+        || ', ' || quote(OLD.due_at)
+        || ', ' || quote(OLD.recurrence)
+        || ', ' || quote(OLD.deleted_at)
+        || ')'
+    FROM undo_recording;
+END;
+
+-- excalidraw_data: goes with its project
+
+CREATE TABLE new_excalidraw_data (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    elements TEXT NOT NULL,
+    app_state TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    project_id INTEGER REFERENCES projects(id) ON DELETE CASCADE DEFAULT 1,
+    deleted_at DATETIME
+);
+
+INSERT INTO new_excalidraw_data (id, elements, app_state, updated_at, project_id, deleted_at)
+SELECT id, elements, app_state, updated_at, project_id, deleted_at
+FROM excalidraw_data;
+
+DELETE FROM sqlite_sequence WHERE name = 'new_excalidraw_data';
+INSERT INTO sqlite_sequence (name, seq) SELECT 'new_excalidraw_data', seq FROM sqlite_sequence WHERE name = 'excalidraw_data';
+
+DROP TABLE excalidraw_data;
+ALTER TABLE new_excalidraw_data RENAME TO excalidraw_data;
+
+-- Text of the live text elements in an Excalidraw elements array; invalid JSON
+-- indexes as empty rather than failing the save
+CREATE TRIGGER search_whiteboard_insert AFTER INSERT ON excalidraw_data
+BEGIN
+    INSERT INTO search_index (rowid, title, body, kind, ref_id, project_id)
+    SELECT NEW.id * 4 + 3, '', COALESCE(group_concat(json_extract(value, '$.text'), ' '), ''), 'whiteboard', NEW.id, NEW.project_id
+    FROM json_each(CASE WHEN json_valid(NEW.elements) THEN NEW.elements ELSE '[]' END)
+    WHERE json_extract(value, '$.type') = 'text' AND COALESCE(json_extract(value, '$.isDeleted'), 0) = 0;
+END;
+
+CREATE TRIGGER search_whiteboard_update AFTER UPDATE OF elements, project_id ON excalidraw_data
+BEGIN
+    UPDATE search_index SET
+        body = (
+            SELECT COALESCE(group_concat(json_extract(value, '$.text'), ' '), '')
+            FROM json_each(CASE WHEN json_valid(NEW.elements) THEN NEW.elements ELSE '[]' END)
+            WHERE json_extract(value, '$.type') = 'text' AND COALESCE(json_extract(value, '$.isDeleted'), 0) = 0
+        ),
+        project_id = NEW.project_id
+    WHERE rowid = NEW.id * 4 + 3;
+END;
+
+CREATE TRIGGER search_whiteboard_delete AFTER DELETE ON excalidraw_data
+BEGIN
+    DELETE FROM search_index WHERE rowid = OLD.id * 4 + 3;
+END;
+
+CREATE TRIGGER undo_excalidraw_insert AFTER INSERT ON excalidraw_data
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'DELETE FROM excalidraw_data WHERE id = ' || NEW.id FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_excalidraw_update AFTER UPDATE ON excalidraw_data
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'UPDATE excalidraw_data SET elements = ' || quote(OLD.elements)
+        || ', app_state = ' || quote(OLD.app_state)
+        || ', updated_at = ' || quote(OLD.updated_at)
+        || ', project_id = ' || quote(OLD.project_id)
+        || ', deleted_at = ' || quote(OLD.deleted_at)
+        || ' WHERE id = ' || OLD.id
+    FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_excalidraw_delete AFTER DELETE ON excalidraw_data
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'INSERT INTO excalidraw_data (id, elements, app_state, updated_at, project_id, deleted_at) VALUES ('
+        || OLD.id
+        || ', ' || quote(OLD.elements)
+        || ', ' || quote(OLD.app_state)
+        || ', ' || quote(OLD.updated_at)
+        || ', ' || quote(OLD.project_id)
+        || ', ' || quote(OLD.deleted_at)
+        || ')'
+    FROM undo_recording;
+END;
+
+-- todo_tags: a link goes with either end
+
+CREATE TABLE new_todo_tags (
+    todo_id INTEGER NOT NULL REFERENCES todos(id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (todo_id, tag_id)
+);
+
+INSERT INTO new_todo_tags (todo_id, tag_id)
+SELECT todo_id, tag_id
+FROM todo_tags
+WHERE todo_id IN (SELECT id FROM todos) AND tag_id IN (SELECT id FROM tags);
+
+DROP TABLE todo_tags;
+ALTER TABLE new_todo_tags RENAME TO todo_tags;
+
+CREATE INDEX idx_todo_tags_tag ON todo_tags(tag_id);
+
+CREATE TRIGGER undo_todo_tags_insert AFTER INSERT ON todo_tags
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'DELETE FROM todo_tags WHERE todo_id = ' || NEW.todo_id || ' AND tag_id = ' || NEW.tag_id
+    FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_todo_tags_delete AFTER DELETE ON todo_tags
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'INSERT INTO todo_tags (todo_id, tag_id) VALUES (' || OLD.todo_id || ', ' || OLD.tag_id || ')'
+    FROM undo_recording;
+END;
+
+-- focus_sessions: the time logged on a todo goes with it
+
+CREATE TABLE new_focus_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    todo_id INTEGER NOT NULL REFERENCES todos(id) ON DELETE CASCADE,
+    project_id INTEGER NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    started_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ended_at TEXT,
+    paused_at TEXT,
+    paused_seconds INTEGER NOT NULL DEFAULT 0,
+    outcome TEXT CHECK (outcome IN ('completed', 'skipped', 'abandoned'))
+);
+
+INSERT INTO new_focus_sessions (id, todo_id, project_id, started_at, ended_at, paused_at, paused_seconds, outcome)
+SELECT id, todo_id, project_id, started_at, ended_at, paused_at, paused_seconds, outcome
+FROM focus_sessions;
+
+DELETE FROM sqlite_sequence WHERE name = 'new_focus_sessions';
+INSERT INTO sqlite_sequence (name, seq) SELECT 'new_focus_sessions', seq FROM sqlite_sequence WHERE name = 'focus_sessions';
+
+DROP TABLE focus_sessions;
+ALTER TABLE new_focus_sessions RENAME TO focus_sessions;
+
+CREATE INDEX idx_focus_sessions_todo ON focus_sessions(todo_id);
+CREATE INDEX idx_focus_sessions_project ON focus_sessions(project_id, started_at);
+CREATE INDEX idx_focus_sessions_started ON focus_sessions(started_at);
+
+-- Forget what the search index still holds for rows that are gone
+DELETE FROM search_index WHERE kind = 'todo' AND ref_id NOT IN (SELECT id FROM todos);
+DELETE FROM search_index WHERE kind = 'whiteboard' AND ref_id NOT IN (SELECT id FROM excalidraw_data);
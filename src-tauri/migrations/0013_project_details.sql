@@ -0,0 +1,43 @@
+-- Presentation and state of a project: a `#rrggbb` color, an emoji or icon name,
+-- and when it was archived (NULL while active).
+
+ALTER TABLE projects ADD COLUMN color TEXT;
+ALTER TABLE projects ADD COLUMN icon TEXT;
+ALTER TABLE projects ADD COLUMN archived_at DATETIME;
+
+-- The undo triggers from 0011 have to restore the new columns too
+
+DROP TRIGGER undo_projects_update;
+DROP TRIGGER undo_projects_delete;
+
+CREATE TRIGGER undo_projects_update AFTER UPDATE ON projects
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'UPDATE projects SET name = ' || quote(OLD.name)
+        || ', description = ' || quote(OLD.description)
+        || ', created_at = ' || quote(OLD.created_at)
+        || ', deleted_at = ' || quote(OLD.deleted_at)
+        || ', color = ' || quote(OLD.color)
+        || ', icon = ' || quote(OLD.icon)
+        || ', archived_at = ' || quote(OLD.archived_at)
+        || ' WHERE id = ' || OLD.id
+    FROM undo_recording;
+END;
+
+CREATE TRIGGER undo_projects_delete AFTER DELETE ON projects
+WHEN (SELECT entry_id FROM undo_recording) IS NOT NULL
+BEGIN
+    INSERT INTO undo_changes (entry_id, statement)
+    SELECT entry_id, 'INSERT INTO projects (id, name, description, created_at, deleted_at, color, icon, archived_at) VALUES ('
+        || OLD.id
+        || ', ' || quote(OLD.name)
+        || ', ' || quote(OLD.description)
+        || ', ' || quote(OLD.created_at)
+        || ', ' || quote(OLD.deleted_at)
+        || ', ' || quote(OLD.color)
+        || ', ' || quote(OLD.icon)
+        || ', ' || quote(OLD.archived_at)
+        || ')'
+    FROM undo_recording;
+END;
@@ -0,0 +1,25 @@
+-- Reusable project layouts. A template keeps a project's todo list (text, planned
+-- minutes, order and nesting) and optionally its whiteboard; todo text may contain
+-- {{placeholders}} that are filled in when a project is created from it.
+
+CREATE TABLE project_templates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    color TEXT,
+    icon TEXT,
+    elements TEXT,  -- starter whiteboard, NULL for none
+    app_state TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE template_todos (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    template_id INTEGER NOT NULL REFERENCES project_templates(id) ON DELETE CASCADE,
+    parent_id INTEGER REFERENCES template_todos(id) ON DELETE CASCADE,
+    text TEXT NOT NULL,
+    time INTEGER NOT NULL DEFAULT 25,
+    position INTEGER NOT NULL -- order within the template's list
+);
+
+CREATE INDEX idx_template_todos_template ON template_todos(template_id, position);
@@ -0,0 +1,4 @@
+-- What a run of changes that undo together has in common, e.g. the whiteboard of
+-- one project for its autosaves; NULL for entries that stand on their own.
+
+ALTER TABLE undo_entries ADD COLUMN merge_key TEXT;
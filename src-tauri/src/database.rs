@@ -1,13 +1,17 @@
-use sqlx::{sqlite::SqlitePool};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tauri::State;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use crate::error::MissionControlError;
+use crate::migrations::{latest_version, run_migrations};
 
 // Database state - using Arc<Mutex> for thread safety
 pub struct Database {
     pub pool: Arc<Mutex<Option<SqlitePool>>>,
     pub initialized: Arc<Mutex<bool>>,
+    pub path: Arc<Mutex<Option<PathBuf>>>,
 }
 
 impl Database {
@@ -15,12 +19,13 @@ impl Database {
         Self {
             pool: Arc::new(Mutex::new(None)),
             initialized: Arc::new(Mutex::new(false)),
+            path: Arc::new(Mutex::new(None)),
         }
     }
 }
 
 // Get database path with better error handling and fallback options
-fn get_database_path() -> Result<PathBuf, String> {
+fn get_database_path() -> Result<PathBuf, MissionControlError> {
     // Try multiple approaches for finding a writable directory
     let possible_paths = vec![
         // Try XDG data home first (Linux standard)
@@ -72,74 +77,13 @@ fn get_database_path() -> Result<PathBuf, String> {
         }
     }
     
-    Err("Could not find a writable directory for the database".to_string())
+    Err(MissionControlError::Io {
+        message: "Could not find a writable directory for the database".to_string(),
+        details: None,
+    })
 }
 
-async fn create_tables(pool: &SqlitePool) -> Result<(), String> {
-    // Create projects table first (referenced by other tables)
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS projects (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL UNIQUE,
-            description TEXT,
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
-        )
-        "#,
-    )
-    .execute(pool)
-    .await
-    .map_err(|e| {
-        let error_msg = format!("Failed to create projects table: {}", e);
-        println!("{}", error_msg);
-        error_msg
-    })?;
-
-    // Create todos table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS todos (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            text TEXT NOT NULL,
-            completed INTEGER NOT NULL DEFAULT 0,
-            time INTEGER NOT NULL DEFAULT 25,
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            project_id INTEGER REFERENCES projects(id) DEFAULT 1
-        )
-        "#,
-    )
-    .execute(pool)
-    .await
-    .map_err(|e| {
-        let error_msg = format!("Failed to create todos table: {}", e);
-        println!("{}", error_msg);
-        error_msg
-    })?;
-
-    // Create excalidraw_data table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS excalidraw_data (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            elements TEXT NOT NULL,
-            app_state TEXT NOT NULL,
-            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            project_id INTEGER REFERENCES projects(id) DEFAULT 1
-        )
-        "#,
-    )
-    .execute(pool)
-    .await
-    .map_err(|e| {
-        let error_msg = format!("Failed to create excalidraw_data table: {}", e);
-        println!("{}", error_msg);
-        error_msg
-    })?;
-
-    Ok(())
-}
-
-async fn create_default_project(pool: &SqlitePool) -> Result<(), String> {
+async fn create_default_project(pool: &SqlitePool) -> Result<(), MissionControlError> {
     // Create default project if none exists
     let project_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM projects")
         .fetch_one(pool)
@@ -152,19 +96,22 @@ async fn create_default_project(pool: &SqlitePool) -> Result<(), String> {
             .bind("Your first project")
             .execute(pool)
             .await
-            .map_err(|e| {
-                let error_msg = format!("Failed to create default project: {}", e);
-                println!("{}", error_msg);
-                error_msg
-            })?;
+            .map_err(|e| MissionControlError::from_sqlx("Failed to create default project", e))?;
         println!("Default project created");
     }
 
     Ok(())
 }
 
+// Open a pool whose connections all enforce foreign keys, so deletes cascade as the
+// schema declares. sqlx turns them on by default; this keeps it from depending on that.
+async fn connect(database_url: &str) -> Result<SqlitePool, sqlx::Error> {
+    let options = SqliteConnectOptions::from_str(database_url)?.foreign_keys(true);
+    SqlitePool::connect_with(options).await
+}
+
 // Initialize database
-pub async fn init_database(database: &Database) -> Result<String, String> {
+pub async fn init_database(database: &Database) -> Result<String, MissionControlError> {
     // Check if already initialized
     {
         let initialized_guard = database.initialized.lock().await;
@@ -197,7 +144,7 @@ pub async fn init_database(database: &Database) -> Result<String, String> {
     for attempt in 1..=3 {
         println!("Connection attempt {} of 3", attempt);
         
-        match SqlitePool::connect(&database_url).await {
+        match connect(&database_url).await {
             Ok(pool) => {
                 println!("Database connected successfully on attempt {}", attempt);
                 
@@ -205,13 +152,13 @@ pub async fn init_database(database: &Database) -> Result<String, String> {
                     println!("New database created successfully");
                 }
 
-                // Create tables
-                create_tables(&pool).await?;
+                // Bring the schema up to date
+                let schema_version = run_migrations(&pool).await?;
                 
                 if is_new_database {
-                    println!("New database tables created successfully");
+                    println!("New database schema created at version {}", schema_version);
                 } else {
-                    println!("Database tables verified/exist");
+                    println!("Database schema verified at version {}", schema_version);
                 }
 
                 // Create default project if needed
@@ -222,6 +169,11 @@ pub async fn init_database(database: &Database) -> Result<String, String> {
                     let mut pool_guard = database.pool.lock().await;
                     *pool_guard = Some(pool);
                 }
+
+                {
+                    let mut path_guard = database.path.lock().await;
+                    *path_guard = Some(absolute_path.clone());
+                }
                 
                 {
                     let mut initialized_guard = database.initialized.lock().await;
@@ -250,11 +202,14 @@ pub async fn init_database(database: &Database) -> Result<String, String> {
         }
     }
     
-    Err(format!("All connection attempts failed. Last error: {}", last_error))
+    Err(MissionControlError::Database {
+        message: "All connection attempts failed".to_string(),
+        details: Some(last_error),
+    })
 }
 
 // Helper function to get pool
-pub async fn get_pool(database: &State<'_, Database>) -> Result<SqlitePool, String> {
+pub async fn get_pool(database: &State<'_, Database>) -> Result<SqlitePool, MissionControlError> {
     let pool_guard = database.pool.lock().await;
     match pool_guard.as_ref() {
         Some(pool) => Ok(pool.clone()),
@@ -269,8 +224,157 @@ pub async fn get_pool(database: &State<'_, Database>) -> Result<SqlitePool, Stri
             let pool_guard = database.pool.lock().await;
             pool_guard
                 .as_ref()
-                .ok_or("Database initialization failed".to_string())
-                .map(|p| p.clone())
+                .ok_or_else(|| MissionControlError::NotInitialized("Database initialization failed".to_string()))
+                .cloned()
+        }
+    }
+}
+
+// Pool for background jobs - unlike get_pool this never triggers initialization
+pub async fn try_get_pool(database: &Database) -> Option<SqlitePool> {
+    database.pool.lock().await.clone()
+}
+
+// Path of the open database file
+pub async fn get_database_file(database: &Database) -> Result<PathBuf, MissionControlError> {
+    database
+        .path
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| MissionControlError::NotInitialized("Database is not initialized".to_string()))
+}
+
+// Schema version recorded in the database file at `path`, opened read-only
+async fn file_schema_version(path: &Path) -> Result<i64, MissionControlError> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let pool = SqlitePool::connect_with(options)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to open snapshot", e))?;
+    let version = crate::migrations::current_version(&pool).await.map_err(|_| {
+        MissionControlError::Validation(format!("{} is not a MissionControl database", path.display()))
+    });
+    pool.close().await;
+    version
+}
+
+// Replace the database file with `source` and reopen it. The pool lock is held
+// throughout, so no command can use the old pool while the file is swapped.
+// A snapshot from a newer build is refused before anything is touched, and the
+// copy is written next to the database and renamed over it, so a failed copy
+// leaves the live file as it was.
+pub async fn replace_database_file(database: &Database, source: &Path) -> Result<(), MissionControlError> {
+    let db_path = get_database_file(database).await?;
+
+    let snapshot_version = file_schema_version(source).await?;
+    let latest = latest_version();
+    if snapshot_version > latest {
+        return Err(MissionControlError::Conflict(format!(
+            "Snapshot schema version {} is newer than this build supports ({})",
+            snapshot_version, latest
+        )));
+    }
+
+    let staged = PathBuf::from(format!("{}.restoring", db_path.to_string_lossy()));
+    let staged_result = std::fs::copy(source, &staged)
+        .and_then(|_| std::fs::File::open(&staged)?.sync_all())
+        .map_err(|e| MissionControlError::from_io("Failed to copy snapshot next to the database", e));
+    if let Err(e) = staged_result {
+        let _ = std::fs::remove_file(&staged);
+        return Err(e);
+    }
+
+    let mut pool_guard = database.pool.lock().await;
+
+    if let Some(pool) = pool_guard.take() {
+        // Waits for in-flight queries and checkpoints the WAL
+        pool.close().await;
+    }
+
+    let swap_result = async {
+        for suffix in ["-wal", "-shm"] {
+            let sidecar = PathBuf::from(format!("{}{}", db_path.to_string_lossy(), suffix));
+            if sidecar.exists() {
+                std::fs::remove_file(&sidecar)
+                    .map_err(|e| MissionControlError::from_io("Failed to remove database journal", e))?;
+            }
         }
+        std::fs::rename(&staged, &db_path)
+            .map_err(|e| MissionControlError::from_io("Failed to move snapshot over database", e))?;
+
+        let database_url = format!("sqlite:{}?mode=rwc", db_path.to_string_lossy());
+        let pool = connect(&database_url)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to reopen database", e))?;
+        // Snapshots from older builds still need to be brought up to date
+        run_migrations(&pool).await?;
+        Ok::<SqlitePool, MissionControlError>(pool)
     }
-} 
\ No newline at end of file
+    .await;
+
+    match swap_result {
+        Ok(pool) => {
+            *pool_guard = Some(pool);
+            Ok(())
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&staged);
+            // Let the next command re-run initialization from scratch
+            *database.initialized.lock().await = false;
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{project, TempDatabase};
+
+    // A Database serving `temp` as if init_database had opened it
+    async fn open(temp: &TempDatabase) -> Database {
+        let database = Database::new();
+        *database.path.lock().await = Some(temp.path.clone());
+        *database.pool.lock().await = Some(temp.pool.clone());
+        *database.initialized.lock().await = true;
+        database
+    }
+
+    async fn project_names(database: &Database) -> Vec<String> {
+        let pool = try_get_pool(database).await.unwrap();
+        sqlx::query_scalar("SELECT name FROM projects ORDER BY id").fetch_all(&pool).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn replacing_swaps_in_the_snapshot() {
+        let live = TempDatabase::new(1).await;
+        project("Live", &live.pool).await;
+        let snapshot = TempDatabase::new(1).await;
+        project("Snapshot", &snapshot.pool).await;
+        snapshot.pool.close().await;
+
+        let database = open(&live).await;
+        replace_database_file(&database, &snapshot.path).await.unwrap();
+        assert_eq!(project_names(&database).await, vec!["Snapshot"]);
+        assert!(!PathBuf::from(format!("{}.restoring", live.path.display())).exists());
+    }
+
+    #[tokio::test]
+    async fn snapshots_from_a_newer_build_are_refused() {
+        let live = TempDatabase::new(1).await;
+        project("Live", &live.pool).await;
+        let snapshot = TempDatabase::new(1).await;
+        sqlx::query("INSERT INTO schema_version (version, name) VALUES (?, 'from_the_future')")
+            .bind(latest_version() + 1)
+            .execute(&snapshot.pool)
+            .await
+            .unwrap();
+        snapshot.pool.close().await;
+
+        let database = open(&live).await;
+        let result = replace_database_file(&database, &snapshot.path).await;
+        assert_eq!(result.unwrap_err().kind(), "conflict");
+        // The live database is still open and unchanged
+        assert_eq!(project_names(&database).await, vec!["Live"]);
+    }
+}
@@ -0,0 +1,179 @@
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use std::fmt;
+
+// Error type shared by every service and Tauri command.
+// Serialized over IPC as `{ kind, message, details }` so the frontend can tell
+// e.g. a duplicate project name apart from a locked database.
+#[derive(Debug)]
+pub enum MissionControlError {
+    NotFound(String),
+    Conflict(String),
+    Validation(String),
+    Database { message: String, details: Option<String> },
+    Io { message: String, details: Option<String> },
+    NotInitialized(String),
+}
+
+impl MissionControlError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::NotFound(_) => "not_found",
+            Self::Conflict(_) => "conflict",
+            Self::Validation(_) => "validation",
+            Self::Database { .. } => "database",
+            Self::Io { .. } => "io",
+            Self::NotInitialized(_) => "not_initialized",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::NotFound(message)
+            | Self::Conflict(message)
+            | Self::Validation(message)
+            | Self::NotInitialized(message) => message,
+            Self::Database { message, .. } | Self::Io { message, .. } => message,
+        }
+    }
+
+    pub fn details(&self) -> Option<&str> {
+        match self {
+            Self::Database { details, .. } | Self::Io { details, .. } => details.as_deref(),
+            _ => None,
+        }
+    }
+
+    // Map a sqlx error to the most specific kind, prefixing it with what we were doing.
+    // Constraint violations become Conflict and missing rows become NotFound.
+    pub fn from_sqlx(context: &str, error: sqlx::Error) -> Self {
+        let mapped = match &error {
+            sqlx::Error::RowNotFound => Self::NotFound(format!("{}: record not found", context)),
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+                Self::Conflict(format!("{}: a record with the same value already exists", context))
+            }
+            sqlx::Error::Database(db_error) if db_error.is_foreign_key_violation() => {
+                Self::Conflict(format!("{}: referenced record does not exist", context))
+            }
+            sqlx::Error::Database(db_error) => Self::Database {
+                message: context.to_string(),
+                details: Some(match db_error.code() {
+                    Some(code) => format!("[{}] {}", code, db_error.message()),
+                    None => db_error.message().to_string(),
+                }),
+            },
+            other => Self::Database {
+                message: context.to_string(),
+                details: Some(other.to_string()),
+            },
+        };
+        println!("{}", mapped);
+        mapped
+    }
+
+    pub fn from_io(context: &str, error: std::io::Error) -> Self {
+        let mapped = Self::Io {
+            message: context.to_string(),
+            details: Some(error.to_string()),
+        };
+        println!("{}", mapped);
+        mapped
+    }
+}
+
+impl fmt::Display for MissionControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.details() {
+            Some(details) => write!(f, "{}: {}", self.message(), details),
+            None => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for MissionControlError {}
+
+impl From<sqlx::Error> for MissionControlError {
+    fn from(error: sqlx::Error) -> Self {
+        Self::from_sqlx("Database error", error)
+    }
+}
+
+impl From<std::io::Error> for MissionControlError {
+    fn from(error: std::io::Error) -> Self {
+        Self::from_io("I/O error", error)
+    }
+}
+
+impl Serialize for MissionControlError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("MissionControlError", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", self.message())?;
+        state.serialize_field("details", &self.details())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::test_support::{empty_pool, exec};
+
+    #[test]
+    fn serializes_kind_message_and_details() {
+        let error = MissionControlError::NotFound("Todo 7 not found".to_string());
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({ "kind": "not_found", "message": "Todo 7 not found", "details": null })
+        );
+
+        let error = MissionControlError::Database {
+            message: "Failed to fetch projects".to_string(),
+            details: Some("[5] database is locked".to_string()),
+        };
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({ "kind": "database", "message": "Failed to fetch projects", "details": "[5] database is locked" })
+        );
+
+        for (error, kind) in [
+            (MissionControlError::Conflict(String::new()), "conflict"),
+            (MissionControlError::Validation(String::new()), "validation"),
+            (MissionControlError::Io { message: String::new(), details: None }, "io"),
+            (MissionControlError::NotInitialized(String::new()), "not_initialized"),
+        ] {
+            let value = serde_json::to_value(&error).unwrap();
+            assert_eq!(value["kind"], kind);
+            assert_eq!(value.as_object().unwrap().len(), 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn maps_sqlx_errors_to_kinds() {
+        let pool = empty_pool().await;
+        exec(
+            r#"
+            CREATE TABLE parents (id INTEGER PRIMARY KEY, name TEXT UNIQUE);
+            CREATE TABLE children (parent_id INTEGER REFERENCES parents(id));
+            INSERT INTO parents (id, name) VALUES (1, 'taken');
+            "#,
+            &pool,
+        )
+        .await;
+
+        let duplicate = sqlx::query("INSERT INTO parents (id, name) VALUES (2, 'taken')").execute(&pool).await.unwrap_err();
+        assert_eq!(MissionControlError::from_sqlx("Failed to add", duplicate).kind(), "conflict");
+
+        let orphan = sqlx::query("INSERT INTO children (parent_id) VALUES (9)").execute(&pool).await.unwrap_err();
+        assert_eq!(MissionControlError::from_sqlx("Failed to add", orphan).kind(), "conflict");
+
+        let missing = sqlx::query("SELECT id FROM parents WHERE id = 9").fetch_one(&pool).await.map(|_| ()).unwrap_err();
+        assert_eq!(MissionControlError::from_sqlx("Failed to read", missing).kind(), "not_found");
+
+        let broken = sqlx::query("SELECT missing_column FROM parents").fetch_all(&pool).await.map(|_| ()).unwrap_err();
+        let error = MissionControlError::from_sqlx("Failed to read", broken);
+        assert_eq!(error.kind(), "database");
+        assert_eq!(error.message(), "Failed to read");
+        assert!(error.details().unwrap().contains("no such column"));
+    }
+}
@@ -0,0 +1,89 @@
+// RFC 4180 CSV. Fields are quoted only when they contain the delimiter, a quote or
+// a line break; records end with CRLF. Fields that a spreadsheet would run as a
+// formula get a leading `'`, so an exported todo can't execute anything.
+
+pub const DEFAULT_DELIMITER: char = ',';
+
+pub fn valid_delimiter(delimiter: char) -> bool {
+    !matches!(delimiter, '"' | '\r' | '\n')
+}
+
+fn starts_formula(value: &str) -> bool {
+    value.starts_with(['=', '+', '-', '@', '\t', '\r'])
+}
+
+pub fn escape_field(value: &str, delimiter: char) -> String {
+    let value = if starts_formula(value) { format!("'{}", value) } else { value.to_string() };
+    if value.contains([delimiter, '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+pub fn render_record(fields: &[String], delimiter: char) -> String {
+    let mut record = fields
+        .iter()
+        .map(|field| escape_field(field, delimiter))
+        .collect::<Vec<_>>()
+        .join(&delimiter.to_string());
+    record.push_str("\r\n");
+    record
+}
+
+pub fn render(header: &[&str], rows: &[Vec<String>], delimiter: char) -> String {
+    let header: Vec<String> = header.iter().map(|name| name.to_string()).collect();
+    let mut out = render_record(&header, delimiter);
+    for row in rows {
+        out.push_str(&render_record(row, delimiter));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn fields_are_quoted_only_when_needed() {
+        let record = render_record(&fields(&["plain", "a,b", "say \"hi\"", "two\nlines", "cr\rhere", ""]), ',');
+        assert_eq!(record, "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\"cr\rhere\",\r\n");
+    }
+
+    #[test]
+    fn a_custom_delimiter_changes_what_needs_quotes() {
+        let record = render_record(&fields(&["a,b", "a;b", "P2"]), ';');
+        assert_eq!(record, "a,b;\"a;b\";P2\r\n");
+
+        let out = render(&["id", "text"], &[fields(&["1", "Buy milk"]), fields(&["2", "Call; then email"])], ';');
+        assert_eq!(out, "id;text\r\n1;Buy milk\r\n2;\"Call; then email\"\r\n");
+    }
+
+    #[test]
+    fn quotes_and_line_breaks_cannot_be_delimiters() {
+        for delimiter in ['"', '\r', '\n'] {
+            assert!(!valid_delimiter(delimiter), "{:?}", delimiter);
+        }
+        for delimiter in [',', ';', '\t', '|'] {
+            assert!(valid_delimiter(delimiter), "{:?}", delimiter);
+        }
+    }
+
+    #[test]
+    fn formulas_are_neutralized() {
+        let record = render_record(
+            &fields(&["=1+1", "+1", "-2", "@SUM(A1)", "\tTab", "=HYPERLINK(\"x\",\"y\")", "a=b", "2-1"]),
+            ',',
+        );
+        assert_eq!(
+            record,
+            "'=1+1,'+1,'-2,'@SUM(A1),'\tTab,\"'=HYPERLINK(\"\"x\"\",\"\"y\"\")\",a=b,2-1\r\n"
+        );
+        // A leading line break is both neutralized and quoted
+        assert_eq!(escape_field("\r=1", ','), "\"'\r=1\"");
+    }
+}
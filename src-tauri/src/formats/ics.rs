@@ -0,0 +1,360 @@
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use crate::services::todos;
+use crate::types::{IcsExportOptions, Project, Todo};
+
+const PRODID: &str = "-//MissionControl//Focus Plan//EN";
+const MAX_LINE_OCTETS: usize = 75;
+
+// Block start given either in UTC or as floating local time (no time zone), which
+// calendar apps show at the same wall-clock time wherever they are
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanStart {
+    Utc(DateTime<Utc>),
+    Floating(NaiveDateTime),
+}
+
+// Accepts RFC 3339 (`2024-03-01T09:00:00+01:00`) or a local `2024-03-01T09:00[:00]`
+pub fn parse_start(value: &str) -> Option<PlanStart> {
+    let value = value.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Some(PlanStart::Utc(at.with_timezone(&Utc)));
+    }
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(PlanStart::Floating)
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedBlock<'a> {
+    pub todo: &'a Todo,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+// Lay the todos out back to back from `start`, each lasting its `time` in minutes,
+// with `break_minutes` between consecutive blocks
+pub fn plan_blocks(todos: &[Todo], start: NaiveDateTime, break_minutes: u32) -> Vec<PlannedBlock<'_>> {
+    let mut cursor = start;
+    let mut blocks = Vec::with_capacity(todos.len());
+    for todo in todos {
+        let end = cursor + Duration::minutes(i64::from(todo.time.max(0)));
+        blocks.push(PlannedBlock { todo, start: cursor, end });
+        cursor = end + Duration::minutes(i64::from(break_minutes));
+    }
+    blocks
+}
+
+// TEXT values escape backslashes, semicolons, commas and newlines (RFC 5545 3.3.11)
+pub fn escape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// Content lines are folded at 75 octets without splitting a UTF-8 character, and
+// end with CRLF (RFC 5545 3.1)
+pub fn fold_line(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + 8);
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            octets = 1;
+        }
+        out.push(c);
+        octets += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+// When a todo is due: a whole day for a date-only due date, which is stored as the
+// end of that day in local time, or a moment in UTC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Due {
+    Day(NaiveDate),
+    At(NaiveDateTime),
+}
+
+pub fn todo_due<Tz: TimeZone>(todo: &Todo, zone: &Tz) -> Option<Due> {
+    let due_at = todo.due_at.as_deref().and_then(todos::parse_due_at)?;
+    let local = zone.from_utc_datetime(&due_at).naive_local();
+    if local.time() == NaiveTime::from_hms_opt(23, 59, 59)? {
+        Some(Due::Day(local.date()))
+    } else {
+        Some(Due::At(due_at))
+    }
+}
+
+fn format_time(at: NaiveDateTime, utc: bool) -> String {
+    if utc {
+        at.format("%Y%m%dT%H%M%SZ").to_string()
+    } else {
+        at.format("%Y%m%dT%H%M%S").to_string()
+    }
+}
+
+// Render the plan as an iCalendar file: one VEVENT per block and, with
+// `include_tasks`, a VTODO per todo, due when the todo is (all day for a date-only
+// due date) or else at the end of its block. `stamp` is the DTSTAMP and `zone` the
+// time zone due dates were entered in, passed in so the output depends only on the
+// arguments.
+pub fn render_plan<Tz: TimeZone>(
+    project: &Project,
+    todos: &[Todo],
+    start: PlanStart,
+    options: &IcsExportOptions,
+    stamp: DateTime<Utc>,
+    zone: &Tz,
+) -> String {
+    let (start_at, utc) = match start {
+        PlanStart::Utc(at) => (at.naive_utc(), true),
+        PlanStart::Floating(at) => (at, false),
+    };
+    let dtstamp = format_time(stamp.naive_utc(), true);
+    let plan_id = start_at.format("%Y%m%dT%H%M%S");
+
+    let mut lines: Vec<String> = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(&project.name)),
+    ];
+
+    for block in plan_blocks(todos, start_at, options.break_minutes) {
+        let summary = escape_text(&block.todo.text);
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:focus-{}-{}@missioncontrol", block.todo.id, plan_id),
+            format!("DTSTAMP:{}", dtstamp),
+            format!("DTSTART:{}", format_time(block.start, utc)),
+            format!("DTEND:{}", format_time(block.end, utc)),
+            format!("SUMMARY:{}", summary),
+            format!("CATEGORIES:{}", escape_text(&project.name)),
+            "TRANSP:OPAQUE".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+        if options.include_tasks {
+            lines.extend([
+                "BEGIN:VTODO".to_string(),
+                format!("UID:todo-{}-{}@missioncontrol", block.todo.id, plan_id),
+                format!("DTSTAMP:{}", dtstamp),
+            ]);
+            // DTSTART has to be before DUE and of the same type, so it is only kept
+            // for the block-end default; the VEVENT carries the planned start anyway
+            match todo_due(block.todo, zone) {
+                Some(Due::Day(day)) => lines.push(format!("DUE;VALUE=DATE:{}", day.format("%Y%m%d"))),
+                Some(Due::At(at)) => lines.push(format!("DUE:{}", format_time(at, true))),
+                None => lines.extend([
+                    format!("DTSTART:{}", format_time(block.start, utc)),
+                    format!("DUE:{}", format_time(block.end, utc)),
+                ]),
+            }
+            lines.extend([
+                format!("SUMMARY:{}", summary),
+                format!("CATEGORIES:{}", escape_text(&project.name)),
+                "STATUS:NEEDS-ACTION".to_string(),
+                "END:VTODO".to_string(),
+            ]);
+        }
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold_line(line)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn project() -> Project {
+        Project {
+            id: 1,
+            name: "Launch, v2; final".to_string(),
+            description: None,
+            created_at: "2024-03-01 08:00:00".to_string(),
+            color: None,
+            icon: None,
+            archived_at: None,
+        }
+    }
+
+    fn todo(id: i64, text: &str, minutes: i32, due_at: Option<&str>) -> Todo {
+        Todo {
+            id,
+            text: text.to_string(),
+            completed: false,
+            time: minutes,
+            created_at: "2024-03-01 08:00:00".to_string(),
+            project_id: 1,
+            parent_id: None,
+            priority: 2,
+            due_at: due_at.map(str::to_string),
+            recurrence: None,
+            tags: Vec::new(),
+            position: id as f64,
+            depth: 0,
+            total_time: minutes,
+        }
+    }
+
+    fn stamp() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 2, 28, 12, 0, 0).unwrap()
+    }
+
+    // Due dates entered at UTC+01:00
+    fn zone() -> FixedOffset {
+        FixedOffset::east_opt(3600).unwrap()
+    }
+
+    fn render(todos: &[Todo], start: &str, include_tasks: bool) -> String {
+        let options = IcsExportOptions { break_minutes: 5, include_tasks };
+        render_plan(&project(), todos, parse_start(start).unwrap(), &options, stamp(), &zone())
+    }
+
+    // Content lines with folding undone
+    fn unfolded(ics: &str) -> Vec<String> {
+        ics.replace("\r\n ", "").split("\r\n").filter(|line| !line.is_empty()).map(str::to_string).collect()
+    }
+
+    // Properties of each component of `kind`, in order
+    fn components(ics: &str, kind: &str) -> Vec<Vec<String>> {
+        let mut found = Vec::new();
+        let mut current: Option<Vec<String>> = None;
+        for line in unfolded(ics) {
+            if line == format!("BEGIN:{}", kind) {
+                current = Some(Vec::new());
+            } else if line == format!("END:{}", kind) {
+                found.extend(current.take());
+            } else if let Some(properties) = current.as_mut() {
+                properties.push(line);
+            }
+        }
+        found
+    }
+
+    fn property<'a>(component: &'a [String], name: &str) -> Vec<&'a str> {
+        component
+            .iter()
+            .filter(|line| line.split([':', ';']).next() == Some(name))
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn lines_end_with_crlf() {
+        let ics = render(&[todo(1, "Write\nthe plan", 25, None)], "2024-03-01T09:00", true);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(!ics.replace("\r\n", "").contains(['\r', '\n']));
+    }
+
+    #[test]
+    fn long_lines_fold_at_75_octets() {
+        assert_eq!(fold_line(&"a".repeat(75)), format!("{}\r\n", "a".repeat(75)));
+        assert_eq!(fold_line(&"a".repeat(76)), format!("{}\r\n a\r\n", "a".repeat(75)));
+
+        // A two-octet character that would straddle the limit moves to the next line
+        let line = format!("{}é{}", "a".repeat(74), "b".repeat(80));
+        let folded = fold_line(&line);
+        let physical: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(physical[0], "a".repeat(74));
+        assert!(physical[1].starts_with(" é"));
+        assert!(physical.iter().all(|line| line.len() <= MAX_LINE_OCTETS));
+        assert_eq!(folded.replace("\r\n ", "").trim_end_matches("\r\n"), line);
+
+        let text = "Prepare the quarterly review deck – résumé of wins, misses and next steps for everyone";
+        let ics = render(&[todo(1, text, 25, None)], "2024-03-01T09:00", false);
+        assert!(ics.split("\r\n").all(|line| line.len() <= MAX_LINE_OCTETS));
+        let events = components(&ics, "VEVENT");
+        assert_eq!(property(&events[0], "SUMMARY"), vec![format!("SUMMARY:{}", escape_text(text))]);
+    }
+
+    #[test]
+    fn text_values_are_escaped() {
+        assert_eq!(escape_text(r"a,b;c\d"), r"a\,b\;c\\d");
+        assert_eq!(escape_text("one\ntwo\r\nthree"), r"one\ntwo\nthree");
+
+        let ics = render(&[todo(1, "Call Bob, Sue; ask re: C:\\temp\nthen email", 25, None)], "2024-03-01T09:00", false);
+        let lines = unfolded(&ics);
+        assert!(lines.contains(&r"X-WR-CALNAME:Launch\, v2\; final".to_string()));
+        let events = components(&ics, "VEVENT");
+        assert_eq!(property(&events[0], "SUMMARY"), vec![r"SUMMARY:Call Bob\, Sue\; ask re: C:\\temp\nthen email"]);
+        assert_eq!(property(&events[0], "CATEGORIES"), vec![r"CATEGORIES:Launch\, v2\; final"]);
+    }
+
+    #[test]
+    fn every_component_has_a_dtstamp_and_a_unique_uid() {
+        let todos = [todo(1, "First", 25, None), todo(2, "Second", 50, Some("2024-03-02 10:00:00"))];
+        let ics = render(&todos, "2024-03-01T09:00:00+01:00", true);
+
+        let mut uids = Vec::new();
+        for kind in ["VEVENT", "VTODO"] {
+            let found = components(&ics, kind);
+            assert_eq!(found.len(), 2);
+            for component in found {
+                assert_eq!(property(&component, "DTSTAMP"), vec!["DTSTAMP:20240228T120000Z"]);
+                let uid = property(&component, "UID");
+                assert_eq!(uid.len(), 1);
+                assert!(uid[0].ends_with("@missioncontrol"));
+                uids.push(uid[0].to_string());
+            }
+        }
+        uids.sort();
+        uids.dedup();
+        assert_eq!(uids.len(), 4);
+    }
+
+    #[test]
+    fn blocks_follow_each_other_with_breaks() {
+        let todos = [todo(1, "First", 25, None), todo(2, "Second", 50, None)];
+
+        let floating = components(&render(&todos, "2024-03-01T09:00", false), "VEVENT");
+        assert_eq!(property(&floating[0], "DTSTART"), vec!["DTSTART:20240301T090000"]);
+        assert_eq!(property(&floating[0], "DTEND"), vec!["DTEND:20240301T092500"]);
+        assert_eq!(property(&floating[1], "DTSTART"), vec!["DTSTART:20240301T093000"]);
+        assert_eq!(property(&floating[1], "DTEND"), vec!["DTEND:20240301T102000"]);
+
+        let utc = components(&render(&todos, "2024-03-01T09:00:00+01:00", false), "VEVENT");
+        assert_eq!(property(&utc[0], "DTSTART"), vec!["DTSTART:20240301T080000Z"]);
+        assert_eq!(property(&utc[1], "DTEND"), vec!["DTEND:20240301T092000Z"]);
+    }
+
+    #[test]
+    fn due_dates_are_all_day_or_timed() {
+        let todos = [
+            // A date-only due date: the end of 2024-03-05 at UTC+01:00
+            todo(1, "Day", 25, Some("2024-03-05 22:59:59")),
+            todo(2, "Timed", 25, Some("2024-03-05 15:30:00")),
+            todo(3, "Undated", 25, None),
+        ];
+        let tasks = components(&render(&todos, "2024-03-01T09:00", true), "VTODO");
+
+        assert_eq!(property(&tasks[0], "DUE"), vec!["DUE;VALUE=DATE:20240305"]);
+        assert!(property(&tasks[0], "DTSTART").is_empty());
+
+        assert_eq!(property(&tasks[1], "DUE"), vec!["DUE:20240305T153000Z"]);
+        assert!(property(&tasks[1], "DTSTART").is_empty());
+
+        // Without a due date the task is due when its block ends
+        assert_eq!(property(&tasks[2], "DTSTART"), vec!["DTSTART:20240301T100000"]);
+        assert_eq!(property(&tasks[2], "DUE"), vec!["DUE:20240301T102500"]);
+
+        assert_eq!(todo_due(&todos[0], &zone()), Some(Due::Day(NaiveDate::from_ymd_opt(2024, 3, 5).unwrap())));
+        // In another zone the same moment is no longer the end of a day
+        assert!(matches!(todo_due(&todos[0], &Utc), Some(Due::At(_))));
+    }
+}
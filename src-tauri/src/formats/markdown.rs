@@ -0,0 +1,289 @@
+use crate::types::{MarkdownExportOptions, Project, ProjectReport, Todo};
+
+// Todo text is written on a single line so it stays one checklist item
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn format_minutes(minutes: f64) -> String {
+    if minutes.fract().abs() < f64::EPSILON {
+        format!("{}", minutes as i64)
+    } else {
+        format!("{:.1}", minutes)
+    }
+}
+
+// Render a project as Markdown. The output depends only on the arguments, so the
+// same data always produces byte-identical text.
+pub fn render_project(
+    project: &Project,
+    todos: &[Todo],
+    report: Option<&ProjectReport>,
+    board_notes: &[String],
+    options: &MarkdownExportOptions,
+) -> String {
+    let mut out = String::new();
+
+    out.push_str(&format!("# {}\n", single_line(&project.name)));
+    if let Some(description) = project.description.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+        out.push('\n');
+        out.push_str(description);
+        out.push('\n');
+    }
+
+    out.push_str("\n## Todos\n\n");
+    let visible: Vec<&Todo> = todos
+        .iter()
+        .filter(|todo| options.include_completed || !todo.completed)
+        .collect();
+    if visible.is_empty() {
+        out.push_str("_No todos._\n");
+    }
+    // Subtasks are indented under their parent, which shows its rolled-up time
+    for todo in visible {
+        out.push_str(&format!(
+            "{}- [{}] {} ({}m)\n",
+            "  ".repeat(todo.depth as usize),
+            if todo.completed { "x" } else { " " },
+            single_line(&todo.text),
+            todo.total_time
+        ));
+    }
+
+    if options.include_time_summary {
+        // Each todo's own time, as in the time report; totals would count subtasks twice
+        let estimated: i64 = todos.iter().map(|todo| i64::from(todo.time)).sum();
+        let completed: i64 = todos.iter().filter(|todo| todo.completed).map(|todo| i64::from(todo.time)).sum();
+
+        out.push_str("\n## Time summary\n\n");
+        out.push_str("| | Minutes |\n|---|---:|\n");
+        out.push_str(&format!("| Estimated | {} |\n", estimated));
+        out.push_str(&format!("| Completed | {} |\n", completed));
+        out.push_str(&format!("| Remaining | {} |\n", estimated - completed));
+        if let Some(report) = report.filter(|report| report.session_count > 0) {
+            out.push_str(&format!("| Tracked | {} |\n", format_minutes(report.actual_minutes)));
+        }
+        out.push_str(&format!(
+            "\n{} of {} todos done.\n",
+            todos.iter().filter(|todo| todo.completed).count(),
+            todos.len()
+        ));
+    }
+
+    if options.include_board && !board_notes.is_empty() {
+        out.push_str("\n## Board\n\n");
+        for note in board_notes {
+            out.push_str(&format!("- {}\n", single_line(note)));
+        }
+    }
+
+    out
+}
+
+// Text written on an Excalidraw board, in element order, skipping deleted elements
+pub fn board_text(elements_json: &str) -> Vec<String> {
+    let elements: Vec<serde_json::Value> = serde_json::from_str(elements_json).unwrap_or_default();
+
+    elements
+        .iter()
+        .filter(|element| element.get("type").and_then(|t| t.as_str()) == Some("text"))
+        .filter(|element| !element.get("isDeleted").and_then(|d| d.as_bool()).unwrap_or(false))
+        .filter_map(|element| element.get("text").and_then(|t| t.as_str()))
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .collect()
+}
+
+// One `- [ ]` / `- [x]` line from a pasted checklist
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChecklistItem {
+    pub line: usize,
+    pub depth: usize,
+    pub text: String,
+    pub completed: bool,
+    pub minutes: Option<i32>,
+}
+
+// Parse a duration such as `25m`, `45min`, `1h`, `1h30m` or `90 minutes` into minutes
+pub fn parse_duration(value: &str) -> Option<i32> {
+    let compact: String = value.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+    let mut chars = compact.chars().peekable();
+    let mut total = 0i32;
+    let mut parts = 0;
+
+    while chars.peek().is_some() {
+        let digits: String = std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_digit())).collect();
+        let unit: String = std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_alphabetic())).collect();
+        let amount: i32 = digits.parse().ok()?;
+        let factor = match unit.as_str() {
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60,
+            "m" | "min" | "mins" | "minute" | "minutes" => 1,
+            _ => return None,
+        };
+        total = total.checked_add(amount.checked_mul(factor)?)?;
+        parts += 1;
+    }
+
+    if parts > 0 && total > 0 {
+        Some(total)
+    } else {
+        None
+    }
+}
+
+// Split a trailing `(25m)` or `@45min` duration off a todo's text
+fn split_duration(text: &str) -> (String, Option<i32>) {
+    let trimmed = text.trim_end();
+
+    if let Some(inner) = trimmed.strip_suffix(')') {
+        if let Some(open) = inner.rfind('(') {
+            if let Some(minutes) = parse_duration(&inner[open + 1..]) {
+                return (inner[..open].trim_end().to_string(), Some(minutes));
+            }
+        }
+    }
+
+    if let Some(at) = trimmed.rfind('@') {
+        let preceded_by_space = at == 0 || trimmed[..at].ends_with(char::is_whitespace);
+        if preceded_by_space {
+            if let Some(minutes) = parse_duration(&trimmed[at + 1..]) {
+                return (trimmed[..at].trim_end().to_string(), Some(minutes));
+            }
+        }
+    }
+
+    (trimmed.to_string(), None)
+}
+
+// Checklist items in `text`. Nesting depth comes from indentation relative to the
+// enclosing items; lines that aren't checklist items are ignored.
+pub fn parse_checklist(text: &str) -> Vec<ChecklistItem> {
+    let mut items = Vec::new();
+    let mut indent_stack: Vec<usize> = Vec::new();
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let indent: usize = raw_line
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum();
+        let line = raw_line.trim_start();
+
+        let rest = match line
+            .strip_prefix("- ")
+            .or_else(|| line.strip_prefix("* "))
+            .or_else(|| line.strip_prefix("+ "))
+        {
+            Some(rest) => rest.trim_start(),
+            None => continue,
+        };
+        let (completed, body) = if let Some(body) = rest.strip_prefix("[ ]") {
+            (false, body)
+        } else if let Some(body) = rest.strip_prefix("[x]").or_else(|| rest.strip_prefix("[X]")) {
+            (true, body)
+        } else {
+            continue;
+        };
+
+        let (text, minutes) = split_duration(body.trim());
+        if text.is_empty() {
+            continue;
+        }
+
+        while indent_stack.last().is_some_and(|&last| last >= indent) {
+            indent_stack.pop();
+        }
+        let depth = indent_stack.len();
+        indent_stack.push(indent);
+
+        items.push(ChecklistItem {
+            line: index + 1,
+            depth,
+            text,
+            completed,
+            minutes,
+        });
+    }
+
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(id: i64, parent_id: Option<i64>, text: &str, minutes: i32, completed: bool, total_time: i32) -> Todo {
+        Todo {
+            id,
+            text: text.to_string(),
+            completed,
+            time: minutes,
+            created_at: "2024-05-01 09:00:00".to_string(),
+            project_id: 1,
+            parent_id,
+            priority: 2,
+            due_at: None,
+            recurrence: None,
+            tags: Vec::new(),
+            position: id as f64 * 1024.0,
+            depth: u32::from(parent_id.is_some()),
+            total_time,
+        }
+    }
+
+    // A project with a nested list, a tracked time report and whiteboard notes,
+    // with the todos in the depth-first order `get_todos_by_project` returns
+    fn launch_project() -> (Project, Vec<Todo>, ProjectReport, Vec<String>) {
+        let project = Project {
+            id: 1,
+            name: "Product   launch".to_string(),
+            description: Some("  Everything for the v2 launch.\nOwner: Sam  ".to_string()),
+            created_at: "2024-05-01 09:00:00".to_string(),
+            color: Some("#3b82f6".to_string()),
+            icon: None,
+            archived_at: None,
+        };
+        let todos = vec![
+            todo(1, None, "Launch plan", 25, false, 100),
+            todo(2, Some(1), "Write the\nannouncement", 45, true, 45),
+            todo(3, Some(1), "Review copy", 30, false, 30),
+            todo(4, None, "Book venue", 20, true, 20),
+            todo(5, None, "Order snacks", 15, false, 15),
+        ];
+        let report = ProjectReport {
+            project_id: 1,
+            project_name: project.name.clone(),
+            from: None,
+            to: None,
+            todos: Vec::new(),
+            total_todos: 5,
+            completed_todos: 2,
+            session_count: 3,
+            estimated_minutes: 110,
+            actual_minutes: 62.5,
+            overrun_ratio: None,
+            accuracy_score: None,
+        };
+        let notes = vec!["Budget: $2k".to_string(), "Ask   design\nfor slides".to_string()];
+        (project, todos, report, notes)
+    }
+
+    #[test]
+    fn render_matches_golden_file() {
+        let (project, todos, report, notes) = launch_project();
+        let options = MarkdownExportOptions { include_completed: true, include_time_summary: true, include_board: true };
+
+        let rendered = render_project(&project, &todos, Some(&report), &notes, &options);
+        assert_eq!(rendered, include_str!("testdata/product_launch.md"));
+        assert_eq!(rendered, render_project(&project, &todos, Some(&report), &notes, &options));
+    }
+
+    #[test]
+    fn render_without_completed_or_extras_matches_golden_file() {
+        let (project, todos, report, notes) = launch_project();
+        let options = MarkdownExportOptions { include_completed: false, ..Default::default() };
+
+        let rendered = render_project(&project, &todos, Some(&report), &notes, &options);
+        assert_eq!(rendered, include_str!("testdata/product_launch_open.md"));
+    }
+}
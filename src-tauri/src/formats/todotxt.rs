@@ -0,0 +1,339 @@
+use chrono::{Local, NaiveDate, TimeZone};
+use crate::formats::markdown;
+use crate::services::todos::{self, DEFAULT_PRIORITY, LOWEST_PRIORITY};
+use crate::types::{Project, Todo};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+// One task line of a todo.txt file (http://todotxt.org). `text` is the description
+// with `+project` tags, the `est:` estimate and the `due:` date removed; `@contexts` and other
+// `key:value` extras stay in it so nothing is lost on import.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TodoTxtTask {
+    pub completed: bool,
+    pub priority: Option<char>,
+    pub completion_date: Option<NaiveDate>,
+    pub creation_date: Option<NaiveDate>,
+    pub text: String,
+    pub projects: Vec<String>,
+    pub contexts: Vec<String>,
+    pub extras: Vec<(String, String)>,
+}
+
+impl TodoTxtTask {
+    pub fn extra(&self, key: &str) -> Option<&str> {
+        self.extras
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Due time in UTC from `due:2024-03-01`, the end of that day in local time
+    pub fn due_at(&self) -> Option<String> {
+        self.extra("due").and_then(todos::parse_due_at).map(todos::format_due_at)
+    }
+
+    // Planned minutes from `est:25`, also accepting `est:1h30m`
+    pub fn estimate(&self) -> Option<i32> {
+        let value = self.extra("est")?;
+        match value.parse::<i32>() {
+            Ok(minutes) if minutes > 0 => Some(minutes),
+            Ok(_) => None,
+            Err(_) => markdown::parse_duration(value),
+        }
+    }
+}
+
+// (A), (B) and (C) are P0, P1 and P2, and (D) or lower is P3; no priority is the
+// default P2
+pub fn priority_from_letter(letter: Option<char>) -> u8 {
+    match letter {
+        Some('A') => 0,
+        Some('B') => 1,
+        Some('C') => 2,
+        Some(_) => LOWEST_PRIORITY,
+        None => DEFAULT_PRIORITY,
+    }
+}
+
+pub fn priority_letter(priority: u8) -> Option<char> {
+    match priority {
+        0 => Some('A'),
+        1 => Some('B'),
+        2 => Some('C'),
+        _ => Some('D'),
+    }
+}
+
+// Fields split out of the description into their own columns; other extras stay in it
+fn is_field(key: &str) -> bool {
+    key.eq_ignore_ascii_case("est") || key.eq_ignore_ascii_case("due")
+}
+
+// todo.txt project tags can't contain spaces, so `Release 1.2` becomes `+Release_1.2`
+pub fn project_tag(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+fn parse_date(token: &str) -> Option<NaiveDate> {
+    if token.len() != 10 {
+        return None;
+    }
+    NaiveDate::parse_from_str(token, DATE_FORMAT).ok()
+}
+
+fn parse_priority(token: &str) -> Option<char> {
+    let mut chars = token.chars();
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some('('), Some(letter), Some(')'), None) if letter.is_ascii_uppercase() => Some(letter),
+        _ => None,
+    }
+}
+
+// `key:value` with no spaces or further colons; `http://...` is not an extra
+fn parse_extra(token: &str) -> Option<(String, String)> {
+    let (key, value) = token.split_once(':')?;
+    if key.is_empty() || value.is_empty() || value.contains(':') || value.starts_with("//") {
+        return None;
+    }
+    Some((key.to_string(), value.to_string()))
+}
+
+// Parse one line; blank lines yield `None`
+pub fn parse_line(line: &str) -> Option<TodoTxtTask> {
+    let mut tokens = line.split_whitespace().peekable();
+    tokens.peek()?;
+
+    let completed = tokens.next_if_eq(&"x").is_some();
+    let priority = if completed {
+        None
+    } else {
+        tokens.next_if(|token| parse_priority(token).is_some()).and_then(parse_priority)
+    };
+
+    // A completed task may carry a completion date before the creation date
+    let first_date = tokens.next_if(|token| parse_date(token).is_some()).and_then(parse_date);
+    let (completion_date, creation_date) = if completed && first_date.is_some() {
+        let second_date = tokens.next_if(|token| parse_date(token).is_some()).and_then(parse_date);
+        (first_date, second_date)
+    } else {
+        (None, first_date)
+    };
+
+    let mut words = Vec::new();
+    let mut projects = Vec::new();
+    let mut contexts = Vec::new();
+    let mut extras = Vec::new();
+    for token in tokens {
+        if let Some(project) = token.strip_prefix('+').filter(|p| !p.is_empty()) {
+            projects.push(project.to_string());
+            continue;
+        }
+        if let Some(context) = token.strip_prefix('@').filter(|c| !c.is_empty()) {
+            contexts.push(context.to_string());
+        } else if let Some((key, value)) = parse_extra(token) {
+            let field = is_field(&key);
+            extras.push((key, value));
+            if field {
+                continue;
+            }
+        }
+        words.push(token);
+    }
+
+    Some(TodoTxtTask {
+        completed,
+        priority,
+        completion_date,
+        creation_date,
+        text: words.join(" "),
+        projects,
+        contexts,
+        extras,
+    })
+}
+
+// Tasks in `text` with their 1-based line numbers
+pub fn parse(text: &str) -> Vec<(usize, TodoTxtTask)> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(index, line)| parse_line(line).map(|task| (index + 1, task)))
+        .collect()
+}
+
+pub fn from_todo(todo: &Todo, project: &Project) -> TodoTxtTask {
+    let text = todo.text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut extras = vec![("est".to_string(), todo.time.to_string())];
+    // todo.txt due dates are plain dates, so the due time is shown on its local day
+    if let Some(due_at) = todo.due_at.as_deref().and_then(todos::parse_due_at) {
+        let local = Local.from_utc_datetime(&due_at);
+        extras.push(("due".to_string(), local.format(DATE_FORMAT).to_string()));
+    }
+    TodoTxtTask {
+        completed: todo.completed,
+        priority: priority_letter(todo.priority),
+        completion_date: None,
+        creation_date: todo.created_at.get(..10).and_then(parse_date),
+        contexts: text
+            .split(' ')
+            .filter_map(|word| word.strip_prefix('@'))
+            .filter(|context| !context.is_empty())
+            .map(str::to_string)
+            .collect(),
+        text,
+        projects: vec![project_tag(&project.name)],
+        extras,
+    }
+}
+
+pub fn render_task(task: &TodoTxtTask) -> String {
+    let mut parts: Vec<String> = Vec::new();
+
+    if task.completed {
+        parts.push("x".to_string());
+    } else if let Some(priority) = task.priority {
+        parts.push(format!("({})", priority));
+    }
+    // todo.txt only allows a creation date on a completed task after its completion
+    // date, and we don't record when todos were completed
+    match (task.completed, task.completion_date, task.creation_date) {
+        (true, Some(completed_on), created_on) => {
+            parts.push(completed_on.format(DATE_FORMAT).to_string());
+            if let Some(created_on) = created_on {
+                parts.push(created_on.format(DATE_FORMAT).to_string());
+            }
+        }
+        (false, _, Some(created_on)) => parts.push(created_on.format(DATE_FORMAT).to_string()),
+        _ => {}
+    }
+
+    if !task.text.is_empty() {
+        parts.push(task.text.clone());
+    }
+    parts.extend(task.projects.iter().map(|project| format!("+{}", project)));
+    // Other extras are still part of `text`; only the fields were split out
+    parts.extend(
+        task.extras
+            .iter()
+            .filter(|(key, _)| is_field(key))
+            .map(|(key, value)| format!("{}:{}", key, value)),
+    );
+
+    parts.join(" ")
+}
+
+// One task per line, incomplete todos before completed ones as todo.txt clients expect
+pub fn render(todos: &[(&Project, &Todo)]) -> String {
+    let (open, done): (Vec<_>, Vec<_>) = todos.iter().partition(|(_, todo)| !todo.completed);
+
+    open.iter()
+        .chain(done.iter())
+        .map(|(project, todo)| render_task(&from_todo(todo, project)) + "\n")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parsing what was rendered gives the same task back
+    fn assert_round_trip(line: &str) -> TodoTxtTask {
+        let task = parse_line(line).unwrap();
+        assert_eq!(render_task(&task), line);
+        assert_eq!(parse_line(&render_task(&task)).unwrap(), task);
+        task
+    }
+
+    fn todo(text: &str, priority: u8, completed: bool) -> Todo {
+        Todo {
+            id: 1,
+            text: text.to_string(),
+            completed,
+            time: 25,
+            created_at: "2024-05-01 09:00:00".to_string(),
+            project_id: 1,
+            parent_id: None,
+            priority,
+            due_at: None,
+            recurrence: None,
+            tags: Vec::new(),
+            position: 1024.0,
+            depth: 0,
+            total_time: 25,
+        }
+    }
+
+    fn project(name: &str) -> Project {
+        Project {
+            id: 1,
+            name: name.to_string(),
+            description: None,
+            created_at: "2024-05-01 09:00:00".to_string(),
+            color: None,
+            icon: None,
+            archived_at: None,
+        }
+    }
+
+    #[test]
+    fn priorities_map_to_letters_and_back() {
+        let letters = [(Some('A'), 0), (Some('B'), 1), (Some('C'), 2), (Some('D'), 3), (Some('Z'), 3), (None, 2)];
+        for (letter, priority) in letters {
+            assert_eq!(priority_from_letter(letter), priority, "{:?}", letter);
+        }
+        for priority in 0..=LOWEST_PRIORITY {
+            assert_eq!(priority_from_letter(priority_letter(priority)), priority);
+        }
+    }
+
+    #[test]
+    fn completed_tasks_keep_both_dates() {
+        let task = assert_round_trip("x 2024-03-02 2024-02-28 Send the invites +Launch");
+        assert!(task.completed);
+        assert_eq!(task.priority, None);
+        assert_eq!(task.completion_date, NaiveDate::from_ymd_opt(2024, 3, 2));
+        assert_eq!(task.creation_date, NaiveDate::from_ymd_opt(2024, 2, 28));
+
+        // A single date on a completed task is its completion date
+        let task = assert_round_trip("x 2024-03-02 Send the invites");
+        assert_eq!(task.completion_date, NaiveDate::from_ymd_opt(2024, 3, 2));
+        assert_eq!(task.creation_date, None);
+    }
+
+    #[test]
+    fn projects_contexts_and_fields_are_split_out() {
+        let task = assert_round_trip("(B) 2024-01-05 Call @phone Bob about:venue +Launch +Ops est:1h30m due:2024-03-01");
+        assert_eq!(task.priority, Some('B'));
+        assert_eq!(task.text, "Call @phone Bob about:venue");
+        assert_eq!(task.projects, vec!["Launch", "Ops"]);
+        assert_eq!(task.contexts, vec!["phone"]);
+        assert_eq!(task.extra("about"), Some("venue"));
+        assert_eq!(task.estimate(), Some(90));
+        assert!(task.due_at().is_some());
+
+        assert_eq!(parse_line("Stretch est:20").unwrap().estimate(), Some(20));
+        assert_eq!(parse_line("Stretch est:0").unwrap().estimate(), None);
+    }
+
+    #[test]
+    fn urls_are_not_extras() {
+        let task = assert_round_trip("Read https://example.com/notes and mailto:me@example.com");
+        assert_eq!(task.text, "Read https://example.com/notes and mailto:me@example.com");
+        assert_eq!(task.extras, vec![("mailto".to_string(), "me@example.com".to_string())]);
+        assert!(parse_line("Check http://localhost:1420 first").unwrap().extras.is_empty());
+    }
+
+    #[test]
+    fn project_names_with_spaces_become_one_tag() {
+        let line = render_task(&from_todo(&todo("Cut   the branch", 2, false), &project("Release  1.2")));
+        assert_eq!(line, "(C) 2024-05-01 Cut the branch +Release_1.2 est:25");
+
+        let task = parse_line(&line).unwrap();
+        assert_eq!(task.projects, vec![project_tag("Release 1.2")]);
+        assert_eq!(priority_from_letter(task.priority), 2);
+
+        // Completed todos drop their priority, as todo.txt has no place for it
+        let line = render_task(&from_todo(&todo("Tag the release", 3, true), &project("Release 1.2")));
+        assert_eq!(line, "x Tag the release +Release_1.2 est:25");
+    }
+}
@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+use tauri::State;
+use crate::error::MissionControlError;
+use crate::database::{Database, get_pool};
+use crate::types::{BackupSummary, ImportMode};
+use crate::services::backup;
+
+#[tauri::command]
+pub async fn export_backup(path: String, database: State<'_, Database>) -> Result<BackupSummary, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    backup::export_to_file(&PathBuf::from(path), &pool).await
+}
+
+#[tauri::command]
+pub async fn import_backup(path: String, mode: ImportMode, database: State<'_, Database>) -> Result<BackupSummary, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    backup::import_from_file(&PathBuf::from(path), mode, &pool).await
+}
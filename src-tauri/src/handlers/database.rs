@@ -1,7 +1,8 @@
 use tauri::State;
+use crate::error::MissionControlError;
 use crate::database::{Database, init_database as database_init};
 
 #[tauri::command]
-pub async fn init_database(database: State<'_, Database>) -> Result<String, String> {
+pub async fn init_database(database: State<'_, Database>) -> Result<String, MissionControlError> {
     database_init(&database).await
 } 
\ No newline at end of file
@@ -1,4 +1,5 @@
 use tauri::State;
+use crate::error::MissionControlError;
 use crate::database::{Database, get_pool};
 use crate::types::ExcalidrawData;
 use crate::services::excalidraw;
@@ -9,13 +10,13 @@ pub async fn save_excalidraw_data(
     app_state: String,
     project_id: i64,
     database: State<'_, Database>,
-) -> Result<(), String> {
+) -> Result<(), MissionControlError> {
     let pool = get_pool(&database).await?;
     excalidraw::save_excalidraw_data(elements, app_state, project_id, &pool).await
 }
 
 #[tauri::command]
-pub async fn get_excalidraw_data(project_id: i64, database: State<'_, Database>) -> Result<Option<ExcalidrawData>, String> {
+pub async fn get_excalidraw_data(project_id: i64, database: State<'_, Database>) -> Result<Option<ExcalidrawData>, MissionControlError> {
     let pool = get_pool(&database).await?;
     excalidraw::get_excalidraw_data(project_id, &pool).await
 } 
\ No newline at end of file
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+use tauri::State;
+use crate::error::MissionControlError;
+use crate::database::{Database, get_pool};
+use crate::formats::csv;
+use crate::types::{CsvExportSummary, CsvScope, IcsExportOptions, MarkdownExportOptions, TodoTxtExportSummary};
+use crate::services::exports;
+
+#[tauri::command]
+pub async fn export_project_markdown(
+    project_id: i64,
+    options: Option<MarkdownExportOptions>,
+    database: State<'_, Database>,
+) -> Result<String, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    exports::export_project_markdown(project_id, options.unwrap_or_default(), &pool).await
+}
+
+// `per_project` treats `path` as a directory and writes one file per project
+#[tauri::command]
+pub async fn export_todotxt(
+    path: String,
+    per_project: Option<bool>,
+    database: State<'_, Database>,
+) -> Result<TodoTxtExportSummary, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    exports::export_todotxt(&PathBuf::from(path), per_project.unwrap_or(false), &pool).await
+}
+
+#[tauri::command]
+pub async fn export_csv(
+    scope: CsvScope,
+    path: String,
+    delimiter: Option<char>,
+    database: State<'_, Database>,
+) -> Result<CsvExportSummary, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    exports::export_csv(scope, &PathBuf::from(path), delimiter.unwrap_or(csv::DEFAULT_DELIMITER), &pool).await
+}
+
+// Returns the .ics text; `start_at` is local time (`2024-03-01T09:00`) or RFC 3339
+#[tauri::command]
+pub async fn export_ics(
+    project_id: i64,
+    start_at: String,
+    options: Option<IcsExportOptions>,
+    database: State<'_, Database>,
+) -> Result<String, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    exports::export_ics(project_id, &start_at, options.unwrap_or_default(), &pool).await
+}
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+use tauri::State;
+use crate::error::MissionControlError;
+use crate::database::{Database, get_pool};
+use crate::types::{MarkdownImportResult, TodoTxtImportSummary};
+use crate::services::imports;
+
+// Preview by default; pass `commit: true` once the user has confirmed
+#[tauri::command]
+pub async fn import_markdown(
+    project_id: i64,
+    text: String,
+    commit: Option<bool>,
+    database: State<'_, Database>,
+) -> Result<MarkdownImportResult, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    imports::import_markdown(project_id, &text, commit.unwrap_or(false), &pool).await
+}
+
+#[tauri::command]
+pub async fn import_todotxt(
+    path: String,
+    default_project_id: Option<i64>,
+    database: State<'_, Database>,
+) -> Result<TodoTxtImportSummary, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    imports::import_todotxt(&PathBuf::from(path), default_project_id, &pool).await
+}
@@ -0,0 +1,24 @@
+use tauri::State;
+use crate::error::MissionControlError;
+use crate::database::{Database, get_pool};
+use crate::types::{HistoryEntry, UndoHistory};
+use crate::services::journal;
+
+// Returns what was undone, or null when there is nothing to undo
+#[tauri::command]
+pub async fn undo(database: State<'_, Database>) -> Result<Option<HistoryEntry>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    journal::undo(&pool).await
+}
+
+#[tauri::command]
+pub async fn redo(database: State<'_, Database>) -> Result<Option<HistoryEntry>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    journal::redo(&pool).await
+}
+
+#[tauri::command]
+pub async fn get_undo_history(database: State<'_, Database>) -> Result<UndoHistory, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    journal::get_history(&pool).await
+}
@@ -1,4 +1,16 @@
 pub mod database;
 pub mod projects;
 pub mod todos;
-pub mod excalidraw; 
\ No newline at end of file
+pub mod excalidraw;
+pub mod timer;
+pub mod sessions;
+pub mod reports;
+pub mod backup;
+pub mod snapshots;
+pub mod exports;
+pub mod imports;
+pub mod tags;
+pub mod search;
+pub mod trash;
+pub mod journal;
+pub mod templates;
\ No newline at end of file
@@ -1,22 +1,41 @@
 use tauri::State;
+use crate::error::MissionControlError;
 use crate::database::{Database, get_pool};
-use crate::types::{Project, CreateProject};
+use crate::types::{Project, CreateProject, UpdateProject};
 use crate::services::projects;
 
 #[tauri::command]
-pub async fn get_projects(database: State<'_, Database>) -> Result<Vec<Project>, String> {
+pub async fn get_projects(include_archived: Option<bool>, database: State<'_, Database>) -> Result<Vec<Project>, MissionControlError> {
     let pool = get_pool(&database).await?;
-    projects::get_all_projects(&pool).await
+    projects::get_all_projects(include_archived.unwrap_or(false), &pool).await
 }
 
 #[tauri::command]
-pub async fn create_project(project: CreateProject, database: State<'_, Database>) -> Result<Project, String> {
+pub async fn create_project(project: CreateProject, database: State<'_, Database>) -> Result<Project, MissionControlError> {
     let pool = get_pool(&database).await?;
-    projects::create_new_project(project, &pool).await
+    projects::create_project(project, &pool).await
 }
 
 #[tauri::command]
-pub async fn delete_project(id: i64, database: State<'_, Database>) -> Result<(), String> {
+pub async fn update_project(id: i64, update: UpdateProject, database: State<'_, Database>) -> Result<Project, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    projects::update_project(id, update, &pool).await
+}
+
+#[tauri::command]
+pub async fn archive_project(id: i64, database: State<'_, Database>) -> Result<Project, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    projects::archive_project(id, &pool).await
+}
+
+#[tauri::command]
+pub async fn unarchive_project(id: i64, database: State<'_, Database>) -> Result<Project, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    projects::unarchive_project(id, &pool).await
+}
+
+#[tauri::command]
+pub async fn delete_project(id: i64, database: State<'_, Database>) -> Result<(), MissionControlError> {
     let pool = get_pool(&database).await?;
     projects::delete_project_by_id(id, &pool).await
 } 
\ No newline at end of file
@@ -0,0 +1,16 @@
+use tauri::State;
+use crate::error::MissionControlError;
+use crate::database::{Database, get_pool};
+use crate::types::ProjectReport;
+use crate::services::reports;
+
+#[tauri::command]
+pub async fn get_project_report(
+    project_id: i64,
+    from: Option<String>,
+    to: Option<String>,
+    database: State<'_, Database>,
+) -> Result<ProjectReport, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    reports::get_project_report(project_id, from, to, &pool).await
+}
@@ -0,0 +1,15 @@
+use tauri::State;
+use crate::error::MissionControlError;
+use crate::database::{Database, get_pool};
+use crate::types::{SearchFilters, SearchHit};
+use crate::services::search;
+
+#[tauri::command]
+pub async fn search(
+    query: String,
+    filters: Option<SearchFilters>,
+    database: State<'_, Database>
+) -> Result<Vec<SearchHit>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    search::search(&query, &filters.unwrap_or_default(), &pool).await
+}
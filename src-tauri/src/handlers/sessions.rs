@@ -0,0 +1,28 @@
+use tauri::State;
+use crate::error::MissionControlError;
+use crate::database::{Database, get_pool};
+use crate::types::FocusSession;
+use crate::services::sessions;
+
+#[tauri::command]
+pub async fn list_sessions_by_todo(todo_id: i64, database: State<'_, Database>) -> Result<Vec<FocusSession>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    sessions::get_sessions_by_todo(todo_id, &pool).await
+}
+
+#[tauri::command]
+pub async fn list_sessions_by_project(project_id: i64, database: State<'_, Database>) -> Result<Vec<FocusSession>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    sessions::get_sessions_by_project(project_id, &pool).await
+}
+
+#[tauri::command]
+pub async fn list_sessions_by_date_range(
+    from: String,
+    to: String,
+    project_id: Option<i64>,
+    database: State<'_, Database>,
+) -> Result<Vec<FocusSession>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    sessions::get_sessions_by_date_range(&from, &to, project_id, &pool).await
+}
@@ -0,0 +1,68 @@
+use tauri::{AppHandle, State};
+use crate::error::MissionControlError;
+use crate::database::{Database, get_database_file, get_pool, replace_database_file};
+use crate::services::snapshots;
+use crate::timer::{FocusTimerState, emit_state};
+use crate::types::{SnapshotInfo, SnapshotPolicy};
+
+#[tauri::command]
+pub async fn list_backups(database: State<'_, Database>) -> Result<Vec<SnapshotInfo>, MissionControlError> {
+    get_pool(&database).await?;
+    let dir = snapshots::snapshot_dir(&get_database_file(&database).await?);
+    snapshots::list_snapshots(&dir).await
+}
+
+#[tauri::command]
+pub async fn create_backup(database: State<'_, Database>) -> Result<SnapshotInfo, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    let dir = snapshots::snapshot_dir(&get_database_file(&database).await?);
+    let snapshot = snapshots::take_snapshot(&pool, &dir).await?;
+    snapshots::prune_snapshots(&dir, &snapshots::get_policy(&pool).await?).await?;
+    Ok(snapshot)
+}
+
+// Restore snapshot `id` over the live database. A snapshot of the current state is
+// taken first and returned, so the restore itself can be undone.
+#[tauri::command]
+pub async fn restore_backup(
+    id: String,
+    app: AppHandle,
+    timer: State<'_, FocusTimerState>,
+    database: State<'_, Database>,
+) -> Result<SnapshotInfo, MissionControlError> {
+    if snapshots::parse_snapshot_id(&id).is_none() {
+        return Err(MissionControlError::Validation(format!("Invalid snapshot id '{}'", id)));
+    }
+
+    let pool = get_pool(&database).await?;
+    let dir = snapshots::snapshot_dir(&get_database_file(&database).await?);
+    let source = snapshots::snapshot_path(&dir, &id);
+    if !source.exists() {
+        return Err(MissionControlError::NotFound(format!("Snapshot {} not found", id)));
+    }
+
+    // Hold the timer so no focus session is written while the file is swapped
+    let mut timer = timer.timer.lock().await;
+    let safety_snapshot = snapshots::take_snapshot(&pool, &dir).await?;
+    drop(pool);
+    replace_database_file(&database, &source).await?;
+
+    // The restored data may not contain the todo in focus any more
+    timer.take_session_id();
+    timer.reset();
+    emit_state(&app, &timer.snapshot());
+
+    Ok(safety_snapshot)
+}
+
+#[tauri::command]
+pub async fn get_snapshot_policy(database: State<'_, Database>) -> Result<SnapshotPolicy, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    snapshots::get_policy(&pool).await
+}
+
+#[tauri::command]
+pub async fn set_snapshot_policy(policy: SnapshotPolicy, database: State<'_, Database>) -> Result<SnapshotPolicy, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    snapshots::set_policy(policy, &pool).await
+}
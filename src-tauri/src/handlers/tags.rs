@@ -0,0 +1,45 @@
+use tauri::State;
+use crate::error::MissionControlError;
+use crate::database::{Database, get_pool};
+use crate::types::{Tag, Todo};
+use crate::services::tags;
+
+#[tauri::command]
+pub async fn list_tags(database: State<'_, Database>) -> Result<Vec<Tag>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    tags::list_tags(&pool).await
+}
+
+#[tauri::command]
+pub async fn add_tag(todo_id: i64, name: String, database: State<'_, Database>) -> Result<Todo, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    tags::add_tag(todo_id, &name, &pool).await
+}
+
+#[tauri::command]
+pub async fn remove_tag(todo_id: i64, name: String, database: State<'_, Database>) -> Result<Todo, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    tags::remove_tag(todo_id, &name, &pool).await
+}
+
+#[tauri::command]
+pub async fn rename_tag(id: i64, name: String, database: State<'_, Database>) -> Result<Tag, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    tags::rename_tag(id, &name, &pool).await
+}
+
+#[tauri::command]
+pub async fn merge_tags(
+    source_ids: Vec<i64>,
+    target_id: i64,
+    database: State<'_, Database>
+) -> Result<Tag, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    tags::merge_tags(&source_ids, target_id, &pool).await
+}
+
+#[tauri::command]
+pub async fn delete_tag(id: i64, database: State<'_, Database>) -> Result<(), MissionControlError> {
+    let pool = get_pool(&database).await?;
+    tags::delete_tag(id, &pool).await
+}
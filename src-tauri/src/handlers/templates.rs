@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use tauri::State;
+use crate::error::MissionControlError;
+use crate::database::{Database, get_pool};
+use crate::types::{Project, ProjectTemplate, SaveTemplateOptions};
+use crate::services::templates;
+
+#[tauri::command]
+pub async fn list_templates(database: State<'_, Database>) -> Result<Vec<ProjectTemplate>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    templates::list_templates(&pool).await
+}
+
+#[tauri::command]
+pub async fn save_project_as_template(
+    project_id: i64,
+    options: Option<SaveTemplateOptions>,
+    database: State<'_, Database>
+) -> Result<ProjectTemplate, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    templates::save_project_as_template(project_id, &options.unwrap_or_default(), &pool).await
+}
+
+#[tauri::command]
+pub async fn create_project_from_template(
+    template_id: i64,
+    name: String,
+    values: Option<HashMap<String, String>>,
+    database: State<'_, Database>
+) -> Result<Project, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    templates::create_project_from_template(template_id, name, values.unwrap_or_default(), &pool).await
+}
+
+#[tauri::command]
+pub async fn delete_template(id: i64, database: State<'_, Database>) -> Result<(), MissionControlError> {
+    let pool = get_pool(&database).await?;
+    templates::delete_template(id, &pool).await
+}
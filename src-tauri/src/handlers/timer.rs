@@ -0,0 +1,172 @@
+use tauri::{AppHandle, State};
+use crate::error::MissionControlError;
+use crate::database::{Database, get_pool};
+use crate::services::{sessions, todos};
+use crate::timer::{
+    FocusTimerState, TimerPhase, TimerSnapshot, close_session, emit_state, incomplete_todos,
+    next_on_done, next_on_skip, open_session,
+};
+use crate::types::{SessionOutcome, UpdateTodo};
+
+#[tauri::command]
+pub async fn get_focus_state(timer: State<'_, FocusTimerState>) -> Result<TimerSnapshot, MissionControlError> {
+    let timer = timer.timer.lock().await;
+    Ok(timer.snapshot())
+}
+
+#[tauri::command]
+pub async fn start_focus(
+    project_id: i64,
+    todo_id: Option<i64>,
+    break_minutes: Option<u32>,
+    app: AppHandle,
+    timer: State<'_, FocusTimerState>,
+    database: State<'_, Database>,
+) -> Result<TimerSnapshot, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    let incomplete = incomplete_todos(project_id, &pool).await?;
+
+    // Without an explicit todo, start with the next focus candidate
+    let todo = match todo_id {
+        Some(todo_id) => incomplete.into_iter().find(|todo| todo.id == todo_id).ok_or_else(|| {
+            MissionControlError::NotFound(format!("No incomplete todo {} in project {}", todo_id, project_id))
+        })?,
+        None => incomplete.into_iter().next().ok_or_else(|| {
+            MissionControlError::Validation("There are no incomplete todos to focus on".to_string())
+        })?,
+    };
+
+    let mut timer = timer.timer.lock().await;
+    // Restarting abandons the current run; anything else still open is left over from a crash
+    close_session(&mut timer, SessionOutcome::Abandoned, &pool).await?;
+    sessions::abandon_open_sessions(&pool).await?;
+
+    if let Some(minutes) = break_minutes {
+        timer.set_break_minutes(minutes);
+    }
+    timer.start(project_id, todo);
+    open_session(&mut timer, &pool).await?;
+
+    let snapshot = timer.snapshot();
+    emit_state(&app, &snapshot);
+    Ok(snapshot)
+}
+
+#[tauri::command]
+pub async fn pause_focus(
+    app: AppHandle,
+    timer: State<'_, FocusTimerState>,
+    database: State<'_, Database>,
+) -> Result<TimerSnapshot, MissionControlError> {
+    let mut timer = timer.timer.lock().await;
+    timer.check_pause()?;
+
+    // Log first, so a failed write leaves the timer and the session log in step
+    if let Some(session_id) = timer.session_id() {
+        let pool = get_pool(&database).await?;
+        sessions::pause_session(session_id, &pool).await?;
+    }
+    timer.pause()?;
+
+    let snapshot = timer.snapshot();
+    emit_state(&app, &snapshot);
+    Ok(snapshot)
+}
+
+#[tauri::command]
+pub async fn resume_focus(
+    app: AppHandle,
+    timer: State<'_, FocusTimerState>,
+    database: State<'_, Database>,
+) -> Result<TimerSnapshot, MissionControlError> {
+    let mut timer = timer.timer.lock().await;
+    timer.check_resume()?;
+
+    // Log first, so a failed write leaves the timer and the session log in step
+    if let Some(session_id) = timer.session_id() {
+        let pool = get_pool(&database).await?;
+        sessions::resume_session(session_id, &pool).await?;
+    }
+    timer.resume()?;
+
+    let snapshot = timer.snapshot();
+    emit_state(&app, &snapshot);
+    Ok(snapshot)
+}
+
+#[tauri::command]
+pub async fn skip_focus(
+    app: AppHandle,
+    timer: State<'_, FocusTimerState>,
+    database: State<'_, Database>,
+) -> Result<TimerSnapshot, MissionControlError> {
+    let mut timer = timer.timer.lock().await;
+    let pool = get_pool(&database).await?;
+
+    if timer.phase() == TimerPhase::Break {
+        // Skipping a break goes straight to the queued todo
+        timer.finish_break();
+    } else {
+        let project_id = timer.project_id().ok_or_else(|| {
+            MissionControlError::Validation("Focus timer is not running".to_string())
+        })?;
+        close_session(&mut timer, SessionOutcome::Skipped, &pool).await?;
+
+        let incomplete = incomplete_todos(project_id, &pool).await?;
+        let next = next_on_skip(&incomplete, timer.current_todo_id());
+        timer.advance(next, false);
+    }
+    open_session(&mut timer, &pool).await?;
+
+    let snapshot = timer.snapshot();
+    emit_state(&app, &snapshot);
+    Ok(snapshot)
+}
+
+#[tauri::command]
+pub async fn complete_focus(
+    app: AppHandle,
+    timer: State<'_, FocusTimerState>,
+    database: State<'_, Database>,
+) -> Result<TimerSnapshot, MissionControlError> {
+    let mut timer = timer.timer.lock().await;
+    let (project_id, todo_id) = match (timer.project_id(), timer.current_todo_id()) {
+        (Some(project_id), Some(todo_id)) => (project_id, todo_id),
+        _ => return Err(MissionControlError::Validation("There is no todo in focus".to_string())),
+    };
+
+    let pool = get_pool(&database).await?;
+    todos::update_todo_by_id(
+        todo_id,
+        UpdateTodo { completed: Some(true), ..Default::default() },
+        &pool,
+    )
+    .await?;
+    close_session(&mut timer, SessionOutcome::Completed, &pool).await?;
+
+    let incomplete = incomplete_todos(project_id, &pool).await?;
+    timer.advance(next_on_done(&incomplete, todo_id), true);
+    open_session(&mut timer, &pool).await?;
+
+    let snapshot = timer.snapshot();
+    emit_state(&app, &snapshot);
+    Ok(snapshot)
+}
+
+#[tauri::command]
+pub async fn stop_focus(
+    app: AppHandle,
+    timer: State<'_, FocusTimerState>,
+    database: State<'_, Database>,
+) -> Result<TimerSnapshot, MissionControlError> {
+    let mut timer = timer.timer.lock().await;
+    if timer.session_id().is_some() {
+        let pool = get_pool(&database).await?;
+        close_session(&mut timer, SessionOutcome::Abandoned, &pool).await?;
+    }
+    timer.reset();
+
+    let snapshot = timer.snapshot();
+    emit_state(&app, &snapshot);
+    Ok(snapshot)
+}
@@ -1,12 +1,22 @@
 use tauri::State;
+use crate::error::MissionControlError;
 use crate::database::{Database, get_pool};
-use crate::types::{Todo, UpdateTodo};
-use crate::services::todos;
+use crate::types::{Recurrence, SubtaskPolicy, Todo, UpdateTodo};
+use crate::services::{ordering, recurring, tags, todos};
 
+// With `tag`, only todos carrying it: in `project_id`, or in every project when that is omitted
 #[tauri::command]
-pub async fn get_todos(project_id: i64, database: State<'_, Database>) -> Result<Vec<Todo>, String> {
+pub async fn get_todos(
+    project_id: Option<i64>,
+    tag: Option<String>,
+    database: State<'_, Database>
+) -> Result<Vec<Todo>, MissionControlError> {
     let pool = get_pool(&database).await?;
-    todos::get_todos_by_project(project_id, &pool).await
+    match (project_id, tag) {
+        (project_id, Some(tag)) => tags::get_todos_by_tag(&tag, project_id, &pool).await,
+        (Some(project_id), None) => todos::get_todos_by_project(project_id, &pool).await,
+        (None, None) => Err(MissionControlError::Validation("Pass a project or a tag".to_string())),
+    }
 }
 
 #[tauri::command]
@@ -14,20 +24,80 @@ pub async fn create_todo(
     text: String,
     time: i32,
     project_id: i64,
+    parent_id: Option<i64>,
     database: State<'_, Database>
-) -> Result<Todo, String> {
+) -> Result<Todo, MissionControlError> {
     let pool = get_pool(&database).await?;
-    todos::create_new_todo(text, time, project_id, &pool).await
+    todos::create_todo(text, time, project_id, parent_id, &pool).await
 }
 
 #[tauri::command]
-pub async fn update_todo(id: i64, update: UpdateTodo, database: State<'_, Database>) -> Result<Todo, String> {
+pub async fn update_todo(id: i64, update: UpdateTodo, database: State<'_, Database>) -> Result<Todo, MissionControlError> {
     let pool = get_pool(&database).await?;
     todos::update_todo_by_id(id, update, &pool).await
 }
 
 #[tauri::command]
-pub async fn delete_todo(id: i64, database: State<'_, Database>) -> Result<(), String> {
+pub async fn delete_todo(
+    id: i64,
+    subtasks: Option<SubtaskPolicy>,
+    database: State<'_, Database>
+) -> Result<(), MissionControlError> {
+    let pool = get_pool(&database).await?;
+    todos::delete_todo_by_id(id, subtasks, &pool).await
+} 
+
+// Place a todo in front of `before_id` and/or after `after_id`; returns the reordered list
+#[tauri::command]
+pub async fn reorder_todo(
+    id: i64,
+    before_id: Option<i64>,
+    after_id: Option<i64>,
+    database: State<'_, Database>
+) -> Result<Vec<Todo>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    ordering::reorder_todo(id, before_id, after_id, &pool).await
+}
+
+// Without `target_project_id` this reorders within the todos' own project
+#[tauri::command]
+pub async fn move_todos(
+    ids: Vec<i64>,
+    target_project_id: Option<i64>,
+    before_id: Option<i64>,
+    after_id: Option<i64>,
+    database: State<'_, Database>
+) -> Result<Vec<Todo>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    ordering::move_todos(&ids, target_project_id, before_id, after_id, &pool).await
+}
+
+#[tauri::command]
+pub async fn copy_todos(
+    ids: Vec<i64>,
+    target_project_id: i64,
+    database: State<'_, Database>
+) -> Result<Vec<Todo>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    todos::copy_todos(&ids, target_project_id, &pool).await
+}
+
+#[tauri::command]
+pub async fn next_focus_candidate(
+    project_id: i64,
+    database: State<'_, Database>
+) -> Result<Option<Todo>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    todos::next_focus_candidate(project_id, &pool).await
+}
+
+// `rule: null` stops the todo repeating
+#[tauri::command]
+pub async fn set_todo_recurrence(
+    id: i64,
+    rule: Option<Recurrence>,
+    database: State<'_, Database>
+) -> Result<Todo, MissionControlError> {
     let pool = get_pool(&database).await?;
-    todos::delete_todo_by_id(id, &pool).await
-} 
\ No newline at end of file
+    recurring::set_recurrence(id, rule, &pool).await
+}
@@ -0,0 +1,33 @@
+use tauri::State;
+use crate::error::MissionControlError;
+use crate::database::{Database, get_pool};
+use crate::types::{TrashItem, TrashKind, TrashPolicy};
+use crate::services::trash;
+
+#[tauri::command]
+pub async fn list_trash(database: State<'_, Database>) -> Result<Vec<TrashItem>, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    trash::list_trash(&pool).await
+}
+
+#[tauri::command]
+pub async fn restore_from_trash(
+    kind: TrashKind,
+    id: i64,
+    database: State<'_, Database>
+) -> Result<(), MissionControlError> {
+    let pool = get_pool(&database).await?;
+    trash::restore_from_trash(kind, id, &pool).await
+}
+
+#[tauri::command]
+pub async fn get_trash_policy(database: State<'_, Database>) -> Result<TrashPolicy, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    trash::get_policy(&pool).await
+}
+
+#[tauri::command]
+pub async fn set_trash_policy(policy: TrashPolicy, database: State<'_, Database>) -> Result<TrashPolicy, MissionControlError> {
+    let pool = get_pool(&database).await?;
+    trash::set_policy(policy, &pool).await
+}
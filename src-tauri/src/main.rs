@@ -2,32 +2,109 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod types;
+mod error;
 mod database;
+mod migrations;
+mod timer;
+mod recurrence;
+mod scheduler;
+mod formats;
 mod services;
 mod handlers;
+#[cfg(test)]
+mod test_support;
 
 use database::Database;
+use timer::FocusTimerState;
 use handlers::{
     database::init_database,
-    projects::{get_projects, create_project, delete_project},
-    todos::{get_todos, create_todo, update_todo, delete_todo},
+    projects::{get_projects, create_project, update_project, archive_project, unarchive_project, delete_project},
+    todos::{get_todos, create_todo, update_todo, delete_todo, reorder_todo, move_todos, copy_todos, next_focus_candidate, set_todo_recurrence},
     excalidraw::{save_excalidraw_data, get_excalidraw_data},
+    sessions::{list_sessions_by_todo, list_sessions_by_project, list_sessions_by_date_range},
+    reports::get_project_report,
+    backup::{export_backup, import_backup},
+    snapshots::{list_backups, create_backup, restore_backup, get_snapshot_policy, set_snapshot_policy},
+    exports::{export_csv, export_ics, export_project_markdown, export_todotxt},
+    imports::{import_markdown, import_todotxt},
+    tags::{list_tags, add_tag, remove_tag, rename_tag, merge_tags, delete_tag},
+    search::search,
+    trash::{list_trash, restore_from_trash, get_trash_policy, set_trash_policy},
+    journal::{undo, redo, get_undo_history},
+    templates::{list_templates, save_project_as_template, create_project_from_template, delete_template},
+    timer::{get_focus_state, start_focus, pause_focus, resume_focus, skip_focus, complete_focus, stop_focus},
 };
 
 fn main() {
     tauri::Builder::default()
         .manage(Database::new())
+        .manage(FocusTimerState::new())
+        .setup(|app| {
+            timer::spawn_ticker(app.handle());
+            scheduler::spawn_scheduler(app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             init_database,
             get_projects,
             create_project,
+            update_project,
+            archive_project,
+            unarchive_project,
             delete_project,
             get_todos,
             create_todo,
             update_todo,
             delete_todo,
+            reorder_todo,
+            move_todos,
+            copy_todos,
+            next_focus_candidate,
+            set_todo_recurrence,
             save_excalidraw_data,
-            get_excalidraw_data
+            get_excalidraw_data,
+            get_focus_state,
+            start_focus,
+            pause_focus,
+            resume_focus,
+            skip_focus,
+            complete_focus,
+            stop_focus,
+            list_sessions_by_todo,
+            list_sessions_by_project,
+            list_sessions_by_date_range,
+            get_project_report,
+            export_backup,
+            import_backup,
+            list_backups,
+            create_backup,
+            restore_backup,
+            get_snapshot_policy,
+            set_snapshot_policy,
+            export_project_markdown,
+            import_markdown,
+            export_todotxt,
+            import_todotxt,
+            list_tags,
+            add_tag,
+            remove_tag,
+            rename_tag,
+            merge_tags,
+            delete_tag,
+            search,
+            list_trash,
+            restore_from_trash,
+            get_trash_policy,
+            set_trash_policy,
+            undo,
+            redo,
+            get_undo_history,
+            list_templates,
+            save_project_as_template,
+            create_project_from_template,
+            delete_template,
+            export_csv,
+            export_ics
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
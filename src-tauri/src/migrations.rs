@@ -0,0 +1,524 @@
+use sqlx::{sqlite::SqlitePool, Connection, Executor, Sqlite, SqliteConnection};
+use crate::error::MissionControlError;
+
+// A single forward-only schema change, embedded in the binary at compile time
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+// All migrations in order. Never edit or reorder a migration that has shipped -
+// add a new one with the next version number instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        sql: include_str!("../migrations/0001_initial_schema.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "focus_sessions",
+        sql: include_str!("../migrations/0002_focus_sessions.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "settings",
+        sql: include_str!("../migrations/0003_settings.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "subtasks",
+        sql: include_str!("../migrations/0004_subtasks.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "todo_positions",
+        sql: include_str!("../migrations/0005_todo_positions.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "todo_priority_due",
+        sql: include_str!("../migrations/0006_todo_priority_due.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "recurrence",
+        sql: include_str!("../migrations/0007_recurrence.sql"),
+    },
+    // The comment in 0008 predates sqlx turning foreign keys on for every connection.
+    // They are enforced, and its trigger is what lets a tagged todo be deleted, as the
+    // links have no ON DELETE action; 0012 replaces the trigger with ON DELETE CASCADE.
+    Migration {
+        version: 8,
+        name: "tags",
+        sql: include_str!("../migrations/0008_tags.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "search",
+        sql: include_str!("../migrations/0009_search.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "trash",
+        sql: include_str!("../migrations/0010_trash.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "undo",
+        sql: include_str!("../migrations/0011_undo.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "foreign_keys",
+        sql: include_str!("../migrations/0012_foreign_keys.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "project_details",
+        sql: include_str!("../migrations/0013_project_details.sql"),
+    },
+    Migration {
+        version: 14,
+        name: "templates",
+        sql: include_str!("../migrations/0014_templates.sql"),
+    },
+    Migration {
+        version: 15,
+        name: "undo_merge",
+        sql: include_str!("../migrations/0015_undo_merge.sql"),
+    },
+];
+
+// One row per applied migration
+const SCHEMA_VERSION_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS schema_version (
+        version INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+"#;
+
+// Highest schema version this build knows how to handle
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+// Schema version recorded in the database
+pub async fn current_version<'e, E>(executor: E) -> Result<i64, MissionControlError>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+        .fetch_one(executor)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read schema version", e))
+}
+
+// Apply every pending migration inside a single transaction. Refuses to touch a
+// database that was written by a newer build.
+pub async fn run_migrations(pool: &SqlitePool) -> Result<i64, MissionControlError> {
+    // Use one dedicated connection so the whole upgrade sees the same session
+    let mut conn = pool.acquire().await.map_err(|e| MissionControlError::from_sqlx("Failed to acquire connection for migrations", e))?;
+
+    (&mut *conn)
+        .execute(SCHEMA_VERSION_TABLE)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to create schema_version table", e))?;
+
+    let current: i64 = sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read schema version", e))?;
+
+    let latest = latest_version();
+    if current > latest {
+        let error = MissionControlError::Conflict(format!(
+            "Database schema version {} is newer than this build supports ({}). Please update MissionControl.",
+            current, latest
+        ));
+        println!("{}", error);
+        return Err(error);
+    }
+
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > current).collect();
+    if pending.is_empty() {
+        println!("Database schema is up to date (version {})", current);
+        return Ok(current);
+    }
+
+    // Rebuilding a table drops it while other tables still reference it, which would
+    // cascade or fail with foreign keys on. They can only be switched outside a
+    // transaction, so they are off for the upgrade and every reference is checked
+    // before it commits.
+    set_foreign_keys(false, &mut conn).await?;
+    let applied = apply(&pending, &mut conn).await;
+    let restored = set_foreign_keys(true, &mut conn).await;
+    applied?;
+    restored?;
+
+    println!("Database schema migrated from version {} to {}", current, latest);
+    Ok(latest)
+}
+
+async fn set_foreign_keys(enabled: bool, conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    let pragma = if enabled { "PRAGMA foreign_keys = ON" } else { "PRAGMA foreign_keys = OFF" };
+    conn.execute(pragma)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to switch foreign key enforcement", e))?;
+    Ok(())
+}
+
+async fn apply(pending: &[&Migration], conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    let mut tx = conn.begin().await.map_err(|e| MissionControlError::from_sqlx("Failed to start migration transaction", e))?;
+
+    for migration in pending {
+        println!("Applying migration {:04}_{}", migration.version, migration.name);
+
+        (&mut *tx).execute(migration.sql).await.map_err(|e| {
+            MissionControlError::from_sqlx(
+                &format!("Migration {:04}_{} failed", migration.version, migration.name),
+                e,
+            )
+        })?;
+
+        sqlx::query("INSERT INTO schema_version (version, name) VALUES (?, ?)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to record migration", e))?;
+    }
+
+    let broken: Vec<(String, Option<i64>, String)> = sqlx::query_as("SELECT \"table\", rowid, parent FROM pragma_foreign_key_check")
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to check foreign keys", e))?;
+    if let Some((table, rowid, parent)) = broken.first() {
+        let error = MissionControlError::Database {
+            message: "Migrations left rows pointing at missing records".to_string(),
+            details: Some(format!(
+                "{} broken reference(s), first: {} row {} -> {}",
+                broken.len(),
+                table,
+                rowid.map(|rowid| rowid.to_string()).unwrap_or_default(),
+                parent
+            )),
+        };
+        println!("{}", error);
+        return Err(error);
+    }
+
+    // Dropping the transaction on any error above rolls back every pending migration
+    tx.commit().await.map_err(|e| MissionControlError::from_sqlx("Failed to commit migrations", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{empty_pool, exec};
+
+    // Tables as `database::create_tables` made them before the migration engine existed
+    const PRE_ENGINE_SCHEMA: &str = r#"
+        CREATE TABLE IF NOT EXISTS projects (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            description TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS todos (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            text TEXT NOT NULL,
+            completed INTEGER NOT NULL DEFAULT 0,
+            time INTEGER NOT NULL DEFAULT 25,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            project_id INTEGER REFERENCES projects(id) DEFAULT 1
+        );
+        CREATE TABLE IF NOT EXISTS excalidraw_data (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            elements TEXT NOT NULL,
+            app_state TEXT NOT NULL,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            project_id INTEGER REFERENCES projects(id) DEFAULT 1
+        );
+    "#;
+
+    // Database left behind by a build whose newest migration was `version`;
+    // 0 is the schema from before the migration engine
+    async fn database_at(version: i64) -> SqlitePool {
+        let pool = empty_pool().await;
+        if version == 0 {
+            exec(PRE_ENGINE_SCHEMA, &pool).await;
+        } else {
+            let mut conn = pool.acquire().await.unwrap();
+            (&mut *conn).execute(SCHEMA_VERSION_TABLE).await.unwrap();
+            let shipped: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version <= version).collect();
+            set_foreign_keys(false, &mut conn).await.unwrap();
+            apply(&shipped, &mut conn).await.unwrap();
+            set_foreign_keys(true, &mut conn).await.unwrap();
+        }
+        seed(version, &pool).await;
+        pool
+    }
+
+    // Rows using only what the schema at `version` has
+    async fn seed(version: i64, pool: &SqlitePool) {
+        exec(
+            r#"
+            INSERT INTO projects (id, name, description) VALUES (1, 'Inbox', 'Default'), (2, 'Launch', NULL);
+            INSERT INTO todos (id, text, completed, time, project_id) VALUES
+                (1, 'Write spec', 0, 50, 1),
+                (2, 'Ship it', 1, 25, 2),
+                (3, 'Review, then merge', 0, 15, 2);
+            INSERT INTO excalidraw_data (elements, app_state, project_id) VALUES ('[{"id":"a"}]', '{}', 2);
+            "#,
+            pool,
+        )
+        .await;
+        if version >= 2 {
+            exec(
+                "INSERT INTO focus_sessions (todo_id, project_id, ended_at, outcome) VALUES (1, 1, CURRENT_TIMESTAMP, 'completed')",
+                pool,
+            )
+            .await;
+        }
+        if version >= 5 {
+            exec("UPDATE todos SET position = id * 1024.0", pool).await;
+        }
+    }
+
+    // Columns, references, indexes and triggers of every table. Compared instead of
+    // the CREATE text, which differs in whitespace between adopted and created tables.
+    async fn schema(pool: &SqlitePool) -> Vec<String> {
+        sqlx::query_scalar(
+            r#"
+            SELECT m.type || ' ' || m.name || ' on ' || m.tbl_name
+            FROM sqlite_master m WHERE m.name NOT LIKE 'sqlite_%'
+            UNION ALL
+            SELECT 'column ' || m.name || ' ' || c.cid || ' ' || c.name || ' ' || c.type
+                || ' notnull=' || c."notnull" || ' default=' || COALESCE(c.dflt_value, 'NULL') || ' pk=' || c.pk
+            FROM sqlite_master m, pragma_table_info(m.name) c WHERE m.type = 'table'
+            UNION ALL
+            SELECT 'reference ' || m.name || '.' || f."from" || ' -> ' || f."table" || '.' || COALESCE(f."to", 'id')
+                || ' on delete ' || f.on_delete
+            FROM sqlite_master m, pragma_foreign_key_list(m.name) f WHERE m.type = 'table'
+            ORDER BY 1
+            "#,
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn count(sql: &str, pool: &SqlitePool) -> i64 {
+        sqlx::query_scalar(sql).fetch_one(pool).await.unwrap()
+    }
+
+    async fn assert_seed_preserved(version: i64, pool: &SqlitePool) {
+        let projects: Vec<(i64, String, Option<String>)> =
+            sqlx::query_as("SELECT id, name, description FROM projects ORDER BY id")
+                .fetch_all(pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            projects,
+            vec![(1, "Inbox".to_string(), Some("Default".to_string())), (2, "Launch".to_string(), None)],
+            "projects after upgrading from version {}",
+            version
+        );
+
+        let todos: Vec<(i64, String, bool, i64, i64)> =
+            sqlx::query_as("SELECT id, text, completed, time, project_id FROM todos ORDER BY project_id, position")
+                .fetch_all(pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            todos,
+            vec![
+                (1, "Write spec".to_string(), false, 50, 1),
+                (2, "Ship it".to_string(), true, 25, 2),
+                (3, "Review, then merge".to_string(), false, 15, 2),
+            ],
+            "todos after upgrading from version {}",
+            version
+        );
+
+        // Columns added along the way get their defaults
+        assert_eq!(count("SELECT COUNT(*) FROM todos WHERE priority = 2 AND parent_id IS NULL AND deleted_at IS NULL", pool).await, 3);
+        assert_eq!(count("SELECT COUNT(*) FROM projects WHERE archived_at IS NULL AND deleted_at IS NULL", pool).await, 2);
+        assert_eq!(count("SELECT COUNT(*) FROM excalidraw_data WHERE project_id = 2 AND elements = '[{\"id\":\"a\"}]'", pool).await, 1);
+        assert_eq!(count("SELECT COUNT(*) FROM focus_sessions", pool).await, i64::from(version >= 2));
+
+        // Existing rows are searchable once the index exists
+        assert_eq!(count("SELECT COUNT(*) FROM search_index WHERE search_index MATCH 'spec'", pool).await, 1);
+
+        // New rows continue after the existing IDs
+        exec("INSERT INTO todos (text, project_id, position) VALUES ('Next', 1, 4096)", pool).await;
+        assert_eq!(count("SELECT MAX(id) FROM todos", pool).await, 4);
+    }
+
+    #[tokio::test]
+    async fn upgrades_every_historical_schema() {
+        let expected = schema(&database_at(latest_version()).await).await;
+
+        for version in 0..latest_version() {
+            let pool = database_at(version).await;
+            assert_eq!(run_migrations(&pool).await.unwrap(), latest_version());
+
+            let applied: Vec<i64> = sqlx::query_scalar("SELECT version FROM schema_version ORDER BY version")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+            assert_eq!(applied, MIGRATIONS.iter().map(|m| m.version).collect::<Vec<_>>());
+            assert_eq!(schema(&pool).await, expected, "schema after upgrading from version {}", version);
+            assert_eq!(count("SELECT COUNT(*) FROM pragma_foreign_key_check", &pool).await, 0);
+            assert_seed_preserved(version, &pool).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn second_run_is_a_no_op() {
+        let pool = database_at(0).await;
+        run_migrations(&pool).await.unwrap();
+        let schema_before = schema(&pool).await;
+        let recorded_before: Vec<(i64, String, String)> =
+            sqlx::query_as("SELECT version, name, applied_at FROM schema_version ORDER BY version")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+
+        assert_eq!(run_migrations(&pool).await.unwrap(), latest_version());
+
+        let recorded_after: Vec<(i64, String, String)> =
+            sqlx::query_as("SELECT version, name, applied_at FROM schema_version ORDER BY version")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(recorded_after, recorded_before);
+        assert_eq!(schema(&pool).await, schema_before);
+        assert_seed_preserved(0, &pool).await;
+    }
+
+    #[tokio::test]
+    async fn refuses_a_newer_database() {
+        let pool = database_at(latest_version()).await;
+        sqlx::query("INSERT INTO schema_version (version, name) VALUES (?, 'from_the_future')")
+            .bind(latest_version() + 1)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let error = run_migrations(&pool).await.unwrap_err();
+        assert_eq!(error.kind(), "conflict");
+        assert_eq!(current_version(&pool).await.unwrap(), latest_version() + 1);
+    }
+
+    // Everything a failed upgrade must leave as it was
+    async fn snapshot(pool: &SqlitePool) -> (Vec<String>, i64, Vec<(i64, String, i64)>) {
+        let todos = sqlx::query_as("SELECT id, text, project_id FROM todos ORDER BY id")
+            .fetch_all(pool)
+            .await
+            .unwrap();
+        (schema(pool).await, current_version(pool).await.unwrap(), todos)
+    }
+
+    #[tokio::test]
+    async fn failing_migration_rolls_back_the_upgrade() {
+        let pool = database_at(10).await;
+        // 0012 rebuilds todos, then stops when creating a table that is already there
+        exec("CREATE TABLE new_excalidraw_data (id INTEGER PRIMARY KEY)", &pool).await;
+        let before = snapshot(&pool).await;
+
+        let error = run_migrations(&pool).await.unwrap_err();
+        assert_eq!(error.kind(), "database");
+        assert!(error.to_string().contains("0012_foreign_keys"), "{}", error);
+
+        // 0011 went back along with the half-applied 0012
+        assert_eq!(snapshot(&pool).await, before);
+        assert_eq!(current_version(&pool).await.unwrap(), 10);
+        assert_eq!(count("SELECT COUNT(*) FROM sqlite_master WHERE name = 'undo_entries'", &pool).await, 0);
+        assert_eq!(count("PRAGMA foreign_keys", &pool).await, 1);
+
+        // Once the obstacle is gone the upgrade goes through
+        exec("DROP TABLE new_excalidraw_data", &pool).await;
+        assert_eq!(run_migrations(&pool).await.unwrap(), latest_version());
+        assert_seed_preserved(10, &pool).await;
+    }
+
+    #[tokio::test]
+    async fn broken_reference_rolls_back_the_upgrade() {
+        let pool = database_at(11).await;
+        // 0012 leaves undo_changes as it is, so a change of a missing entry stays behind
+        exec(
+            "PRAGMA foreign_keys = OFF; \
+             INSERT INTO undo_changes (entry_id, statement) VALUES (99, 'DELETE FROM todos WHERE id = 1'); \
+             PRAGMA foreign_keys = ON;",
+            &pool,
+        )
+        .await;
+        let before = snapshot(&pool).await;
+
+        let error = run_migrations(&pool).await.unwrap_err();
+        assert_eq!(error.kind(), "database");
+        match &error {
+            MissionControlError::Database { details, .. } => {
+                assert!(details.as_deref().unwrap_or_default().contains("undo_changes"), "{:?}", details);
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        assert_eq!(snapshot(&pool).await, before);
+        assert_eq!(current_version(&pool).await.unwrap(), 11);
+        assert_eq!(count("SELECT COUNT(*) FROM undo_changes WHERE entry_id = 99", &pool).await, 1);
+        assert_eq!(count("PRAGMA foreign_keys", &pool).await, 1);
+    }
+
+    #[tokio::test]
+    async fn sessions_go_with_their_todo_from_version_2() {
+        // The seed logs a session on todo 1
+        let pool = database_at(2).await;
+        exec("DELETE FROM todos WHERE id = 1", &pool).await;
+        assert_eq!(count("SELECT COUNT(*) FROM focus_sessions", &pool).await, 0);
+    }
+
+    #[tokio::test]
+    async fn orphans_move_to_a_recovered_project_at_version_12() {
+        let pool = database_at(11).await;
+        exec(
+            "PRAGMA foreign_keys = OFF; \
+             INSERT INTO todos (id, text, project_id, parent_id, position) VALUES \
+                 (10, 'Lost', NULL, NULL, 1024), \
+                 (11, 'Stray', 99, NULL, 1024), \
+                 (12, 'Stray step', 99, 11, 2048), \
+                 (13, 'Split', 1, 11, 9216); \
+             INSERT INTO excalidraw_data (elements, app_state, project_id) VALUES ('[]', '{}', 99); \
+             INSERT INTO focus_sessions (todo_id, project_id) VALUES (11, 99), (500, 1); \
+             PRAGMA foreign_keys = ON;",
+            &pool,
+        )
+        .await;
+
+        assert_eq!(run_migrations(&pool).await.unwrap(), latest_version());
+        let recovered = count("SELECT id FROM projects WHERE name = 'Recovered items' AND deleted_at IS NULL", &pool).await;
+        assert_eq!(recovered, 3);
+
+        let moved: Vec<(i64, i64, Option<i64>)> =
+            sqlx::query_as("SELECT id, project_id, parent_id FROM todos WHERE id >= 10 ORDER BY id")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(moved, [(10, 3, None), (11, 3, None), (12, 3, Some(11)), (13, 1, None)]);
+        assert_eq!(count("SELECT COUNT(DISTINCT position) FROM todos WHERE project_id = 3", &pool).await, 3);
+        assert_eq!(count("SELECT COUNT(*) FROM excalidraw_data WHERE project_id = 3", &pool).await, 1);
+        assert_eq!(count("SELECT COUNT(*) FROM search_index WHERE search_index MATCH 'stray' AND project_id = 3", &pool).await, 2);
+
+        // The session keeps its todo and follows it; one for a todo that is gone is cleared
+        let sessions: Vec<(i64, i64)> = sqlx::query_as("SELECT todo_id, project_id FROM focus_sessions ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(sessions, [(1, 1), (11, 3)]);
+    }
+}
@@ -0,0 +1,280 @@
+use chrono::{Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Weekday};
+use crate::types::Recurrence;
+
+// Longest interval `every_n_days` accepts
+pub const MAX_INTERVAL_DAYS: u32 = 366;
+
+pub fn validate(rule: &Recurrence) -> Result<(), String> {
+    match rule {
+        Recurrence::Weekly { days } if days.is_empty() => Err("A weekly rule needs at least one day".to_string()),
+        Recurrence::EveryNDays { interval } if *interval == 0 || *interval > MAX_INTERVAL_DAYS => {
+            Err(format!("The interval must be between 1 and {} days", MAX_INTERVAL_DAYS))
+        }
+        Recurrence::MonthlyByDay { day } if *day == 0 || *day > 31 => {
+            Err("The day of the month must be between 1 and 31".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|first| first.pred_opt())
+        .map(|last| last.day())
+        .unwrap_or(28)
+}
+
+// `day` of the given month, or its last day when the month is shorter
+fn day_of_month(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(year, month, day.min(days_in_month(year, month)))
+}
+
+fn is_weekday(date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+// First date strictly after `after` on which the rule fires. `every_n_days` counts
+// from `after`, so a series keeps its cadence; `monthly_by_day` always aims for the
+// configured day, so the 31st falls back to the 30th in April but returns in May.
+pub fn next_date(rule: &Recurrence, after: NaiveDate) -> Option<NaiveDate> {
+    match rule {
+        Recurrence::Daily => after.succ_opt(),
+        Recurrence::Weekdays => after.iter_days().skip(1).find(|date| is_weekday(*date)),
+        Recurrence::Weekly { days } => after.iter_days().skip(1).take(7).find(|date| days.contains(&date.weekday())),
+        Recurrence::EveryNDays { interval } => after.checked_add_signed(Duration::days(i64::from((*interval).max(1)))),
+        Recurrence::MonthlyByDay { day } => {
+            let (mut year, mut month) = (after.year(), after.month());
+            // The target day exists, possibly clamped, in every month, so two tries suffice
+            for _ in 0..2 {
+                let candidate = day_of_month(year, month, *day)?;
+                if candidate > after {
+                    return Some(candidate);
+                }
+                (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+            }
+            None
+        }
+    }
+}
+
+// The occurrence after `current` that isn't before `today`: missed occurrences are
+// skipped rather than created in bulk
+pub fn next_occurrence(rule: &Recurrence, current: NaiveDate, today: NaiveDate) -> Option<NaiveDate> {
+    let mut date = next_date(rule, current)?;
+    while date < today {
+        date = next_date(rule, date)?;
+    }
+    Some(date)
+}
+
+// Wall-clock time in `tz` as UTC. A time skipped by a DST jump is read with the
+// offset from before the jump (02:30 becomes 03:30 after clocks spring forward),
+// and a time that happens twice uses the first one (RFC 5545 3.3.5).
+pub fn local_to_utc<Tz: TimeZone>(at: NaiveDateTime, tz: &Tz) -> Option<NaiveDateTime> {
+    match tz.from_local_datetime(&at) {
+        LocalResult::Single(local) => Some(local.naive_utc()),
+        LocalResult::Ambiguous(first, second) => Some(first.naive_utc().min(second.naive_utc())),
+        LocalResult::None => {
+            let before = tz.from_local_datetime(&(at - Duration::days(1))).earliest()?;
+            let offset = before.offset().fix().local_minus_utc();
+            Some(at - Duration::seconds(i64::from(offset)))
+        }
+    }
+}
+
+pub fn utc_to_local<Tz: TimeZone>(at: NaiveDateTime, tz: &Tz) -> NaiveDateTime {
+    tz.from_utc_datetime(&at).naive_local()
+}
+
+// Due time of the instance after one due at `due_at` (UTC), at the same local
+// wall-clock time on the next occurrence that isn't before `today`
+pub fn next_due<Tz: TimeZone>(rule: &Recurrence, due_at: NaiveDateTime, today: NaiveDate, tz: &Tz) -> Option<NaiveDateTime> {
+    let local = utc_to_local(due_at, tz);
+    let date = next_occurrence(rule, local.date(), today)?;
+    local_to_utc(date.and_time(local.time()), tz)
+}
+
+// Due time for a todo that is made recurring without a due date: the end of the
+// first occurrence from `today` on
+pub fn first_due<Tz: TimeZone>(rule: &Recurrence, today: NaiveDate, tz: &Tz) -> Option<NaiveDateTime> {
+    let date = match rule {
+        Recurrence::EveryNDays { .. } => today,
+        _ => next_occurrence(rule, today.pred_opt()?, today)?,
+    };
+    local_to_utc(date.and_time(NaiveTime::from_hms_opt(23, 59, 59)?), tz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        date(year, month, day).and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    // Central European time: UTC+1, and UTC+2 from 01:00 UTC on the last Sunday of
+    // March to 01:00 UTC on the last Sunday of October
+    #[derive(Debug, Clone, Copy)]
+    struct Cet;
+
+    fn last_sunday(year: i32, month: u32) -> NaiveDate {
+        let last = day_of_month(year, month, 31).unwrap();
+        last - Duration::days(i64::from(last.weekday().num_days_from_sunday()))
+    }
+
+    fn hours(offset: i32) -> FixedOffset {
+        FixedOffset::east_opt(offset * 3600).unwrap()
+    }
+
+    impl TimeZone for Cet {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Cet
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            // Summer time first, as it is the earlier reading of a repeated hour
+            let fitting: Vec<FixedOffset> = [hours(2), hours(1)]
+                .into_iter()
+                .filter(|offset| {
+                    let utc = *local - Duration::seconds(offset.local_minus_utc().into());
+                    self.offset_from_utc_datetime(&utc) == *offset
+                })
+                .collect();
+            match fitting[..] {
+                [offset] => LocalResult::Single(offset),
+                [summer, winter] => LocalResult::Ambiguous(summer, winter),
+                _ => LocalResult::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            let one = NaiveTime::from_hms_opt(1, 0, 0).unwrap();
+            let spring = last_sunday(utc.year(), 3).and_time(one);
+            let fall = last_sunday(utc.year(), 10).and_time(one);
+            if *utc >= spring && *utc < fall { hours(2) } else { hours(1) }
+        }
+    }
+
+    #[test]
+    fn validates_rules() {
+        assert!(validate(&Recurrence::Weekly { days: vec![] }).is_err());
+        assert!(validate(&Recurrence::EveryNDays { interval: 0 }).is_err());
+        assert!(validate(&Recurrence::EveryNDays { interval: MAX_INTERVAL_DAYS + 1 }).is_err());
+        assert!(validate(&Recurrence::MonthlyByDay { day: 32 }).is_err());
+        assert!(validate(&Recurrence::MonthlyByDay { day: 31 }).is_ok());
+    }
+
+    #[test]
+    fn monthly_on_the_31st_clamps_to_shorter_months() {
+        let rule = Recurrence::MonthlyByDay { day: 31 };
+        assert_eq!(next_date(&rule, date(2024, 1, 31)), Some(date(2024, 2, 29)));
+        assert_eq!(next_date(&rule, date(2023, 1, 31)), Some(date(2023, 2, 28)));
+        // Clamping is per month, so the series returns to the 31st
+        assert_eq!(next_date(&rule, date(2024, 2, 29)), Some(date(2024, 3, 31)));
+        assert_eq!(next_date(&rule, date(2024, 3, 31)), Some(date(2024, 4, 30)));
+        assert_eq!(next_date(&rule, date(2024, 4, 30)), Some(date(2024, 5, 31)));
+        assert_eq!(next_date(&rule, date(2024, 12, 31)), Some(date(2025, 1, 31)));
+        // Mid-month the same month's last day comes first
+        assert_eq!(next_date(&rule, date(2024, 2, 10)), Some(date(2024, 2, 29)));
+    }
+
+    #[test]
+    fn monthly_on_the_29th_in_february() {
+        let rule = Recurrence::MonthlyByDay { day: 29 };
+        assert_eq!(next_date(&rule, date(2024, 1, 29)), Some(date(2024, 2, 29)));
+        assert_eq!(next_date(&rule, date(2024, 2, 29)), Some(date(2024, 3, 29)));
+        assert_eq!(next_date(&rule, date(2023, 1, 29)), Some(date(2023, 2, 28)));
+        assert_eq!(next_date(&rule, date(2023, 2, 28)), Some(date(2023, 3, 29)));
+        assert_eq!(next_date(&rule, date(2100, 1, 29)), Some(date(2100, 2, 28)));
+    }
+
+    #[test]
+    fn weekly_and_daily_rules() {
+        let weekly = Recurrence::Weekly { days: vec![Weekday::Mon, Weekday::Thu] };
+        // 2024-03-04 is a Monday
+        assert_eq!(next_date(&weekly, date(2024, 3, 4)), Some(date(2024, 3, 7)));
+        assert_eq!(next_date(&weekly, date(2024, 3, 7)), Some(date(2024, 3, 11)));
+        assert_eq!(next_date(&Recurrence::Weekdays, date(2024, 3, 8)), Some(date(2024, 3, 11)));
+        assert_eq!(next_date(&Recurrence::Daily, date(2024, 2, 28)), Some(date(2024, 2, 29)));
+        assert_eq!(next_date(&Recurrence::EveryNDays { interval: 3 }, date(2024, 2, 28)), Some(date(2024, 3, 2)));
+        assert_eq!(next_occurrence(&Recurrence::Daily, date(2024, 3, 1), date(2024, 3, 10)), Some(date(2024, 3, 10)));
+    }
+
+    #[test]
+    fn test_zone_switches_on_the_last_sundays() {
+        assert_eq!(last_sunday(2024, 3), date(2024, 3, 31));
+        assert_eq!(last_sunday(2024, 10), date(2024, 10, 27));
+        assert_eq!(local_to_utc(at(2024, 1, 15, 9, 0), &Cet), Some(at(2024, 1, 15, 8, 0)));
+        assert_eq!(local_to_utc(at(2024, 7, 15, 9, 0), &Cet), Some(at(2024, 7, 15, 7, 0)));
+    }
+
+    #[test]
+    fn daily_keeps_the_wall_clock_time_across_dst() {
+        // 09:00 on the day before spring forward is 08:00 UTC, and 07:00 UTC after it
+        let due = local_to_utc(at(2024, 3, 30, 9, 0), &Cet).unwrap();
+        assert_eq!(due, at(2024, 3, 30, 8, 0));
+        let next = next_due(&Recurrence::Daily, due, date(2024, 3, 31), &Cet).unwrap();
+        assert_eq!(next, at(2024, 3, 31, 7, 0));
+        assert_eq!(utc_to_local(next, &Cet), at(2024, 3, 31, 9, 0));
+
+        // And back again when the clocks fall back
+        let due = local_to_utc(at(2024, 10, 26, 9, 0), &Cet).unwrap();
+        assert_eq!(due, at(2024, 10, 26, 7, 0));
+        let next = next_due(&Recurrence::EveryNDays { interval: 1 }, due, date(2024, 10, 27), &Cet).unwrap();
+        assert_eq!(next, at(2024, 10, 27, 8, 0));
+        assert_eq!(utc_to_local(next, &Cet), at(2024, 10, 27, 9, 0));
+    }
+
+    #[test]
+    fn weekly_in_a_skipped_or_repeated_hour() {
+        let sundays = Recurrence::Weekly { days: vec![Weekday::Sun] };
+
+        // 02:30 doesn't exist on 2024-03-31; it is read with winter time, as 03:30 summer time
+        let due = local_to_utc(at(2024, 3, 24, 2, 30), &Cet).unwrap();
+        let next = next_due(&sundays, due, date(2024, 3, 25), &Cet).unwrap();
+        assert_eq!(next, at(2024, 3, 31, 1, 30));
+        assert_eq!(utc_to_local(next, &Cet), at(2024, 3, 31, 3, 30));
+
+        // Each instance starts from the previous due time, so the series stays at 03:30
+        let after = next_due(&sundays, next, date(2024, 4, 1), &Cet).unwrap();
+        assert_eq!(utc_to_local(after, &Cet), at(2024, 4, 7, 3, 30));
+
+        // 02:30 happens twice on 2024-10-27; the first, summer-time one is used
+        let due = local_to_utc(at(2024, 10, 20, 2, 30), &Cet).unwrap();
+        let next = next_due(&sundays, due, date(2024, 10, 21), &Cet).unwrap();
+        assert_eq!(next, at(2024, 10, 27, 0, 30));
+        assert_eq!(utc_to_local(next, &Cet), at(2024, 10, 27, 2, 30));
+        let after = next_due(&sundays, next, date(2024, 10, 28), &Cet).unwrap();
+        assert_eq!(after, at(2024, 11, 3, 1, 30));
+    }
+
+    #[test]
+    fn first_due_is_the_end_of_the_first_occurrence() {
+        let today = date(2024, 3, 30);
+        assert_eq!(
+            first_due(&Recurrence::Daily, today, &Cet),
+            Some(at(2024, 3, 30, 22, 59) + Duration::seconds(59))
+        );
+        // Summer time: the end of the day is 21:59:59 UTC
+        assert_eq!(
+            first_due(&Recurrence::Weekly { days: vec![Weekday::Sun] }, today, &Cet),
+            Some(at(2024, 3, 31, 21, 59) + Duration::seconds(59))
+        );
+    }
+}
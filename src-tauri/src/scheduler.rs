@@ -0,0 +1,40 @@
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use crate::database::{Database, get_database_file, try_get_pool};
+use crate::error::MissionControlError;
+use crate::services::{recurring, snapshots, trash};
+
+// How often maintenance jobs check whether they are due
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+// Run periodic maintenance jobs for the lifetime of the app. Jobs only run once
+// the frontend has initialized the database.
+pub fn spawn_scheduler(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = run_jobs(&app).await {
+                println!("Scheduled maintenance failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn run_jobs(app: &AppHandle) -> Result<(), MissionControlError> {
+    let database = app.state::<Database>();
+    let pool = match try_get_pool(&database).await {
+        Some(pool) => pool,
+        None => return Ok(()),
+    };
+
+    recurring::roll_over(&pool).await?;
+    trash::purge_expired(&pool).await?;
+
+    let snapshot_dir = snapshots::snapshot_dir(&get_database_file(&database).await?);
+    snapshots::run_scheduled_snapshot(&pool, &snapshot_dir).await?;
+
+    Ok(())
+}
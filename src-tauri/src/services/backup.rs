@@ -0,0 +1,826 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use sqlx::{sqlite::SqlitePool, Row, Sqlite, Transaction};
+use crate::error::MissionControlError;
+use crate::migrations;
+use crate::recurrence;
+use crate::services::{journal, ordering, tags, todos};
+use crate::types::{
+    BackupDocument, BackupProject, BackupScene, BackupSession, BackupSetting, BackupSummary,
+    BackupTag, BackupTemplate, BackupTemplateTodo, BackupTodo, ImportMode, SessionOutcome,
+};
+
+pub const BACKUP_FORMAT: &str = "missioncontrol-backup";
+// 2 added the trash and the IDs of sessions, scenes and tags; 3 added templates and settings
+pub const BACKUP_VERSION: u32 = 3;
+
+pub fn summarize(document: &BackupDocument) -> BackupSummary {
+    BackupSummary {
+        projects: document.projects.len(),
+        todos: document.projects.iter().map(|project| project.todos.len()).sum(),
+        scenes: document.projects.iter().filter(|project| project.excalidraw.is_some()).count(),
+        sessions: document
+            .projects
+            .iter()
+            .flat_map(|project| &project.todos)
+            .map(|todo| todo.sessions.len())
+            .sum(),
+        templates: document.templates.len(),
+    }
+}
+
+// Snapshot every project with its todos, focus sessions and whiteboard, every tag and
+// template, and the settings. Items in the trash are kept with their deletion time.
+// Everything is read in one transaction so the parts agree with each other.
+pub async fn build_document(pool: &SqlitePool) -> Result<BackupDocument, MissionControlError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start backup transaction", e))?;
+
+    let project_rows = sqlx::query("SELECT id, name, description, created_at, color, icon, archived_at, deleted_at FROM projects ORDER BY id ASC")
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read projects for backup", e))?;
+
+    let todo_rows = sqlx::query("SELECT id, text, completed, time, created_at, project_id, parent_id, position, priority, due_at, recurrence, deleted_at FROM todos ORDER BY id ASC")
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read todos for backup", e))?;
+
+    let session_rows = sqlx::query(
+        "SELECT id, todo_id, started_at, ended_at, paused_seconds, outcome FROM focus_sessions ORDER BY id ASC"
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to read focus sessions for backup", e))?;
+
+    let tag_rows = sqlx::query(
+        "SELECT todo_tags.todo_id, tags.name FROM todo_tags JOIN tags ON tags.id = todo_tags.tag_id ORDER BY tags.name COLLATE NOCASE"
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to read tags for backup", e))?;
+
+    let tags = sqlx::query("SELECT id, name, created_at FROM tags ORDER BY id ASC")
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read tags for backup", e))?
+        .into_iter()
+        .map(|row| BackupTag {
+            id: row.get("id"),
+            name: row.get("name"),
+            created_at: row.get("created_at"),
+        })
+        .collect();
+
+    // Latest scene per project, matching what get_excalidraw_data returns; a project
+    // in the trash has its scene there too
+    let scene_rows = sqlx::query(
+        r#"
+        SELECT id, project_id, elements, app_state, updated_at, deleted_at FROM excalidraw_data e
+        WHERE id = (
+            SELECT id FROM excalidraw_data
+            WHERE project_id = e.project_id
+            ORDER BY deleted_at IS NOT NULL, updated_at DESC, id DESC LIMIT 1
+        )
+        "#,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to read excalidraw data for backup", e))?;
+
+    let mut sessions_by_todo: HashMap<i64, Vec<BackupSession>> = HashMap::new();
+    for row in session_rows {
+        let outcome: Option<String> = row.get("outcome");
+        sessions_by_todo.entry(row.get("todo_id")).or_default().push(BackupSession {
+            id: row.get("id"),
+            started_at: row.get("started_at"),
+            ended_at: row.get("ended_at"),
+            paused_seconds: row.get("paused_seconds"),
+            outcome: outcome.as_deref().and_then(SessionOutcome::parse),
+        });
+    }
+
+    let mut tags_by_todo: HashMap<i64, Vec<String>> = HashMap::new();
+    for row in tag_rows {
+        tags_by_todo.entry(row.get("todo_id")).or_default().push(row.get("name"));
+    }
+
+    let mut todos_by_project: HashMap<i64, Vec<BackupTodo>> = HashMap::new();
+    for row in todo_rows {
+        let id: i64 = row.get("id");
+        let completed_int: i64 = row.get("completed");
+        todos_by_project.entry(row.get("project_id")).or_default().push(BackupTodo {
+            id,
+            text: row.get("text"),
+            completed: completed_int != 0,
+            time: row.get("time"),
+            created_at: row.get("created_at"),
+            parent_id: row.get("parent_id"),
+            position: row.get("position"),
+            priority: u8::try_from(row.get::<i64, _>("priority")).ok(),
+            due_at: row.get("due_at"),
+            recurrence: row
+                .get::<Option<String>, _>("recurrence")
+                .and_then(|rule| serde_json::from_str(&rule).ok()),
+            tags: tags_by_todo.remove(&id).unwrap_or_default(),
+            sessions: sessions_by_todo.remove(&id).unwrap_or_default(),
+            deleted_at: row.get("deleted_at"),
+        });
+    }
+
+    let mut scenes_by_project: HashMap<i64, BackupScene> = scene_rows
+        .into_iter()
+        .map(|row| {
+            (
+                row.get("project_id"),
+                BackupScene {
+                    id: row.get("id"),
+                    elements: row.get("elements"),
+                    app_state: row.get("app_state"),
+                    updated_at: row.get("updated_at"),
+                    deleted_at: row.get("deleted_at"),
+                },
+            )
+        })
+        .collect();
+
+    let projects = project_rows
+        .into_iter()
+        .map(|row| {
+            let id: i64 = row.get("id");
+            BackupProject {
+                id,
+                name: row.get("name"),
+                description: row.get("description"),
+                created_at: row.get("created_at"),
+                color: row.get("color"),
+                icon: row.get("icon"),
+                archived_at: row.get("archived_at"),
+                deleted_at: row.get("deleted_at"),
+                todos: todos_by_project.remove(&id).unwrap_or_default(),
+                excalidraw: scenes_by_project.remove(&id),
+            }
+        })
+        .collect();
+
+    let template_rows = sqlx::query(
+        "SELECT id, name, description, color, icon, elements, app_state, created_at FROM project_templates ORDER BY id ASC"
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to read templates for backup", e))?;
+
+    let template_todo_rows = sqlx::query(
+        "SELECT id, template_id, parent_id, text, time, position FROM template_todos ORDER BY template_id, position, id"
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to read template todos for backup", e))?;
+
+    let settings = sqlx::query("SELECT key, value, updated_at FROM settings ORDER BY key ASC")
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read settings for backup", e))?
+        .into_iter()
+        .map(|row| BackupSetting {
+            key: row.get("key"),
+            value: row.get("value"),
+            updated_at: row.get("updated_at"),
+        })
+        .collect();
+
+    let schema_version = migrations::current_version(&mut *tx).await?;
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to finish backup transaction", e))?;
+
+    let mut todos_by_template: HashMap<i64, Vec<BackupTemplateTodo>> = HashMap::new();
+    for row in template_todo_rows {
+        todos_by_template.entry(row.get("template_id")).or_default().push(BackupTemplateTodo {
+            id: row.get("id"),
+            parent_id: row.get("parent_id"),
+            text: row.get("text"),
+            time: row.get("time"),
+            position: row.get("position"),
+        });
+    }
+
+    let templates = template_rows
+        .into_iter()
+        .map(|row| {
+            let id: i64 = row.get("id");
+            BackupTemplate {
+                id,
+                name: row.get("name"),
+                description: row.get("description"),
+                color: row.get("color"),
+                icon: row.get("icon"),
+                elements: row.get("elements"),
+                app_state: row.get("app_state"),
+                created_at: row.get("created_at"),
+                todos: todos_by_template.remove(&id).unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    Ok(BackupDocument {
+        format: BACKUP_FORMAT.to_string(),
+        version: BACKUP_VERSION,
+        schema_version,
+        exported_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        projects,
+        tags,
+        templates,
+        settings: Some(settings),
+    })
+}
+
+fn validate_document(document: &BackupDocument, mode: ImportMode) -> Result<(), MissionControlError> {
+    if document.format != BACKUP_FORMAT {
+        return Err(MissionControlError::Validation(format!(
+            "Not a MissionControl backup (format '{}')",
+            document.format
+        )));
+    }
+    if document.version > BACKUP_VERSION {
+        return Err(MissionControlError::Conflict(format!(
+            "Backup format version {} is newer than this build supports ({})",
+            document.version, BACKUP_VERSION
+        )));
+    }
+    if mode == ImportMode::ReplaceAll && document.projects.is_empty() {
+        return Err(MissionControlError::Validation(
+            "Cannot replace all data with a backup that has no projects".to_string(),
+        ));
+    }
+
+    let mut names = HashSet::new();
+    for project in &document.projects {
+        if project.name.trim().is_empty() {
+            return Err(MissionControlError::Validation("Backup contains a project without a name".to_string()));
+        }
+        if !names.insert(project.name.as_str()) {
+            return Err(MissionControlError::Validation(format!(
+                "Backup contains the project name '{}' more than once",
+                project.name
+            )));
+        }
+        if let Some(todo) = project.todos.iter().find(|todo| todo.priority.is_some_and(|p| p > todos::LOWEST_PRIORITY)) {
+            return Err(MissionControlError::Validation(format!(
+                "Backup todo '{}' has an invalid priority",
+                todo.text
+            )));
+        }
+        for todo in &project.todos {
+            if let Some(Err(e)) = todo.recurrence.as_ref().map(recurrence::validate) {
+                return Err(MissionControlError::Validation(format!(
+                    "Backup todo '{}' has an invalid recurrence: {}",
+                    todo.text, e
+                )));
+            }
+        }
+    }
+
+    let mut template_names = HashSet::new();
+    for template in &document.templates {
+        if template.name.trim().is_empty() {
+            return Err(MissionControlError::Validation("Backup contains a template without a name".to_string()));
+        }
+        if !template_names.insert(template.name.as_str()) {
+            return Err(MissionControlError::Validation(format!(
+                "Backup contains the template name '{}' more than once",
+                template.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Pick a name that doesn't collide with an existing row of `table` (projects or
+// project_templates)
+async fn available_name(table: &str, name: &str, tx: &mut Transaction<'_, Sqlite>) -> Result<String, MissionControlError> {
+    let mut candidate = name.to_string();
+    let mut attempt = 1;
+    loop {
+        let taken: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {} WHERE name = ?", table))
+            .bind(&candidate)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to check name", e))?;
+        if taken == 0 {
+            return Ok(candidate);
+        }
+        candidate = if attempt == 1 {
+            format!("{} (imported)", name)
+        } else {
+            format!("{} (imported {})", name, attempt)
+        };
+        attempt += 1;
+    }
+}
+
+// Write a backup into the database in one transaction. Replacing all data restores
+// it exactly, IDs, order, trash, templates and settings included; merging adds the
+// projects outside the trash and the templates as new ones with fresh IDs, and keeps
+// the current settings. Either way at least one project must be left open.
+pub async fn apply_document(
+    document: &BackupDocument,
+    mode: ImportMode,
+    pool: &SqlitePool,
+) -> Result<BackupSummary, MissionControlError> {
+    validate_document(document, mode)?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start import transaction", e))?;
+
+    let restore = mode == ImportMode::ReplaceAll;
+    if restore {
+        for table in ["focus_sessions", "excalidraw_data", "todos", "tags", "projects", "template_todos", "project_templates"] {
+            sqlx::query(&format!("DELETE FROM {}", table))
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| MissionControlError::from_sqlx(&format!("Failed to clear {}", table), e))?;
+        }
+        // The history refers to rows that are gone now
+        journal::clear(&mut tx).await?;
+
+        for tag in &document.tags {
+            sqlx::query("INSERT INTO tags (id, name, created_at) VALUES (?, ?, ?)")
+                .bind(tag.id)
+                .bind(&tag.name)
+                .bind(&tag.created_at)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| MissionControlError::from_sqlx("Failed to import tag", e))?;
+        }
+
+        // Backups made before settings were kept leave the current ones in place
+        if let Some(settings) = &document.settings {
+            sqlx::query("DELETE FROM settings")
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| MissionControlError::from_sqlx("Failed to clear settings", e))?;
+            for setting in settings {
+                sqlx::query("INSERT INTO settings (key, value, updated_at) VALUES (?, ?, ?)")
+                    .bind(&setting.key)
+                    .bind(&setting.value)
+                    .bind(&setting.updated_at)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| MissionControlError::from_sqlx("Failed to import setting", e))?;
+            }
+        }
+    }
+
+    for project in document.projects.iter().filter(|project| restore || project.deleted_at.is_none()) {
+        let name = match mode {
+            ImportMode::ReplaceAll => project.name.clone(),
+            ImportMode::MergeAsNewProjects => available_name("projects", &project.name, &mut tx).await?,
+        };
+
+        // A NULL id lets SQLite assign a fresh one
+        let project_id: i64 = sqlx::query_scalar(
+            "INSERT INTO projects (id, name, description, created_at, color, icon, archived_at, deleted_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING id"
+        )
+        .bind(restore.then_some(project.id))
+        .bind(&name)
+        .bind(&project.description)
+        .bind(&project.created_at)
+        .bind(&project.color)
+        .bind(&project.icon)
+        .bind(&project.archived_at)
+        .bind(&project.deleted_at)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to import project", e))?;
+
+        let project_todos: Vec<&BackupTodo> = project
+            .todos
+            .iter()
+            .filter(|todo| restore || todo.deleted_at.is_none())
+            .collect();
+
+        // Restored positions are kept as they are. Otherwise they are renumbered in
+        // backup order, which also covers backups made before todos had one (those
+        // keep their creation order); todos in the trash have none.
+        let keep_positions = restore && project_todos.iter().all(|todo| todo.position.is_some() || todo.deleted_at.is_some());
+        let mut ordered: Vec<(usize, &&BackupTodo)> = project_todos.iter().enumerate().collect();
+        ordered.sort_by(|(a_index, a), (b_index, b)| {
+            let a_position = a.position.unwrap_or(f64::INFINITY);
+            let b_position = b.position.unwrap_or(f64::INFINITY);
+            a_position.total_cmp(&b_position).then(a_index.cmp(b_index))
+        });
+        let mut positions = vec![None; project_todos.len()];
+        for (rank, (index, todo)) in ordered.iter().enumerate() {
+            positions[*index] = if keep_positions {
+                todo.position
+            } else {
+                Some((rank + 1) as f64 * ordering::POSITION_GAP)
+            };
+        }
+
+        let mut todo_ids: HashMap<i64, i64> = HashMap::new();
+        for (index, todo) in project_todos.iter().enumerate() {
+            let todo_id: i64 = sqlx::query_scalar(
+                "INSERT INTO todos (id, text, completed, time, priority, due_at, recurrence, created_at, project_id, position, deleted_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id"
+            )
+            .bind(restore.then_some(todo.id))
+            .bind(&todo.text)
+            .bind(if todo.completed { 1i64 } else { 0i64 })
+            .bind(todo.time)
+            .bind(i64::from(todo.priority.unwrap_or(todos::DEFAULT_PRIORITY)))
+            .bind(todo.due_at.as_deref().and_then(todos::parse_due_at).map(todos::format_due_at))
+            .bind(todo.recurrence.as_ref().and_then(|rule| serde_json::to_string(rule).ok()))
+            .bind(&todo.created_at)
+            .bind(project_id)
+            .bind(positions[index])
+            .bind(&todo.deleted_at)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to import todo", e))?;
+            todo_ids.insert(todo.id, todo_id);
+
+            for name in &todo.tags {
+                tags::attach_tag(todo_id, name, &mut tx).await?;
+            }
+
+            for session in &todo.sessions {
+                sqlx::query(
+                    "INSERT INTO focus_sessions (id, todo_id, project_id, started_at, ended_at, paused_seconds, outcome) VALUES (?, ?, ?, ?, ?, ?, ?)"
+                )
+                .bind(session.id.filter(|_| restore))
+                .bind(todo_id)
+                .bind(project_id)
+                .bind(&session.started_at)
+                .bind(&session.ended_at)
+                .bind(session.paused_seconds)
+                .bind(session.outcome.map(|outcome| outcome.as_str()))
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| MissionControlError::from_sqlx("Failed to import focus session", e))?;
+            }
+        }
+
+        // Parents are linked once every todo has its new ID, so their order doesn't matter
+        for todo in &project_todos {
+            if let Some(parent_id) = todo.parent_id.and_then(|parent_id| todo_ids.get(&parent_id)) {
+                sqlx::query("UPDATE todos SET parent_id = ? WHERE id = ?")
+                    .bind(parent_id)
+                    .bind(todo_ids[&todo.id])
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| MissionControlError::from_sqlx("Failed to link imported subtask", e))?;
+            }
+        }
+
+        if let Some(scene) = project.excalidraw.as_ref().filter(|scene| restore || scene.deleted_at.is_none()) {
+            sqlx::query(
+                "INSERT INTO excalidraw_data (id, elements, app_state, updated_at, project_id, deleted_at) VALUES (?, ?, ?, ?, ?, ?)"
+            )
+            .bind(scene.id.filter(|_| restore))
+            .bind(&scene.elements)
+            .bind(&scene.app_state)
+            .bind(&scene.updated_at)
+            .bind(project_id)
+            .bind(&scene.deleted_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to import excalidraw data", e))?;
+        }
+    }
+
+    for template in &document.templates {
+        let name = match mode {
+            ImportMode::ReplaceAll => template.name.clone(),
+            ImportMode::MergeAsNewProjects => available_name("project_templates", &template.name, &mut tx).await?,
+        };
+
+        let template_id: i64 = sqlx::query_scalar(
+            "INSERT INTO project_templates (id, name, description, color, icon, elements, app_state, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING id"
+        )
+        .bind(restore.then_some(template.id))
+        .bind(&name)
+        .bind(&template.description)
+        .bind(&template.color)
+        .bind(&template.icon)
+        .bind(&template.elements)
+        .bind(&template.app_state)
+        .bind(&template.created_at)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to import template", e))?;
+
+        let mut todo_ids: HashMap<i64, i64> = HashMap::new();
+        for todo in &template.todos {
+            let todo_id: i64 = sqlx::query_scalar(
+                "INSERT INTO template_todos (id, template_id, text, time, position) VALUES (?, ?, ?, ?, ?) RETURNING id"
+            )
+            .bind(restore.then_some(todo.id))
+            .bind(template_id)
+            .bind(&todo.text)
+            .bind(todo.time)
+            .bind(todo.position)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to import template todo", e))?;
+            todo_ids.insert(todo.id, todo_id);
+        }
+        for todo in &template.todos {
+            if let Some(parent_id) = todo.parent_id.and_then(|parent_id| todo_ids.get(&parent_id)) {
+                sqlx::query("UPDATE template_todos SET parent_id = ? WHERE id = ?")
+                    .bind(parent_id)
+                    .bind(todo_ids[&todo.id])
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| MissionControlError::from_sqlx("Failed to link imported template subtask", e))?;
+            }
+        }
+    }
+
+    let open_projects: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM projects WHERE deleted_at IS NULL AND archived_at IS NULL"
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to count open projects", e))?;
+    if open_projects == 0 {
+        return Err(MissionControlError::Validation(
+            "The backup has no project that is neither archived nor in the trash".to_string(),
+        ));
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit import", e))?;
+
+    Ok(summarize(document))
+}
+
+pub async fn export_to_file(path: &Path, pool: &SqlitePool) -> Result<BackupSummary, MissionControlError> {
+    let document = build_document(pool).await?;
+    let json = serde_json::to_string_pretty(&document).map_err(|e| {
+        MissionControlError::Validation(format!("Failed to serialize backup: {}", e))
+    })?;
+
+    tokio::fs::write(path, json)
+        .await
+        .map_err(|e| MissionControlError::from_io(&format!("Failed to write backup to {}", path.display()), e))?;
+
+    Ok(summarize(&document))
+}
+
+pub async fn import_from_file(path: &Path, mode: ImportMode, pool: &SqlitePool) -> Result<BackupSummary, MissionControlError> {
+    let json = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| MissionControlError::from_io(&format!("Failed to read backup from {}", path.display()), e))?;
+
+    let document: BackupDocument = serde_json::from_str(&json).map_err(|e| {
+        MissionControlError::Validation(format!("Backup file is not valid: {}", e))
+    })?;
+
+    apply_document(&document, mode, pool).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Weekday;
+    use crate::services::{excalidraw, projects, recurring, sessions, settings, templates, todos, trash};
+    use crate::test_support::{migrated_pool, project, todo};
+    use crate::types::{
+        Recurrence, SaveTemplateOptions, SubtaskPolicy, TrashKind, TrashPolicy, UpdateProject, UpdateTodo,
+    };
+
+    // Tables a backup covers; the search index is derived from them but has to
+    // come back the same
+    const TABLES: &[&str] = &[
+        "projects",
+        "todos",
+        "focus_sessions",
+        "excalidraw_data",
+        "tags",
+        "todo_tags",
+        "search_index",
+        "project_templates",
+        "template_todos",
+        "settings",
+    ];
+
+    // Every row of `table` with every column, quoted, in a stable order
+    async fn dump(table: &str, pool: &SqlitePool) -> Vec<String> {
+        let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?) ORDER BY cid")
+            .bind(table)
+            .fetch_all(pool)
+            .await
+            .unwrap();
+        let row = columns.iter().map(|column| format!("quote(\"{}\")", column)).collect::<Vec<_>>().join(" || ', ' || ");
+        sqlx::query_scalar(&format!("SELECT {} FROM {} ORDER BY 1", row, table))
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn dump_all(pool: &SqlitePool) -> Vec<Vec<String>> {
+        let mut tables = Vec::new();
+        for table in TABLES {
+            tables.push(dump(table, pool).await);
+        }
+        tables
+    }
+
+    // A bit of everything: nested and reordered todos, tags (one unused), sessions,
+    // whiteboards, an archived project, a todo and a project in the trash, a template
+    // with subtasks and a setting
+    async fn seed(pool: &SqlitePool) {
+        let inbox = project("Inbox", pool).await;
+        let side = project("Side", pool).await;
+        let old = project("Old", pool).await;
+        projects::update_project(
+            inbox.id,
+            UpdateProject { color: Some("#3b82f6".to_string()), icon: Some("📥".to_string()), ..Default::default() },
+            pool,
+        )
+        .await
+        .unwrap();
+
+        let plan = todo("Plan launch", inbox.id, None, pool).await;
+        let draft = todo("Draft notes", inbox.id, Some(plan.id), pool).await;
+        todo("Review notes", inbox.id, Some(plan.id), pool).await;
+        let ship = todo("Ship", inbox.id, None, pool).await;
+        let scrap = todo("Scrap", inbox.id, None, pool).await;
+        ordering::reorder_todo(ship.id, None, Some(plan.id), pool).await.unwrap();
+        todos::update_todo_by_id(
+            ship.id,
+            UpdateTodo { priority: Some(0), due_at: Some("2024-03-01".to_string()), ..Default::default() },
+            pool,
+        )
+        .await
+        .unwrap();
+        recurring::set_recurrence(ship.id, Some(Recurrence::Weekly { days: vec![Weekday::Mon, Weekday::Thu] }), pool)
+            .await
+            .unwrap();
+        todos::update_todo_by_id(draft.id, UpdateTodo { completed: Some(true), ..Default::default() }, pool)
+            .await
+            .unwrap();
+        todos::delete_todo_by_id(scrap.id, Some(SubtaskPolicy::Delete), pool).await.unwrap();
+
+        tags::add_tag(draft.id, "writing", pool).await.unwrap();
+        tags::add_tag(ship.id, "Work", pool).await.unwrap();
+        tags::add_tag(ship.id, "someday", pool).await.unwrap();
+        tags::remove_tag(ship.id, "someday", pool).await.unwrap();
+
+        for (todo_id, outcome) in [(draft.id, SessionOutcome::Completed), (ship.id, SessionOutcome::Skipped), (ship.id, SessionOutcome::Completed)] {
+            let session = sessions::start_session(todo_id, inbox.id, pool).await.unwrap();
+            sessions::end_session(session.id, outcome, pool).await.unwrap();
+        }
+
+        excalidraw::save_excalidraw_data(r#"[{"id":"box"}]"#.to_string(), r#"{"zoom":1}"#.to_string(), inbox.id, pool)
+            .await
+            .unwrap();
+        excalidraw::save_excalidraw_data(r#"[{"id":"old"}]"#.to_string(), "{}".to_string(), old.id, pool)
+            .await
+            .unwrap();
+        templates::save_project_as_template(
+            inbox.id,
+            &SaveTemplateOptions { name: None, include_whiteboard: true },
+            pool,
+        )
+        .await
+        .unwrap();
+        trash::set_policy(TrashPolicy { retention_days: 14 }, pool).await.unwrap();
+
+        todo("Side task", side.id, None, pool).await;
+        projects::archive_project(side.id, pool).await.unwrap();
+        todo("Forgotten", old.id, None, pool).await;
+        projects::delete_project_by_id(old.id, pool).await.unwrap();
+    }
+
+    // Export as the file would hold it
+    async fn export(pool: &SqlitePool) -> BackupDocument {
+        let json = serde_json::to_string_pretty(&build_document(pool).await.unwrap()).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[tokio::test]
+    async fn replace_all_restores_every_table() {
+        let source = migrated_pool().await;
+        seed(&source).await;
+        let document = export(&source).await;
+
+        // Whatever the target held is replaced, templates and settings included
+        let target = migrated_pool().await;
+        let scratch = project("Scratch", &target).await;
+        templates::save_project_as_template(scratch.id, &SaveTemplateOptions::default(), &target)
+            .await
+            .unwrap();
+        settings::set_setting("scratch", &true, &target).await.unwrap();
+        let summary = apply_document(&document, ImportMode::ReplaceAll, &target).await.unwrap();
+        assert_eq!(summary.templates, 1);
+
+        for table in TABLES {
+            let expected = dump(table, &source).await;
+            assert!(!expected.is_empty(), "{} should have been seeded", table);
+            assert_eq!(dump(table, &target).await, expected, "{} differs after restoring", table);
+        }
+
+        // The trash works on the restored data as it did before
+        let old: i64 = sqlx::query_scalar("SELECT id FROM projects WHERE name = 'Old'").fetch_one(&target).await.unwrap();
+        trash::restore_from_trash(TrashKind::Project, old, &target).await.unwrap();
+        assert_eq!(todos::get_todos_by_project(old, &target).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn merge_adds_new_projects_without_the_trash() {
+        let source = migrated_pool().await;
+        seed(&source).await;
+        let document = export(&source).await;
+
+        let target = migrated_pool().await;
+        let inbox = project("Inbox", &target).await;
+        let existing = todo("Existing", inbox.id, None, &target).await;
+        tags::add_tag(existing.id, "work", &target).await.unwrap();
+        templates::save_project_as_template(inbox.id, &SaveTemplateOptions::default(), &target)
+            .await
+            .unwrap();
+        let before = dump("todos", &target).await;
+
+        let summary = apply_document(&document, ImportMode::MergeAsNewProjects, &target).await.unwrap();
+        assert_eq!(summary.projects, 3);
+
+        // What was there is untouched
+        let existing_rows: Vec<String> = dump("todos", &target).await.into_iter().filter(|row| row.starts_with(&format!("{},", existing.id))).collect();
+        assert_eq!(existing_rows, before);
+
+        let names: Vec<String> = sqlx::query_scalar("SELECT name FROM projects ORDER BY id").fetch_all(&target).await.unwrap();
+        assert_eq!(names, vec!["Inbox", "Inbox (imported)", "Side"]);
+        let side = projects::get_all_projects(true, &target).await.unwrap().into_iter().find(|project| project.name == "Side").unwrap();
+        assert!(side.archived_at.is_some());
+
+        // Fresh IDs and renumbered positions, listed depth-first; the trashed todo stays behind
+        let imported: i64 = sqlx::query_scalar("SELECT id FROM projects WHERE name = 'Inbox (imported)'").fetch_one(&target).await.unwrap();
+        let todos = todos::get_todos_by_project(imported, &target).await.unwrap();
+        let listed: Vec<(&str, u32, f64)> = todos.iter().map(|todo| (todo.text.as_str(), todo.depth, todo.position)).collect();
+        assert_eq!(
+            listed,
+            vec![("Plan launch", 0, 1024.0), ("Draft notes", 1, 3072.0), ("Review notes", 1, 4096.0), ("Ship", 0, 2048.0)]
+        );
+        assert!(todos.iter().all(|todo| todo.id > existing.id));
+        assert_eq!(todos[3].priority, 0);
+        assert!(todos[3].recurrence.is_some());
+        assert_eq!(todos[3].tags, vec!["work"]);
+        assert_eq!(todos[1].tags, vec!["writing"]);
+        assert!(todos[1].completed);
+
+        // Tags merge by name with the existing ones
+        let tag_names: Vec<String> = sqlx::query_scalar("SELECT name FROM tags ORDER BY id").fetch_all(&target).await.unwrap();
+        assert_eq!(tag_names, vec!["work", "writing"]);
+
+        assert_eq!(sessions::get_sessions_by_project(imported, &target).await.unwrap().len(), 3);
+        assert!(excalidraw::get_excalidraw_data(imported, &target).await.unwrap().is_some());
+        let trashed: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM todos WHERE deleted_at IS NOT NULL").fetch_one(&target).await.unwrap();
+        assert_eq!(trashed, 0);
+
+        // Templates are added beside the existing ones, subtasks and all; settings stay
+        let template_names: Vec<String> = templates::list_templates(&target)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|template| template.name)
+            .collect();
+        assert_eq!(template_names, vec!["Inbox", "Inbox (imported)"]);
+        let nested: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM template_todos t JOIN project_templates p ON p.id = t.template_id WHERE p.name = 'Inbox (imported)' AND t.parent_id IS NOT NULL"
+        )
+        .fetch_one(&target)
+        .await
+        .unwrap();
+        assert_eq!(nested, 2);
+        assert_eq!(trash::get_policy(&target).await.unwrap().retention_days, 30);
+    }
+
+    #[tokio::test]
+    async fn replace_all_needs_an_open_project() {
+        let source = migrated_pool().await;
+        let inbox = project("Inbox", &source).await;
+        let side = project("Side", &source).await;
+        todo("Keep", inbox.id, None, &source).await;
+        projects::archive_project(inbox.id, &source).await.unwrap();
+        let mut document = export(&source).await;
+        // Only an archived project and one in the trash are left
+        document.projects.iter_mut().find(|project| project.id == side.id).unwrap().deleted_at =
+            Some("2024-01-01 00:00:00".to_string());
+
+        let target = migrated_pool().await;
+        let scratch = project("Scratch", &target).await;
+        let before = dump_all(&target).await;
+
+        let result = apply_document(&document, ImportMode::ReplaceAll, &target).await;
+        assert_eq!(result.unwrap_err().kind(), "validation");
+        assert_eq!(dump_all(&target).await, before);
+        assert_eq!(projects::get_project_by_id(scratch.id, &target).await.unwrap().name, "Scratch");
+    }
+}
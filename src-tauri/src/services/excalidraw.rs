@@ -1,4 +1,6 @@
 use sqlx::{sqlite::SqlitePool, Row};
+use crate::error::MissionControlError;
+use crate::services::{journal, ordering, projects};
 use crate::types::ExcalidrawData;
 
 pub async fn save_excalidraw_data(
@@ -6,49 +8,74 @@ pub async fn save_excalidraw_data(
     app_state: String,
     project_id: i64,
     pool: &SqlitePool,
-) -> Result<(), String> {
+) -> Result<(), MissionControlError> {
     println!("Saving Excalidraw data for project: {}", project_id);
 
-    // Delete existing data for this project
-    sqlx::query("DELETE FROM excalidraw_data WHERE project_id = ?")
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    ordering::ensure_project_exists(project_id, &mut tx).await?;
+    projects::ensure_not_archived(project_id, &mut *tx).await?;
+
+    // Autosaves of one board with nothing else in between undo as one edit: the
+    // first replaces the scene and journals it, the rest only rewrite it in place
+    let merge_key = format!("whiteboard:{}", project_id);
+    let continued = if journal::continues(&merge_key, &mut tx).await? {
+        sqlx::query(
+            "UPDATE excalidraw_data SET elements = ?, app_state = ?, updated_at = CURRENT_TIMESTAMP WHERE project_id = ? AND deleted_at IS NULL"
+        )
+        .bind(&elements)
+        .bind(&app_state)
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to save excalidraw data", e))?
+        .rows_affected()
+            > 0
+    } else {
+        false
+    };
+
+    if !continued {
+        journal::begin_merged("Edit whiteboard", &merge_key, &mut tx).await?;
+
+        // Delete existing data for this project
+        sqlx::query("DELETE FROM excalidraw_data WHERE project_id = ? AND deleted_at IS NULL")
+            .bind(project_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to clear excalidraw data", e))?;
+
+        // Insert new data (even if empty - this ensures project isolation)
+        sqlx::query(
+            "INSERT INTO excalidraw_data (elements, app_state, project_id) VALUES (?, ?, ?)"
+        )
+        .bind(&elements)
+        .bind(&app_state)
         .bind(project_id)
-        .execute(pool)
+        .execute(&mut *tx)
         .await
-        .map_err(|e| {
-            let error_msg = format!("Failed to clear excalidraw data: {}", e);
-            println!("{}", error_msg);
-            error_msg
-        })?;
-
-    // Insert new data (even if empty - this ensures project isolation)
-    sqlx::query(
-        "INSERT INTO excalidraw_data (elements, app_state, project_id) VALUES (?, ?, ?)"
-    )
-    .bind(&elements)
-    .bind(&app_state)
-    .bind(project_id)
-    .execute(pool)
-    .await
-    .map_err(|e| {
-        let error_msg = format!("Failed to save excalidraw data: {}", e);
-        println!("{}", error_msg);
-        error_msg
-    })?;
+        .map_err(|e| MissionControlError::from_sqlx("Failed to save excalidraw data", e))?;
+
+        journal::finish(&mut tx).await?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit excalidraw data", e))?;
 
     println!("Excalidraw data saved successfully for project {}", project_id);
     Ok(())
 }
 
-pub async fn get_excalidraw_data(project_id: i64, pool: &SqlitePool) -> Result<Option<ExcalidrawData>, String> {
-    let row = sqlx::query("SELECT id, elements, app_state, updated_at, project_id FROM excalidraw_data WHERE project_id = ? ORDER BY updated_at DESC LIMIT 1")
+pub async fn get_excalidraw_data(project_id: i64, pool: &SqlitePool) -> Result<Option<ExcalidrawData>, MissionControlError> {
+    let row = sqlx::query("SELECT id, elements, app_state, updated_at, project_id FROM excalidraw_data WHERE project_id = ? AND deleted_at IS NULL ORDER BY updated_at DESC LIMIT 1")
         .bind(project_id)
         .fetch_optional(pool)
         .await
-        .map_err(|e| {
-            let error_msg = format!("Failed to fetch excalidraw data: {}", e);
-            println!("{}", error_msg);
-            error_msg
-        })?;
+        .map_err(|e| MissionControlError::from_sqlx("Failed to fetch excalidraw data", e))?;
 
     match row {
         Some(row) => {
@@ -66,4 +93,83 @@ pub async fn get_excalidraw_data(project_id: i64, pool: &SqlitePool) -> Result<O
             Ok(None)
         }
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{migrated_pool, project, todo};
+
+    async fn save(elements: &str, project_id: i64, pool: &SqlitePool) -> Result<(), MissionControlError> {
+        save_excalidraw_data(elements.to_string(), "{}".to_string(), project_id, pool).await
+    }
+
+    async fn board(project_id: i64, pool: &SqlitePool) -> Option<String> {
+        get_excalidraw_data(project_id, pool).await.unwrap().map(|data| data.elements)
+    }
+
+    async fn undo_labels(pool: &SqlitePool) -> Vec<String> {
+        journal::get_history(pool).await.unwrap().undo.into_iter().map(|entry| entry.label).collect()
+    }
+
+    #[tokio::test]
+    async fn whiteboard_errors_have_the_right_kind() {
+        let pool = migrated_pool().await;
+        project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+
+        let missing = save_excalidraw_data("[]".to_string(), "{}".to_string(), 42, &pool).await;
+        assert_eq!(missing.unwrap_err().kind(), "not_found");
+        assert!(get_excalidraw_data(42, &pool).await.unwrap().is_none());
+
+        save_excalidraw_data("[1]".to_string(), "{}".to_string(), side.id, &pool).await.unwrap();
+        projects::archive_project(side.id, &pool).await.unwrap();
+        let archived = save_excalidraw_data("[2]".to_string(), "{}".to_string(), side.id, &pool).await;
+        assert_eq!(archived.unwrap_err().kind(), "conflict");
+        assert_eq!(get_excalidraw_data(side.id, &pool).await.unwrap().unwrap().elements, "[1]");
+
+        pool.close().await;
+        let closed = save_excalidraw_data("[]".to_string(), "{}".to_string(), side.id, &pool).await;
+        assert_eq!(closed.unwrap_err().kind(), "database");
+        assert_eq!(get_excalidraw_data(side.id, &pool).await.unwrap_err().kind(), "database");
+    }
+
+    #[tokio::test]
+    async fn autosaves_of_one_board_undo_together() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+        save("[1]", inbox.id, &pool).await.unwrap();
+        save("[2]", inbox.id, &pool).await.unwrap();
+        save("[3]", inbox.id, &pool).await.unwrap();
+        assert_eq!(undo_labels(&pool).await, ["Edit whiteboard", "Create project", "Create project"]);
+
+        journal::undo(&pool).await.unwrap().unwrap();
+        assert_eq!(board(inbox.id, &pool).await, None);
+        journal::redo(&pool).await.unwrap().unwrap();
+        assert_eq!(board(inbox.id, &pool).await.as_deref(), Some("[3]"));
+
+        // Another board, another change or an undo in between starts a new entry
+        save("[4]", inbox.id, &pool).await.unwrap();
+        save("[a]", side.id, &pool).await.unwrap();
+        save("[5]", inbox.id, &pool).await.unwrap();
+        todo("Tidy up", inbox.id, None, &pool).await;
+        save("[6]", inbox.id, &pool).await.unwrap();
+        save("[7]", inbox.id, &pool).await.unwrap();
+        assert_eq!(undo_labels(&pool).await.len(), 8);
+
+        journal::undo(&pool).await.unwrap().unwrap();
+        assert_eq!(board(inbox.id, &pool).await.as_deref(), Some("[5]"));
+        save("[8]", inbox.id, &pool).await.unwrap();
+        journal::undo(&pool).await.unwrap().unwrap();
+        assert_eq!(board(inbox.id, &pool).await.as_deref(), Some("[5]"));
+        for _ in 0..2 {
+            journal::undo(&pool).await.unwrap().unwrap();
+        }
+        assert_eq!(board(inbox.id, &pool).await.as_deref(), Some("[4]"));
+        journal::undo(&pool).await.unwrap().unwrap();
+        assert_eq!(board(side.id, &pool).await, None);
+        journal::undo(&pool).await.unwrap().unwrap();
+        assert_eq!(board(inbox.id, &pool).await.as_deref(), Some("[3]"));
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::HashSet;
+use std::path::Path;
+use sqlx::sqlite::SqlitePool;
+use crate::error::MissionControlError;
+use chrono::NaiveDateTime;
+use crate::formats::{csv, ics, markdown, todotxt};
+use crate::services::{excalidraw, projects, reports, sessions, todos};
+use crate::types::{
+    CsvExportSummary, CsvScope, FocusSession, IcsExportOptions, MarkdownExportOptions, TodoTxtExportSummary,
+};
+
+pub async fn export_project_markdown(
+    project_id: i64,
+    options: MarkdownExportOptions,
+    pool: &SqlitePool,
+) -> Result<String, MissionControlError> {
+    let project = projects::get_project_by_id(project_id, pool).await?;
+    let todos = todos::get_todos_by_project(project_id, pool).await?;
+
+    let report = if options.include_time_summary {
+        Some(reports::get_project_report(project_id, None, None, pool).await?)
+    } else {
+        None
+    };
+
+    let board_notes = if options.include_board {
+        excalidraw::get_excalidraw_data(project_id, pool)
+            .await?
+            .map(|data| markdown::board_text(&data.elements))
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    Ok(markdown::render_project(&project, &todos, report.as_ref(), &board_notes, &options))
+}
+
+// `<project>.todo.txt`, keeping only characters that are safe in a file name
+fn todotxt_file_name(project_name: &str, taken: &mut HashSet<String>) -> String {
+    let stem: String = todotxt::project_tag(project_name)
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+    let stem = if stem.trim_matches('.').is_empty() { "project".to_string() } else { stem };
+
+    let mut name = format!("{}.todo.txt", stem);
+    let mut attempt = 2;
+    while !taken.insert(name.to_lowercase()) {
+        name = format!("{}-{}.todo.txt", stem, attempt);
+        attempt += 1;
+    }
+    name
+}
+
+// Write every project's todos in todo.txt format: one combined file at `path`, or
+// with `per_project` one `<project>.todo.txt` per project inside the directory `path`
+pub async fn export_todotxt(path: &Path, per_project: bool, pool: &SqlitePool) -> Result<TodoTxtExportSummary, MissionControlError> {
+    let mut by_project = Vec::new();
+    for project in projects::get_all_projects(true, pool).await? {
+        let todos = todos::get_todos_by_project(project.id, pool).await?;
+        by_project.push((project, todos));
+    }
+    let todo_count = by_project.iter().map(|(_, todos)| todos.len()).sum();
+
+    let mut outputs = Vec::new();
+    if per_project {
+        tokio::fs::create_dir_all(path)
+            .await
+            .map_err(|e| MissionControlError::from_io(&format!("Failed to create {}", path.display()), e))?;
+
+        let mut taken = HashSet::new();
+        for (project, todos) in &by_project {
+            let pairs: Vec<_> = todos.iter().map(|todo| (project, todo)).collect();
+            outputs.push((path.join(todotxt_file_name(&project.name, &mut taken)), todotxt::render(&pairs)));
+        }
+    } else {
+        let pairs: Vec<_> = by_project
+            .iter()
+            .flat_map(|(project, todos)| todos.iter().map(move |todo| (project, todo)))
+            .collect();
+        outputs.push((path.to_path_buf(), todotxt::render(&pairs)));
+    }
+
+    let mut files = Vec::new();
+    for (file, contents) in outputs {
+        tokio::fs::write(&file, contents)
+            .await
+            .map_err(|e| MissionControlError::from_io(&format!("Failed to write {}", file.display()), e))?;
+        files.push(file.display().to_string());
+    }
+
+    Ok(TodoTxtExportSummary { files, todos: todo_count })
+}
+
+const TODO_COLUMNS: [&str; 10] = [
+    "id", "text", "completed", "time", "created_at", "project", "parent_id", "priority", "due_at", "tags",
+];
+const SESSION_COLUMNS: [&str; 9] = [
+    "id", "todo_id", "todo", "project", "started_at", "ended_at", "paused_seconds", "worked_minutes", "outcome",
+];
+
+// Minutes worked in a finished session; blank while it is still open
+fn worked_minutes(session: &FocusSession) -> String {
+    let parse = |value: &str| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").ok();
+    match (parse(&session.started_at), session.ended_at.as_deref().and_then(parse)) {
+        (Some(started), Some(ended)) => {
+            let seconds = ((ended - started).num_seconds() - session.paused_seconds).max(0);
+            format!("{:.1}", seconds as f64 / 60.0)
+        }
+        _ => String::new(),
+    }
+}
+
+// Write todos or focus sessions of every project to a CSV file for spreadsheets
+pub async fn export_csv(
+    scope: CsvScope,
+    path: &Path,
+    delimiter: char,
+    pool: &SqlitePool,
+) -> Result<CsvExportSummary, MissionControlError> {
+    if !csv::valid_delimiter(delimiter) {
+        return Err(MissionControlError::Validation(format!("'{}' cannot be used as a CSV delimiter", delimiter.escape_default())));
+    }
+
+    let mut rows = Vec::new();
+    for project in projects::get_all_projects(true, pool).await? {
+        let project_todos = todos::get_todos_by_project(project.id, pool).await?;
+        match scope {
+            CsvScope::Todos => {
+                rows.extend(project_todos.into_iter().map(|todo| {
+                    vec![
+                        todo.id.to_string(),
+                        todo.text,
+                        todo.completed.to_string(),
+                        todo.time.to_string(),
+                        todo.created_at,
+                        project.name.clone(),
+                        todo.parent_id.map(|id| id.to_string()).unwrap_or_default(),
+                        format!("P{}", todo.priority),
+                        todo.due_at.unwrap_or_default(),
+                        todo.tags.join(", "),
+                    ]
+                }));
+            }
+            CsvScope::Sessions => {
+                for session in sessions::get_sessions_by_project(project.id, pool).await? {
+                    let todo_text = project_todos
+                        .iter()
+                        .find(|todo| todo.id == session.todo_id)
+                        .map(|todo| todo.text.clone())
+                        .unwrap_or_default();
+                    rows.push(vec![
+                        session.id.to_string(),
+                        session.todo_id.to_string(),
+                        todo_text,
+                        project.name.clone(),
+                        session.started_at.clone(),
+                        session.ended_at.clone().unwrap_or_default(),
+                        session.paused_seconds.to_string(),
+                        worked_minutes(&session),
+                        session.outcome.map(|outcome| outcome.as_str().to_string()).unwrap_or_default(),
+                    ]);
+                }
+            }
+        }
+    }
+
+    let header: &[&str] = match scope {
+        CsvScope::Todos => &TODO_COLUMNS,
+        CsvScope::Sessions => &SESSION_COLUMNS,
+    };
+    tokio::fs::write(path, csv::render(header, &rows, delimiter))
+        .await
+        .map_err(|e| MissionControlError::from_io(&format!("Failed to write {}", path.display()), e))?;
+
+    Ok(CsvExportSummary {
+        path: path.display().to_string(),
+        rows: rows.len(),
+    })
+}
+
+// Plan the project's incomplete todos as consecutive calendar blocks from `start_at`
+pub async fn export_ics(
+    project_id: i64,
+    start_at: &str,
+    options: IcsExportOptions,
+    pool: &SqlitePool,
+) -> Result<String, MissionControlError> {
+    let start = ics::parse_start(start_at).ok_or_else(|| {
+        MissionControlError::Validation(format!(
+            "Invalid start time '{}', expected e.g. 2024-03-01T09:00 or an RFC 3339 timestamp",
+            start_at
+        ))
+    })?;
+
+    let project = projects::get_project_by_id(project_id, pool).await?;
+    // A block must end after it starts, so todos without a planned time are left out.
+    // Parents are planned through their subtasks.
+    let all_todos = todos::get_todos_by_project(project_id, pool).await?;
+    let parents = todos::parent_ids(&all_todos);
+    let todos: Vec<_> = all_todos
+        .into_iter()
+        .filter(|todo| !todo.completed && todo.time > 0 && !parents.contains(&todo.id))
+        .collect();
+
+    Ok(ics::render_plan(&project, &todos, start, &options, chrono::Utc::now(), &chrono::Local))
+}
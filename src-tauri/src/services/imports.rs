@@ -0,0 +1,280 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use sqlx::sqlite::SqlitePool;
+use crate::error::MissionControlError;
+use crate::formats::{markdown, todotxt};
+use crate::services::{journal, projects, todos};
+use crate::types::{CreateProject, MarkdownImportItem, MarkdownImportResult, TodoTxtImportSummary};
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// Turn a pasted Markdown checklist into todos. With `commit` false nothing is
+// written and the result is a preview; items whose text already exists in the
+// project are flagged as duplicates and never created.
+pub async fn import_markdown(
+    project_id: i64,
+    text: &str,
+    commit: bool,
+    pool: &SqlitePool,
+) -> Result<MarkdownImportResult, MissionControlError> {
+    projects::get_project_by_id(project_id, pool).await?;
+
+    let parsed = markdown::parse_checklist(text);
+    if parsed.is_empty() {
+        return Err(MissionControlError::Validation("No checklist items (- [ ] or - [x]) found".to_string()));
+    }
+
+    // Existing todos by normalized text; a subtask whose parent is a duplicate goes under the existing todo
+    let mut ids_by_text: HashMap<String, i64> = HashMap::new();
+    for todo in todos::get_todos_by_project(project_id, pool).await? {
+        ids_by_text.entry(normalize(&todo.text)).or_insert(todo.id);
+    }
+    let mut seen: HashSet<String> = ids_by_text.keys().cloned().collect();
+
+    let items: Vec<MarkdownImportItem> = parsed
+        .into_iter()
+        .map(|item| MarkdownImportItem {
+            duplicate: !seen.insert(normalize(&item.text)),
+            line: item.line,
+            depth: item.depth,
+            text: item.text,
+            time: item.minutes.unwrap_or(todos::DEFAULT_TODO_MINUTES),
+            completed: item.completed,
+        })
+        .collect();
+
+    if !commit {
+        return Ok(MarkdownImportResult { items, committed: false, created: Vec::new() });
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start import transaction", e))?;
+    journal::begin("Import checklist", &mut tx).await?;
+
+    let mut created = Vec::new();
+    let mut completed_parents = Vec::new();
+    // (depth, todo id) of the items enclosing the current one
+    let mut ancestors: Vec<(usize, Option<i64>)> = Vec::new();
+    for item in &items {
+        while ancestors.last().is_some_and(|&(depth, _)| depth >= item.depth) {
+            ancestors.pop();
+        }
+        let parent_id = ancestors.last().and_then(|&(_, id)| id);
+
+        let todo_id = if item.duplicate {
+            ids_by_text.get(&normalize(&item.text)).copied()
+        } else {
+            let todo = todos::create_new_todo(item.text.clone(), item.time, project_id, parent_id, &mut *tx).await?;
+            if item.completed {
+                sqlx::query("UPDATE todos SET completed = 1 WHERE id = ?")
+                    .bind(todo.id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| MissionControlError::from_sqlx("Failed to complete imported todo", e))?;
+                completed_parents.push(parent_id);
+            }
+            ids_by_text.insert(normalize(&item.text), todo.id);
+            let id = todo.id;
+            created.push(todo);
+            Some(id)
+        };
+        ancestors.push((item.depth, todo_id));
+    }
+
+    // Only once all subtasks exist can a parent be judged finished
+    for parent_id in completed_parents {
+        todos::complete_finished_parents(parent_id, &mut tx).await?;
+    }
+
+    journal::finish(&mut tx).await?;
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit import", e))?;
+
+    // Reload so depth, rolled-up time and auto-completed parents are reported as stored
+    let created_ids: HashSet<i64> = created.iter().map(|todo| todo.id).collect();
+    let created = todos::get_todos_by_project(project_id, pool)
+        .await?
+        .into_iter()
+        .filter(|todo| created_ids.contains(&todo.id))
+        .collect();
+
+    Ok(MarkdownImportResult { items, committed: true, created })
+}
+
+// Import a todo.txt file in one transaction. Each task goes to the project named by
+// its first `+project` tag, creating the project if none matches; tasks without a
+// tag go to `default_project_id`. Priorities are stored as P0 for (A), P1 for (B),
+// P2 for (C) and P3 for (D) to (Z); tasks without one, and completed tasks, whose
+// priority todo.txt drops, get the default P2.
+pub async fn import_todotxt(
+    path: &Path,
+    default_project_id: Option<i64>,
+    pool: &SqlitePool,
+) -> Result<TodoTxtImportSummary, MissionControlError> {
+    let text = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| MissionControlError::from_io(&format!("Failed to read {}", path.display()), e))?;
+
+    let tasks = todotxt::parse(&text);
+    if tasks.is_empty() {
+        return Err(MissionControlError::Validation("The todo.txt file has no tasks".to_string()));
+    }
+    if let Some(project_id) = default_project_id {
+        projects::get_project_by_id(project_id, pool).await?;
+    }
+    for (line, task) in &tasks {
+        if task.text.is_empty() {
+            return Err(MissionControlError::Validation(format!("Line {} has no description", line)));
+        }
+        if task.projects.is_empty() && default_project_id.is_none() {
+            return Err(MissionControlError::Validation(format!(
+                "Line {} has no +project and no default project was chosen",
+                line
+            )));
+        }
+    }
+
+    // Tags are matched case-insensitively against the tag form of existing names
+    let mut project_ids: HashMap<String, i64> = projects::get_all_projects(true, pool)
+        .await?
+        .into_iter()
+        .map(|project| (todotxt::project_tag(&project.name).to_lowercase(), project.id))
+        .collect();
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start import transaction", e))?;
+    journal::begin("Import todo.txt", &mut tx).await?;
+
+    let mut created_projects = Vec::new();
+    for (_, task) in &tasks {
+        let project_id = match task.projects.first() {
+            Some(tag) => match project_ids.get(&tag.to_lowercase()) {
+                Some(&id) => id,
+                None => {
+                    let project = projects::create_new_project(
+                        CreateProject { name: tag.clone(), description: None },
+                        &mut *tx,
+                    )
+                    .await?;
+                    project_ids.insert(tag.to_lowercase(), project.id);
+                    let id = project.id;
+                    created_projects.push(project);
+                    id
+                }
+            },
+            None => default_project_id.unwrap_or_default(),
+        };
+
+        let todo = todos::create_new_todo(
+            task.text.clone(),
+            task.estimate().unwrap_or(todos::DEFAULT_TODO_MINUTES),
+            project_id,
+            None,
+            &mut *tx,
+        )
+        .await?;
+
+        sqlx::query(
+            "UPDATE todos SET completed = ?, priority = ?, due_at = ?, created_at = COALESCE(?, created_at) WHERE id = ?"
+        )
+        .bind(if task.completed { 1i64 } else { 0i64 })
+        .bind(i64::from(todotxt::priority_from_letter(task.priority)))
+        .bind(task.due_at())
+        .bind(task.creation_date.map(|date| format!("{} 00:00:00", date)))
+        .bind(todo.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to update imported todo", e))?;
+    }
+
+    journal::finish(&mut tx).await?;
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit import", e))?;
+
+    Ok(TodoTxtImportSummary { todos: tasks.len(), created_projects })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{migrated_pool, project};
+
+    // Write `text` to a todo.txt file that is removed again when the guard drops
+    struct TodoTxtFile(std::path::PathBuf);
+
+    impl TodoTxtFile {
+        fn new(name: &str, text: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("missioncontrol-{}-{}.txt", name, std::process::id()));
+            std::fs::write(&path, text).unwrap();
+            TodoTxtFile(path)
+        }
+    }
+
+    impl Drop for TodoTxtFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn todotxt_priorities_are_stored() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let file = TodoTxtFile::new(
+            "priorities",
+            "(A) Call the printer\n\
+             (B) Book the venue +Launch\n\
+             (C) Order badges\n\
+             (D) Sort the cables\n\
+             (Z) Water the plants\n\
+             Tidy the desk\n\
+             x 2024-03-02 Send the invites\n",
+        );
+
+        let summary = import_todotxt(&file.0, Some(inbox.id), &pool).await.unwrap();
+        assert_eq!(summary.todos, 7);
+
+        let stored: Vec<(String, u8)> = sqlx::query_as("SELECT text, priority FROM todos ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        let expected = [
+            ("Call the printer", 0),
+            ("Book the venue", 1),
+            ("Order badges", 2),
+            ("Sort the cables", 3),
+            ("Water the plants", 3),
+            ("Tidy the desk", 2),
+            ("Send the invites", 2),
+        ];
+        assert_eq!(stored, expected.map(|(text, priority)| (text.to_string(), priority)));
+    }
+
+    #[tokio::test]
+    async fn imports_undo_as_one_entry() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let checklist = "- [ ] Plan\n  - [x] Draft\n  - [ ] Review\n- [ ] Ship";
+        import_markdown(inbox.id, checklist, true, &pool).await.unwrap();
+        let file = TodoTxtFile::new("undo", "Call the printer +Launch\nTidy the desk\n");
+        import_todotxt(&file.0, Some(inbox.id), &pool).await.unwrap();
+
+        let history = journal::get_history(&pool).await.unwrap();
+        let labels: Vec<&str> = history.undo.iter().map(|entry| entry.label.as_str()).collect();
+        assert_eq!(labels, ["Import todo.txt", "Import checklist", "Create project"]);
+
+        journal::undo(&pool).await.unwrap().unwrap();
+        assert_eq!(projects::get_all_projects(true, &pool).await.unwrap().len(), 1);
+        assert_eq!(todos::get_todos_by_project(inbox.id, &pool).await.unwrap().len(), 4);
+        journal::undo(&pool).await.unwrap().unwrap();
+        assert!(todos::get_todos_by_project(inbox.id, &pool).await.unwrap().is_empty());
+    }
+}
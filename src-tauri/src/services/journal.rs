@@ -0,0 +1,382 @@
+use sqlx::{sqlite::{SqlitePool, SqliteRow}, Connection, Row, SqliteConnection};
+use crate::error::MissionControlError;
+use crate::types::{HistoryEntry, UndoHistory};
+
+// Undo entries kept; the oldest are dropped as new ones come in
+pub const HISTORY_LIMIT: i64 = 100;
+
+const UNDO: &str = "undo";
+const REDO: &str = "redo";
+
+fn entry_from_row(row: &SqliteRow) -> HistoryEntry {
+    HistoryEntry {
+        id: row.get("id"),
+        label: row.get("label"),
+        created_at: row.get("created_at"),
+    }
+}
+
+async fn start_recording(
+    stack: &str,
+    label: &str,
+    merge_key: Option<&str>,
+    conn: &mut SqliteConnection,
+) -> Result<i64, MissionControlError> {
+    let entry_id: i64 = sqlx::query_scalar("INSERT INTO undo_entries (stack, label, merge_key) VALUES (?, ?, ?) RETURNING id")
+        .bind(stack)
+        .bind(label)
+        .bind(merge_key)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start undo entry", e))?;
+
+    sqlx::query("UPDATE undo_recording SET entry_id = ? WHERE id = 1")
+        .bind(entry_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start recording changes", e))?;
+
+    Ok(entry_id)
+}
+
+// Stop recording and return whether the entry recorded anything; an empty one is dropped
+async fn stop_recording(conn: &mut SqliteConnection) -> Result<bool, MissionControlError> {
+    let entry_id: Option<i64> = sqlx::query_scalar("SELECT entry_id FROM undo_recording WHERE id = 1")
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read undo state", e))?;
+
+    sqlx::query("UPDATE undo_recording SET entry_id = NULL WHERE id = 1")
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to stop recording changes", e))?;
+
+    let entry_id = match entry_id {
+        Some(entry_id) => entry_id,
+        None => return Ok(false),
+    };
+    let changes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM undo_changes WHERE entry_id = ?")
+        .bind(entry_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read undo entry", e))?;
+    if changes == 0 {
+        delete_entries("WHERE id = ?", Some(entry_id), conn).await?;
+    }
+    Ok(changes > 0)
+}
+
+// Delete the entries matched by `filter` (a WHERE clause on undo_entries) with their changes
+async fn delete_entries(filter: &str, param: Option<i64>, conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    for query in [
+        format!("DELETE FROM undo_changes WHERE entry_id IN (SELECT id FROM undo_entries {})", filter),
+        format!("DELETE FROM undo_entries {}", filter),
+    ] {
+        let mut query = sqlx::query(&query);
+        if let Some(param) = param {
+            query = query.bind(param);
+        }
+        query
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to trim undo history", e))?;
+    }
+    Ok(())
+}
+
+async fn delete_stack(stack: &str, conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    let filter = format!("WHERE stack = '{}'", stack);
+    delete_entries(&filter, None, conn).await
+}
+
+// Keep only the newest HISTORY_LIMIT undo entries
+async fn prune(conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    let filter = "WHERE id IN (SELECT id FROM undo_entries WHERE stack = 'undo' ORDER BY id DESC LIMIT -1 OFFSET ?)";
+    delete_entries(filter, Some(HISTORY_LIMIT), conn).await
+}
+
+// Start journaling the changes a service makes on `conn`, which must be inside the
+// transaction that makes them; call `finish` before committing. Rolling back
+// discards the entry along with the changes.
+pub async fn begin(label: &str, conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    start_recording(UNDO, label, None, conn).await?;
+    Ok(())
+}
+
+// Like `begin`, but tags the entry with `key` so that later changes to the same
+// thing can ask `continues` whether to fold into it
+pub async fn begin_merged(label: &str, key: &str, conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    start_recording(UNDO, label, Some(key), conn).await?;
+    Ok(())
+}
+
+// Whether the newest undo entry was begun with `key` and nothing has been undone
+// since. Undoing that entry already restores the data from before the whole run, so
+// a caller that gets true makes its change without journaling it.
+pub async fn continues(key: &str, conn: &mut SqliteConnection) -> Result<bool, MissionControlError> {
+    sqlx::query_scalar(
+        "SELECT COALESCE((SELECT merge_key FROM undo_entries WHERE stack = 'undo' ORDER BY id DESC LIMIT 1) = ?, 0)
+             AND NOT EXISTS (SELECT 1 FROM undo_entries WHERE stack = 'redo')"
+    )
+    .bind(key)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to read undo history", e))
+}
+
+pub async fn finish(conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    if stop_recording(conn).await? {
+        // Anything undone before this change can no longer be redone on top of it
+        delete_stack(REDO, conn).await?;
+        prune(conn).await?;
+    }
+    Ok(())
+}
+
+// Forget all history, for when the data is replaced wholesale
+pub async fn clear(conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    delete_entries("", None, conn).await
+}
+
+enum Replayed {
+    Nothing,
+    Done(HistoryEntry),
+    // The entry no longer applies; the transaction has to be rolled back
+    Stale(HistoryEntry),
+}
+
+// Revert the newest entry on `from`, recording the reverting changes as an entry on
+// `to`. If the data has changed since in a way the entry can't be replayed over, the
+// entry and everything older on `from` is dropped and a Conflict is returned.
+//
+// The transaction starts with BEGIN IMMEDIATE: a deferred one would read the entry
+// under a shared lock, so two replays at once could both pick the same entry and
+// the second would fail to upgrade to a write lock instead of waiting for the first.
+async fn replay(from: &str, to: &str, pool: &SqlitePool) -> Result<Option<HistoryEntry>, MissionControlError> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to acquire database connection", e))?;
+
+    sqlx::query("BEGIN IMMEDIATE")
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    let replayed = replay_newest(from, to, &mut conn).await;
+    let end = if matches!(replayed, Ok(Replayed::Done(_))) { "COMMIT" } else { "ROLLBACK" };
+    if let Err(e) = sqlx::query(end).execute(&mut *conn).await {
+        // Never hand the connection back to the pool inside a transaction
+        let _ = sqlx::query("ROLLBACK").execute(&mut *conn).await;
+        return Err(MissionControlError::from_sqlx("Failed to commit undo", e));
+    }
+
+    match replayed? {
+        Replayed::Nothing => Ok(None),
+        Replayed::Done(entry) => Ok(Some(entry)),
+        Replayed::Stale(entry) => {
+            let mut tx = conn
+                .begin()
+                .await
+                .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+            let filter = format!("WHERE stack = '{}' AND id <= ?", from);
+            delete_entries(&filter, Some(entry.id), &mut tx).await?;
+            tx.commit()
+                .await
+                .map_err(|e| MissionControlError::from_sqlx("Failed to trim undo history", e))?;
+            Err(MissionControlError::Conflict(format!(
+                "Can't {} '{}' because the data has changed since; older history was cleared",
+                from, entry.label
+            )))
+        }
+    }
+}
+
+// The body of `replay`, on a connection inside its transaction
+async fn replay_newest(from: &str, to: &str, conn: &mut SqliteConnection) -> Result<Replayed, MissionControlError> {
+    let entry = match sqlx::query("SELECT id, label, created_at FROM undo_entries WHERE stack = ? ORDER BY id DESC LIMIT 1")
+        .bind(from)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read undo history", e))?
+    {
+        Some(row) => entry_from_row(&row),
+        None => return Ok(Replayed::Nothing),
+    };
+
+    let statements: Vec<String> = sqlx::query_scalar("SELECT statement FROM undo_changes WHERE entry_id = ? ORDER BY id DESC")
+        .bind(entry.id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read undo entry", e))?;
+
+    // Statements run newest first, so check references only once all of them have
+    sqlx::query("PRAGMA defer_foreign_keys = ON")
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to prepare undo", e))?;
+
+    start_recording(to, &entry.label, None, conn).await?;
+    for statement in &statements {
+        match sqlx::query(statement).execute(&mut *conn).await {
+            Ok(result) if result.rows_affected() > 0 => {}
+            Ok(_) => return Ok(Replayed::Stale(entry)),
+            Err(e) => match MissionControlError::from_sqlx("Failed to replay change", e) {
+                MissionControlError::Conflict(_) => return Ok(Replayed::Stale(entry)),
+                other => return Err(other),
+            },
+        }
+    }
+
+    stop_recording(conn).await?;
+    delete_entries("WHERE id = ?", Some(entry.id), conn).await?;
+    prune(conn).await?;
+    Ok(Replayed::Done(entry))
+}
+
+// Revert the most recent change; returns what was undone, or None with nothing to undo
+pub async fn undo(pool: &SqlitePool) -> Result<Option<HistoryEntry>, MissionControlError> {
+    replay(UNDO, REDO, pool).await
+}
+
+// Reapply the most recently undone change
+pub async fn redo(pool: &SqlitePool) -> Result<Option<HistoryEntry>, MissionControlError> {
+    replay(REDO, UNDO, pool).await
+}
+
+pub async fn get_history(pool: &SqlitePool) -> Result<UndoHistory, MissionControlError> {
+    let rows = sqlx::query("SELECT id, stack, label, created_at FROM undo_entries ORDER BY id DESC")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read undo history", e))?;
+
+    let (undo, redo): (Vec<&SqliteRow>, Vec<&SqliteRow>) = rows.iter().partition(|row| row.get::<&str, _>("stack") == UNDO);
+    Ok(UndoHistory {
+        undo: undo.into_iter().map(entry_from_row).collect(),
+        redo: redo.into_iter().map(entry_from_row).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{excalidraw, projects};
+    use crate::test_support::{migrated_pool, project, todo, TempDatabase};
+
+    // Every row a project deletion touches, with its trash state
+    async fn dump(pool: &SqlitePool) -> Vec<String> {
+        sqlx::query_scalar(
+            "SELECT 'project ' || id || ' ' || name || ' ' || IFNULL(deleted_at, 'live') FROM projects
+             UNION ALL
+             SELECT 'todo ' || id || ' ' || text || ' ' || IFNULL(parent_id, '-') || ' ' || IFNULL(deleted_at, 'live') FROM todos
+             UNION ALL
+             SELECT 'board ' || id || ' ' || elements || ' ' || IFNULL(deleted_at, 'live') FROM excalidraw_data
+             ORDER BY 1",
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    fn labels(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.label.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn deleting_a_project_undoes_and_redoes_as_one_entry() {
+        let pool = migrated_pool().await;
+        project("Inbox", &pool).await;
+        let launch = project("Launch", &pool).await;
+        let venue = todo("Book the venue", launch.id, None, &pool).await;
+        todo("Compare offers", launch.id, Some(venue.id), &pool).await;
+        excalidraw::save_excalidraw_data("[1]".to_string(), "{}".to_string(), launch.id, &pool).await.unwrap();
+
+        let before = dump(&pool).await;
+        projects::delete_project_by_id(launch.id, &pool).await.unwrap();
+        let deleted = dump(&pool).await;
+        assert_eq!(deleted.iter().filter(|row| row.ends_with(" live")).count(), 1);
+
+        let history = get_history(&pool).await.unwrap();
+        assert_eq!(labels(&history.undo)[..2], ["Delete project", "Edit whiteboard"]);
+
+        assert_eq!(undo(&pool).await.unwrap().unwrap().label, "Delete project");
+        assert_eq!(dump(&pool).await, before);
+        let history = get_history(&pool).await.unwrap();
+        assert_eq!(labels(&history.undo)[0], "Edit whiteboard");
+        assert_eq!(labels(&history.redo), ["Delete project"]);
+
+        assert_eq!(redo(&pool).await.unwrap().unwrap().label, "Delete project");
+        assert_eq!(dump(&pool).await, deleted);
+        let history = get_history(&pool).await.unwrap();
+        assert_eq!(labels(&history.undo)[0], "Delete project");
+        assert!(history.redo.is_empty());
+    }
+
+    #[tokio::test]
+    async fn history_keeps_the_newest_entries() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let extra = 5;
+        for index in 0..HISTORY_LIMIT + extra {
+            todo(&format!("todo {}", index), inbox.id, None, &pool).await;
+        }
+        assert_eq!(get_history(&pool).await.unwrap().undo.len() as i64, HISTORY_LIMIT);
+
+        for _ in 0..HISTORY_LIMIT {
+            undo(&pool).await.unwrap().unwrap();
+        }
+        assert!(undo(&pool).await.unwrap().is_none());
+
+        // The project and the oldest todos fell out of the history
+        let left: Vec<String> = sqlx::query_scalar("SELECT text FROM todos ORDER BY id").fetch_all(&pool).await.unwrap();
+        assert_eq!(left, (0..extra).map(|index| format!("todo {}", index)).collect::<Vec<_>>());
+        assert_eq!(get_history(&pool).await.unwrap().redo.len() as i64, HISTORY_LIMIT);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_undos_take_one_entry_each() {
+        let database = TempDatabase::new(8).await;
+        let pool = &database.pool;
+        let inbox = project("Inbox", pool).await;
+        for index in 0..8 {
+            todo(&format!("todo {}", index), inbox.id, None, pool).await;
+        }
+
+        let undos: Vec<_> = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                tokio::spawn(async move { undo(&pool).await })
+            })
+            .collect();
+        let mut undone = Vec::new();
+        for handle in undos {
+            undone.push(handle.await.unwrap().unwrap().unwrap().id);
+        }
+        undone.sort();
+        undone.dedup();
+        assert_eq!(undone.len(), 8);
+
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM todos").fetch_one(pool).await.unwrap();
+        assert_eq!(left, 0);
+        let history = get_history(pool).await.unwrap();
+        assert_eq!(labels(&history.undo), ["Create project"]);
+        assert_eq!(history.redo.len(), 8);
+    }
+
+    #[tokio::test]
+    async fn stale_entries_are_dropped_with_everything_older() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let first = todo("First", inbox.id, None, &pool).await;
+        todo("Second", inbox.id, None, &pool).await;
+
+        // A change the journal never saw makes the newest entry unreplayable
+        sqlx::query("DELETE FROM todos WHERE text = 'Second'").execute(&pool).await.unwrap();
+        assert_eq!(undo(&pool).await.unwrap_err().kind(), "conflict");
+
+        assert!(get_history(&pool).await.unwrap().undo.is_empty());
+        assert!(undo(&pool).await.unwrap().is_none());
+        let left: Vec<i64> = sqlx::query_scalar("SELECT id FROM todos").fetch_all(&pool).await.unwrap();
+        assert_eq!(left, [first.id]);
+    }
+}
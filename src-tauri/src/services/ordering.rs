@@ -0,0 +1,446 @@
+use std::collections::HashSet;
+use sqlx::{sqlite::SqlitePool, Row, SqliteConnection};
+use crate::error::MissionControlError;
+use crate::services::{journal, projects, todos};
+use crate::types::Todo;
+
+// Spacing between neighbouring positions when todos are appended or renumbered
+pub const POSITION_GAP: f64 = 1024.0;
+
+// Evenly spaced positions for `count` todos strictly between `lower` and `upper`
+// (either may be open), or None once floating-point precision has run out and the
+// list has to be renumbered
+pub fn positions_between(lower: Option<f64>, upper: Option<f64>, count: usize) -> Option<Vec<f64>> {
+    let slots = (count + 1) as f64;
+    let (lower, upper) = match (lower, upper) {
+        (Some(lower), Some(upper)) => (lower, upper),
+        (Some(lower), None) => (lower, lower + slots * POSITION_GAP),
+        (None, Some(upper)) => (upper - slots * POSITION_GAP, upper),
+        (None, None) => (0.0, slots * POSITION_GAP),
+    };
+
+    let step = (upper - lower) / slots;
+    let positions: Vec<f64> = (1..=count).map(|k| lower + step * k as f64).collect();
+
+    let mut previous = lower;
+    for &position in positions.iter().chain(std::iter::once(&upper)) {
+        if position.is_nan() || position <= previous {
+            return None;
+        }
+        previous = position;
+    }
+    Some(positions)
+}
+
+// Rewrite the positions of the listed todos in `project_id` to 1, 2, 3... times the
+// gap, keeping their order. Positions are cleared first so the unique index on
+// (project_id, position) never sees two rows with the same value.
+async fn renumber(project_id: i64, conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    let ids: Vec<i64> = sqlx::query_scalar(
+        "SELECT id FROM todos WHERE project_id = ? AND position IS NOT NULL ORDER BY position ASC, id ASC"
+    )
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to read todo order", e))?;
+
+    sqlx::query("UPDATE todos SET position = NULL WHERE project_id = ? AND position IS NOT NULL")
+        .bind(project_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to renumber todos", e))?;
+
+    for (index, id) in ids.iter().enumerate() {
+        sqlx::query("UPDATE todos SET position = ? WHERE id = ?")
+            .bind((index + 1) as f64 * POSITION_GAP)
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to renumber todos", e))?;
+    }
+    Ok(())
+}
+
+// Positions of the todos around the slot the moved ones go into. `before_id` is
+// the todo they should end up in front of and `after_id` the one they should
+// follow; with both, the two must still be neighbours.
+async fn slot_bounds(
+    project_id: i64,
+    before_id: Option<i64>,
+    after_id: Option<i64>,
+    conn: &mut SqliteConnection,
+) -> Result<(Option<f64>, Option<f64>), MissionControlError> {
+    let rows: Vec<(i64, f64)> = sqlx::query(
+        "SELECT id, position FROM todos WHERE project_id = ? AND position IS NOT NULL ORDER BY position ASC, id ASC"
+    )
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to read todo order", e))?
+    .iter()
+    .map(|row| (row.get("id"), row.get("position")))
+    .collect();
+
+    let index_of = |id: i64| {
+        rows.iter().position(|(row_id, _)| *row_id == id).ok_or_else(|| {
+            MissionControlError::Validation(format!("Todo {} is not in project {}", id, project_id))
+        })
+    };
+
+    match (after_id, before_id) {
+        (Some(after_id), Some(before_id)) => {
+            let (after, before) = (index_of(after_id)?, index_of(before_id)?);
+            if before != after + 1 {
+                return Err(MissionControlError::Conflict(format!(
+                    "Todos {} and {} are no longer next to each other; reload and try again",
+                    after_id, before_id
+                )));
+            }
+            Ok((Some(rows[after].1), Some(rows[before].1)))
+        }
+        (Some(after_id), None) => {
+            let after = index_of(after_id)?;
+            Ok((Some(rows[after].1), rows.get(after + 1).map(|row| row.1)))
+        }
+        (None, Some(before_id)) => {
+            let before = index_of(before_id)?;
+            Ok((before.checked_sub(1).map(|index| rows[index].1), Some(rows[before].1)))
+        }
+        (None, None) => Ok((rows.last().map(|row| row.1), None)),
+    }
+}
+
+pub async fn ensure_project_exists(project_id: i64, conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    let exists: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM projects WHERE id = ? AND deleted_at IS NULL")
+        .bind(project_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to look up project", e))?;
+    if exists == 0 {
+        return Err(MissionControlError::NotFound(format!("Project {} not found", project_id)));
+    }
+    Ok(())
+}
+
+// Move `ids` as a block, in the given order, between `after_id` and `before_id`
+// (to the end of the list when neither is given), optionally into another project.
+// Todos moved to another project take their subtasks with them, and one whose
+// parent stays behind becomes top-level. Returns the project the todos are now in.
+// The first statement is a write, so concurrent moves are serialized by SQLite
+// instead of reading the same neighbours.
+pub async fn move_in(
+    ids: &[i64],
+    target_project_id: Option<i64>,
+    before_id: Option<i64>,
+    after_id: Option<i64>,
+    conn: &mut SqliteConnection,
+) -> Result<i64, MissionControlError> {
+    if ids.is_empty() {
+        return Err(MissionControlError::Validation("No todos to move".to_string()));
+    }
+    let unique: HashSet<i64> = ids.iter().copied().collect();
+    if unique.len() != ids.len() {
+        return Err(MissionControlError::Validation("A todo is listed more than once".to_string()));
+    }
+    if let Some(anchor) = before_id.into_iter().chain(after_id).find(|anchor| unique.contains(anchor)) {
+        return Err(MissionControlError::Validation(format!(
+            "Todo {} cannot be moved next to itself",
+            anchor
+        )));
+    }
+
+    // Take the moved todos out of the list; this also takes SQLite's write lock
+    let mut clear = sqlx::QueryBuilder::new("UPDATE todos SET position = NULL WHERE deleted_at IS NULL AND id IN (");
+    let mut separated = clear.separated(", ");
+    for id in ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(") RETURNING id, project_id, parent_id");
+    let moved = clear
+        .build()
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to move todos", e))?;
+
+    if moved.len() != ids.len() {
+        let found: HashSet<i64> = moved.iter().map(|row| row.get("id")).collect();
+        let missing = ids.iter().find(|id| !found.contains(id)).copied().unwrap_or_default();
+        return Err(MissionControlError::NotFound(format!("Todo {} not found", missing)));
+    }
+
+    let source_projects: HashSet<i64> = moved.iter().map(|row| row.get("project_id")).collect();
+    let project_id = match target_project_id {
+        Some(target_project_id) => {
+            ensure_project_exists(target_project_id, conn).await?;
+            target_project_id
+        }
+        None if source_projects.len() == 1 => *source_projects.iter().next().unwrap_or(&0),
+        None => {
+            return Err(MissionControlError::Validation(
+                "Todos from different projects need a target project".to_string(),
+            ));
+        }
+    };
+
+    for affected in source_projects.iter().chain(std::iter::once(&project_id)) {
+        projects::ensure_not_archived(*affected, &mut *conn).await?;
+    }
+
+    let mut block = ids.to_vec();
+    if source_projects.iter().any(|source| *source != project_id) {
+        let descendants = todos::descendant_ids(ids, conn).await?;
+        block.extend(descendants);
+
+        let mut change = sqlx::QueryBuilder::new("UPDATE todos SET position = NULL, project_id = ");
+        change.push_bind(project_id);
+        change.push(" WHERE id IN (");
+        let mut separated = change.separated(", ");
+        for id in &block {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+        change
+            .build()
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to move todos to project", e))?;
+
+        for row in &moved {
+            let parent_id: Option<i64> = row.get("parent_id");
+            let source: i64 = row.get("project_id");
+            if source != project_id && parent_id.is_some_and(|parent_id| !unique.contains(&parent_id)) {
+                sqlx::query("UPDATE todos SET parent_id = NULL WHERE id = ?")
+                    .bind(row.get::<i64, _>("id"))
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(|e| MissionControlError::from_sqlx("Failed to detach moved subtask", e))?;
+            }
+        }
+    }
+
+    let (lower, upper) = slot_bounds(project_id, before_id, after_id, conn).await?;
+    let positions = match positions_between(lower, upper, block.len()) {
+        Some(positions) => positions,
+        None => {
+            renumber(project_id, conn).await?;
+            let (lower, upper) = slot_bounds(project_id, before_id, after_id, conn).await?;
+            positions_between(lower, upper, block.len()).ok_or_else(|| {
+                MissionControlError::Validation("Could not find room to move the todos".to_string())
+            })?
+        }
+    };
+
+    for (id, position) in block.iter().zip(positions) {
+        sqlx::query("UPDATE todos SET position = ? WHERE id = ?")
+            .bind(position)
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to move todo", e))?;
+    }
+
+    Ok(project_id)
+}
+
+// Returns the todos of the project the moved ones ended up in
+pub async fn move_todos(
+    ids: &[i64],
+    target_project_id: Option<i64>,
+    before_id: Option<i64>,
+    after_id: Option<i64>,
+    pool: &SqlitePool,
+) -> Result<Vec<Todo>, MissionControlError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    journal::begin(if ids.len() == 1 { "Move todo" } else { "Move todos" }, &mut tx).await?;
+    let project_id = move_in(ids, target_project_id, before_id, after_id, &mut tx).await?;
+    journal::finish(&mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit todo move", e))?;
+
+    todos::get_todos_by_project(project_id, pool).await
+}
+
+pub async fn reorder_todo(
+    id: i64,
+    before_id: Option<i64>,
+    after_id: Option<i64>,
+    pool: &SqlitePool,
+) -> Result<Vec<Todo>, MissionControlError> {
+    move_todos(&[id], None, before_id, after_id, pool).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{exec, migrated_pool, project, todo, TempDatabase};
+
+    // (id, position) of the project's listed todos, in list order
+    async fn order(project_id: i64, pool: &SqlitePool) -> Vec<(i64, f64)> {
+        sqlx::query_as("SELECT id, position FROM todos WHERE project_id = ? ORDER BY position ASC, id ASC")
+            .bind(project_id)
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    fn ids(order: &[(i64, f64)]) -> Vec<i64> {
+        order.iter().map(|(id, _)| *id).collect()
+    }
+
+    fn assert_strictly_increasing(order: &[(i64, f64)]) {
+        assert!(order.windows(2).all(|pair| pair[0].1 < pair[1].1), "positions out of order: {:?}", order);
+    }
+
+    #[test]
+    fn positions_between_spreads_evenly() {
+        assert_eq!(positions_between(Some(0.0), Some(4.0), 3), Some(vec![1.0, 2.0, 3.0]));
+        assert_eq!(positions_between(None, None, 2), Some(vec![POSITION_GAP, 2.0 * POSITION_GAP]));
+        assert_eq!(positions_between(Some(10.0), None, 1), Some(vec![10.0 + POSITION_GAP]));
+        assert_eq!(positions_between(None, Some(10.0), 1), Some(vec![10.0 - POSITION_GAP]));
+        assert_eq!(positions_between(Some(1.0), Some(2.0), 0), Some(vec![]));
+    }
+
+    #[test]
+    fn positions_between_gives_up_when_the_gap_is_exhausted() {
+        let next = f64::from_bits(1.0f64.to_bits() + 1);
+        assert_eq!(positions_between(Some(1.0), Some(next), 1), None);
+        assert_eq!(positions_between(Some(1.0), Some(1.0), 1), None);
+        assert_eq!(positions_between(Some(2.0), Some(1.0), 1), None);
+
+        // Room for two between neighbours three apart, but not for three
+        let after = f64::from_bits(1.0f64.to_bits() + 3);
+        assert_eq!(positions_between(Some(1.0), Some(after), 2).map(|positions| positions.len()), Some(2));
+        assert_eq!(positions_between(Some(1.0), Some(after), 3), None);
+
+        assert_eq!(positions_between(Some(f64::NAN), Some(1.0), 1), None);
+        assert_eq!(positions_between(Some(f64::MAX), None, 1), None);
+
+        // Halving the same gap over and over eventually runs out
+        let (lower, mut upper) = (0.0, POSITION_GAP);
+        let mut moves = 0;
+        while let Some(positions) = positions_between(Some(lower), Some(upper), 1) {
+            upper = positions[0];
+            moves += 1;
+        }
+        assert!(moves > 1000, "ran out after {} moves", moves);
+    }
+
+    #[tokio::test]
+    async fn renumber_keeps_the_order() {
+        let pool = migrated_pool().await;
+        let launch = project("Launch", &pool).await;
+        for text in ["a", "b", "c", "d"] {
+            todo(text, launch.id, None, &pool).await;
+        }
+        exec(
+            "UPDATE todos SET position = CASE text WHEN 'a' THEN 0.5 WHEN 'b' THEN 0.50000001 WHEN 'c' THEN -3 ELSE 1e300 END",
+            &pool,
+        )
+        .await;
+        let before = ids(&order(launch.id, &pool).await);
+
+        let mut conn = pool.acquire().await.unwrap();
+        renumber(launch.id, &mut conn).await.unwrap();
+        drop(conn);
+
+        let after = order(launch.id, &pool).await;
+        assert_eq!(ids(&after), before);
+        let positions: Vec<f64> = after.iter().map(|(_, position)| *position).collect();
+        assert_eq!(positions, vec![POSITION_GAP, 2.0 * POSITION_GAP, 3.0 * POSITION_GAP, 4.0 * POSITION_GAP]);
+    }
+
+    #[tokio::test]
+    async fn slot_bounds_reads_the_neighbours() {
+        let pool = migrated_pool().await;
+        let launch = project("Launch", &pool).await;
+        let other = project("Other", &pool).await;
+        let a = todo("a", launch.id, None, &pool).await;
+        let b = todo("b", launch.id, None, &pool).await;
+        let c = todo("c", launch.id, None, &pool).await;
+        let stranger = todo("stranger", other.id, None, &pool).await;
+
+        let mut conn = pool.acquire().await.unwrap();
+        let id = launch.id;
+        assert_eq!(slot_bounds(id, None, None, &mut conn).await.unwrap(), (Some(c.position), None));
+        assert_eq!(slot_bounds(id, Some(a.id), None, &mut conn).await.unwrap(), (None, Some(a.position)));
+        assert_eq!(slot_bounds(id, Some(c.id), None, &mut conn).await.unwrap(), (Some(b.position), Some(c.position)));
+        assert_eq!(slot_bounds(id, None, Some(c.id), &mut conn).await.unwrap(), (Some(c.position), None));
+        assert_eq!(slot_bounds(id, Some(b.id), Some(a.id), &mut conn).await.unwrap(), (Some(a.position), Some(b.position)));
+
+        let not_neighbours = slot_bounds(id, Some(c.id), Some(a.id), &mut conn).await.unwrap_err();
+        assert_eq!(not_neighbours.kind(), "conflict");
+        let elsewhere = slot_bounds(id, Some(stranger.id), None, &mut conn).await.unwrap_err();
+        assert_eq!(elsewhere.kind(), "validation");
+    }
+
+    #[tokio::test]
+    async fn moving_into_an_exhausted_gap_renumbers() {
+        let pool = migrated_pool().await;
+        let launch = project("Launch", &pool).await;
+        let a = todo("a", launch.id, None, &pool).await;
+        let b = todo("b", launch.id, None, &pool).await;
+        let c = todo("c", launch.id, None, &pool).await;
+        // Put a and b on neighbouring doubles so nothing fits between them
+        exec(&format!("UPDATE todos SET position = 1.0 WHERE id = {}", a.id), &pool).await;
+        exec(&format!("UPDATE todos SET position = {:?} WHERE id = {}", f64::from_bits(1.0f64.to_bits() + 1), b.id), &pool).await;
+
+        reorder_todo(c.id, Some(b.id), Some(a.id), &pool).await.unwrap();
+
+        let after = order(launch.id, &pool).await;
+        assert_eq!(ids(&after), vec![a.id, c.id, b.id]);
+        assert_strictly_increasing(&after);
+        assert_eq!(after[0].1, POSITION_GAP);
+        assert_eq!(after[2].1, 2.0 * POSITION_GAP);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_reorders_keep_positions_unique() {
+        let database = TempDatabase::new(8).await;
+        let pool = &database.pool;
+        let launch = project("Launch", pool).await;
+        let mut todo_ids = Vec::new();
+        for index in 0..12 {
+            todo_ids.push(todo(&format!("todo {}", index), launch.id, None, pool).await.id);
+        }
+
+        // Everyone moves a todo next to another one at the same time; moves that
+        // name a single neighbour can never conflict with each other
+        let moves: Vec<_> = (0..48)
+            .map(|round| {
+                let pool = pool.clone();
+                let id = todo_ids[(round * 5) % todo_ids.len()];
+                let anchor = todo_ids[(round * 7 + 3) % todo_ids.len()];
+                tokio::spawn(async move {
+                    if id == anchor {
+                        return Ok(());
+                    }
+                    let moved = if round % 2 == 0 {
+                        reorder_todo(id, Some(anchor), None, &pool).await
+                    } else {
+                        reorder_todo(id, None, Some(anchor), &pool).await
+                    };
+                    moved.map(|_| ())
+                })
+            })
+            .collect();
+        for handle in moves {
+            handle.await.unwrap().unwrap();
+        }
+
+        let after = order(launch.id, pool).await;
+        assert_eq!(after.len(), todo_ids.len());
+        assert_strictly_increasing(&after);
+        let mut listed = ids(&after);
+        listed.sort();
+        assert_eq!(listed, todo_ids);
+
+        let listed = todos::get_todos_by_project(launch.id, pool).await.unwrap();
+        assert_eq!(listed.iter().map(|todo| todo.id).collect::<Vec<_>>(), ids(&after));
+    }
+}
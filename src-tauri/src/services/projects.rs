@@ -1,95 +1,480 @@
-use sqlx::{sqlite::SqlitePool, Row};
-use crate::types::{Project, CreateProject};
+use sqlx::{sqlite::{SqlitePool, SqliteRow}, Executor, Row, Sqlite, SqliteConnection};
+use crate::error::MissionControlError;
+use crate::services::{journal, trash};
+use crate::types::{Project, CreateProject, UpdateProject};
 
-pub async fn get_all_projects(pool: &SqlitePool) -> Result<Vec<Project>, String> {
-    let rows = sqlx::query("SELECT id, name, description, created_at FROM projects ORDER BY created_at ASC")
-        .fetch_all(pool)
-        .await
-        .map_err(|e| {
-            let error_msg = format!("Failed to fetch projects: {}", e);
-            println!("{}", error_msg);
-            error_msg
-        })?;
-
-    let projects: Vec<Project> = rows
-        .into_iter()
-        .map(|row| Project {
-            id: row.get("id"),
-            name: row.get("name"),
-            description: row.get("description"),
-            created_at: row.get("created_at"),
-        })
-        .collect();
-    Ok(projects)
-}
-
-pub async fn create_new_project(project: CreateProject, pool: &SqlitePool) -> Result<Project, String> {
-    let result = sqlx::query(
-        "INSERT INTO projects (name, description) VALUES (?, ?) RETURNING id, name, description, created_at"
-    )
-    .bind(&project.name)
-    .bind(&project.description)
-    .fetch_one(pool)
-    .await
-    .map_err(|e| {
-        let error_msg = format!("Failed to create project: {}", e);
-        println!("{}", error_msg);
-        error_msg
-    })?;
-
-    let new_project = Project {
-        id: result.get("id"),
-        name: result.get("name"),
-        description: result.get("description"),
-        created_at: result.get("created_at"),
-    };
+const PROJECT_COLUMNS: &str = "id, name, description, created_at, color, icon, archived_at";
+
+// Longest icon accepted, in characters; enough for an icon name or a composed emoji
+const MAX_ICON_CHARS: usize = 32;
+
+fn project_from_row(row: &SqliteRow) -> Project {
+    Project {
+        id: row.get("id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        created_at: row.get("created_at"),
+        color: row.get("color"),
+        icon: row.get("icon"),
+        archived_at: row.get("archived_at"),
+    }
+}
+
+// Project names are unique, so a unique violation means the name is taken
+fn map_name_conflict(context: &str, name: &str, error: sqlx::Error) -> MissionControlError {
+    match MissionControlError::from_sqlx(context, error) {
+        MissionControlError::Conflict(_) => {
+            MissionControlError::Conflict(format!("A project named '{}' already exists", name))
+        }
+        other => other,
+    }
+}
+
+// Accepts `#rgb` or `#rrggbb` and returns it as lowercase `#rrggbb`
+fn normalize_color(color: &str) -> Option<String> {
+    let hex = color.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    match hex.len() {
+        3 => Some(format!("#{}", hex.chars().flat_map(|c| [c, c]).collect::<String>()).to_ascii_lowercase()),
+        6 => Some(format!("#{}", hex).to_ascii_lowercase()),
+        _ => None,
+    }
+}
 
-    Ok(new_project)
+fn archived_error(name: &str) -> MissionControlError {
+    MissionControlError::Conflict(format!("Project '{}' is archived; unarchive it to make changes", name))
 }
 
-pub async fn delete_project_by_id(id: i64, pool: &SqlitePool) -> Result<(), String> {
-    // Don't allow deleting the last project
-    let project_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM projects")
-        .fetch_one(pool)
+// Archived projects are read-only: refuse changes to their todos, whiteboard and
+// focus sessions. A project that doesn't exist is left for the caller to report.
+pub async fn ensure_not_archived<'e, E>(project_id: i64, executor: E) -> Result<(), MissionControlError>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let archived: Option<String> = sqlx::query_scalar("SELECT name FROM projects WHERE id = ? AND archived_at IS NOT NULL")
+        .bind(project_id)
+        .fetch_optional(executor)
         .await
-        .unwrap_or(0);
+        .map_err(|e| MissionControlError::from_sqlx("Failed to look up project", e))?;
+    match archived {
+        Some(name) => Err(archived_error(&name)),
+        None => Ok(()),
+    }
+}
 
-    if project_count <= 1 {
-        return Err("Cannot delete the last project".to_string());
+// The same for the projects that `todo_ids` belong to
+pub async fn ensure_todos_not_archived<'e, E>(todo_ids: &[i64], executor: E) -> Result<(), MissionControlError>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let mut query_builder = sqlx::QueryBuilder::new(
+        "SELECT projects.name FROM todos JOIN projects ON projects.id = todos.project_id WHERE projects.archived_at IS NOT NULL AND todos.id IN (",
+    );
+    let mut separated = query_builder.separated(", ");
+    for id in todo_ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(") LIMIT 1");
+    let archived: Option<String> = query_builder
+        .build_query_scalar()
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to look up project", e))?;
+    match archived {
+        Some(name) => Err(archived_error(&name)),
+        None => Ok(()),
     }
+}
 
-    // Delete all todos in this project
-    sqlx::query("DELETE FROM todos WHERE project_id = ?")
+// Some project has to stay open, so the last active one can't be archived or deleted
+async fn ensure_not_last_active(id: i64, action: &str, conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    let others: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM projects WHERE id != ? AND deleted_at IS NULL AND archived_at IS NULL")
         .bind(id)
-        .execute(pool)
+        .fetch_one(&mut *conn)
         .await
-        .map_err(|e| {
-            let error_msg = format!("Failed to delete todos for project: {}", e);
-            println!("{}", error_msg);
-            error_msg
-        })?;
-
-    // Delete excalidraw data for this project
-    sqlx::query("DELETE FROM excalidraw_data WHERE project_id = ?")
+        .map_err(|e| MissionControlError::from_sqlx("Failed to count projects", e))?;
+    if others == 0 {
+        return Err(MissionControlError::Conflict(format!("Cannot {} the last active project", action)));
+    }
+    Ok(())
+}
+
+// Projects in creation order; archived ones only with `include_archived`
+pub async fn get_all_projects(include_archived: bool, pool: &SqlitePool) -> Result<Vec<Project>, MissionControlError> {
+    let filter = if include_archived { "" } else { " AND archived_at IS NULL" };
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM projects WHERE deleted_at IS NULL{} ORDER BY created_at ASC",
+        PROJECT_COLUMNS, filter
+    ))
+    .fetch_all(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to fetch projects", e))?;
+
+    Ok(rows.iter().map(project_from_row).collect())
+}
+
+pub async fn get_project_by_id(id: i64, pool: &SqlitePool) -> Result<Project, MissionControlError> {
+    let row = sqlx::query(&format!("SELECT {} FROM projects WHERE id = ? AND deleted_at IS NULL", PROJECT_COLUMNS))
         .bind(id)
-        .execute(pool)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to fetch project", e))?
+        .ok_or_else(|| MissionControlError::NotFound(format!("Project {} not found", id)))?;
+
+    Ok(project_from_row(&row))
+}
+
+// Accepts a pool or an open transaction, so imports can create projects atomically
+pub async fn create_new_project<'e, E>(project: CreateProject, executor: E) -> Result<Project, MissionControlError>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let name = project.name.trim();
+    if name.is_empty() {
+        return Err(MissionControlError::Validation("Project name cannot be empty".to_string()));
+    }
+
+    let row = sqlx::query(&format!("INSERT INTO projects (name, description) VALUES (?, ?) RETURNING {}", PROJECT_COLUMNS))
+        .bind(name)
+        .bind(&project.description)
+        .fetch_one(executor)
+        .await
+        .map_err(|e| map_name_conflict("Failed to create project", name, e))?;
+
+    Ok(project_from_row(&row))
+}
+
+// Create a project as an undoable operation
+pub async fn create_project(project: CreateProject, pool: &SqlitePool) -> Result<Project, MissionControlError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    journal::begin("Create project", &mut tx).await?;
+    let project = create_new_project(project, &mut *tx).await?;
+    journal::finish(&mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit project", e))?;
+
+    Ok(project)
+}
+
+// Change any of a project's name, description, color and icon, or archive or
+// unarchive it, as one undoable edit
+pub async fn update_project(id: i64, update: UpdateProject, pool: &SqlitePool) -> Result<Project, MissionControlError> {
+    if update.name.is_none()
+        && update.description.is_none()
+        && update.color.is_none()
+        && update.icon.is_none()
+        && update.archived.is_none()
+    {
+        return Err(MissionControlError::Validation("No fields to update".to_string()));
+    }
+    let name = update.name.as_deref().map(str::trim);
+    if name == Some("") {
+        return Err(MissionControlError::Validation("Project name cannot be empty".to_string()));
+    }
+    // An empty description, color or icon clears it
+    let description = update.description.as_deref().map(|description| {
+        let description = description.trim();
+        (!description.is_empty()).then_some(description)
+    });
+    let color = match update.color.as_deref().map(str::trim) {
+        Some("") => Some(None),
+        Some(value) => Some(Some(normalize_color(value).ok_or_else(|| {
+            MissionControlError::Validation(format!("'{}' is not a color; use #rgb or #rrggbb", value))
+        })?)),
+        None => None,
+    };
+    let icon = match update.icon.as_deref().map(str::trim) {
+        Some("") => Some(None),
+        Some(value) if value.chars().count() > MAX_ICON_CHARS => {
+            return Err(MissionControlError::Validation(format!(
+                "A project icon can be at most {} characters",
+                MAX_ICON_CHARS
+            )));
+        }
+        Some(value) => Some(Some(value)),
+        None => None,
+    };
+
+    let mut query_builder = sqlx::QueryBuilder::new("UPDATE projects SET ");
+    let mut separated = query_builder.separated(", ");
+    if let Some(name) = name {
+        separated.push("name = ");
+        separated.push_bind_unseparated(name);
+    }
+    if let Some(description) = description {
+        separated.push("description = ");
+        separated.push_bind_unseparated(description);
+    }
+    if let Some(color) = color {
+        separated.push("color = ");
+        separated.push_bind_unseparated(color);
+    }
+    if let Some(icon) = icon {
+        separated.push("icon = ");
+        separated.push_bind_unseparated(icon);
+    }
+    match update.archived {
+        // Archiving an archived project keeps the original time
+        Some(true) => {
+            separated.push("archived_at = COALESCE(archived_at, CURRENT_TIMESTAMP)");
+        }
+        Some(false) => {
+            separated.push("archived_at = NULL");
+        }
+        None => {}
+    }
+    query_builder.push(" WHERE id = ");
+    query_builder.push_bind(id);
+    query_builder.push(" AND deleted_at IS NULL");
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    let label = match update.archived {
+        Some(true) => "Archive project",
+        Some(false) => "Unarchive project",
+        None => "Edit project",
+    };
+    journal::begin(label, &mut tx).await?;
+
+    let (current_name, archived_at): (String, Option<String>) =
+        sqlx::query_as("SELECT name, archived_at FROM projects WHERE id = ? AND deleted_at IS NULL")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to look up project", e))?
+            .ok_or_else(|| MissionControlError::NotFound(format!("Project {} not found", id)))?;
+    if archived_at.is_some() {
+        // An archived project can only be edited as part of unarchiving it
+        let edits = update.name.is_some() || update.description.is_some() || update.color.is_some() || update.icon.is_some();
+        if edits && update.archived != Some(false) {
+            return Err(archived_error(&current_name));
+        }
+    } else if update.archived == Some(true) {
+        ensure_not_last_active(id, "archive", &mut tx).await?;
+    }
+
+    query_builder
+        .build()
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| map_name_conflict("Failed to update project", name.unwrap_or_default(), e))?;
+    journal::finish(&mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit project update", e))?;
+
+    get_project_by_id(id, pool).await
+}
+
+// Set a project aside: it leaves the default project list and becomes read-only
+pub async fn archive_project(id: i64, pool: &SqlitePool) -> Result<Project, MissionControlError> {
+    update_project(id, UpdateProject { archived: Some(true), ..Default::default() }, pool).await
+}
+
+pub async fn unarchive_project(id: i64, pool: &SqlitePool) -> Result<Project, MissionControlError> {
+    update_project(id, UpdateProject { archived: Some(false), ..Default::default() }, pool).await
+}
+
+// Move a project to the trash with its todos and whiteboard. Todos already in the
+// trash keep their own deletion time, so they aren't restored with the project.
+pub async fn delete_project_by_id(id: i64, pool: &SqlitePool) -> Result<(), MissionControlError> {
+    let mut tx = pool
+        .begin()
         .await
-        .map_err(|e| {
-            let error_msg = format!("Failed to delete excalidraw data for project: {}", e);
-            println!("{}", error_msg);
-            error_msg
-        })?;
-
-    // Delete the project
-    sqlx::query("DELETE FROM projects WHERE id = ?")
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    journal::begin("Delete project", &mut tx).await?;
+
+    let archived_at: Option<String> = sqlx::query_scalar::<_, Option<String>>("SELECT archived_at FROM projects WHERE id = ? AND deleted_at IS NULL")
         .bind(id)
-        .execute(pool)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to look up project", e))?
+        .ok_or_else(|| MissionControlError::NotFound(format!("Project {} not found", id)))?;
+
+    // An archived project can always go; an active one only while another stays open
+    if archived_at.is_none() {
+        ensure_not_last_active(id, "delete", &mut tx).await?;
+    }
+
+    let deleted_at = trash::deletion_stamp();
+    for (query, context) in [
+        ("UPDATE todos SET deleted_at = ? WHERE project_id = ? AND deleted_at IS NULL", "Failed to delete todos for project"),
+        ("UPDATE excalidraw_data SET deleted_at = ? WHERE project_id = ? AND deleted_at IS NULL", "Failed to delete excalidraw data for project"),
+        ("UPDATE projects SET deleted_at = ? WHERE id = ?", "Failed to delete project"),
+    ] {
+        sqlx::query(query)
+            .bind(&deleted_at)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx(context, e))?;
+    }
+    journal::finish(&mut tx).await?;
+
+    tx.commit()
         .await
-        .map_err(|e| {
-            let error_msg = format!("Failed to delete project: {}", e);
-            println!("{}", error_msg);
-            error_msg
-        })?;
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit project deletion", e))?;
 
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::excalidraw;
+    use crate::test_support::{exec, migrated_pool, project, todo};
+
+    fn rename(name: &str) -> UpdateProject {
+        UpdateProject { name: Some(name.to_string()), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn missing_project_is_not_found() {
+        let pool = migrated_pool().await;
+        project("Inbox", &pool).await;
+
+        assert_eq!(get_project_by_id(42, &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!(update_project(42, rename("Other"), &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!(archive_project(42, &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!(delete_project_by_id(42, &pool).await.unwrap_err().kind(), "not_found");
+    }
+
+    #[tokio::test]
+    async fn last_active_project_stays() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+
+        let error = delete_project_by_id(inbox.id, &pool).await.unwrap_err();
+        assert_eq!(error.kind(), "conflict");
+        assert_eq!(error.message(), "Cannot delete the last active project");
+        assert_eq!(archive_project(inbox.id, &pool).await.unwrap_err().kind(), "conflict");
+
+        // An archived project doesn't count as an open one, but can itself be deleted
+        let side = project("Side", &pool).await;
+        archive_project(side.id, &pool).await.unwrap();
+        assert_eq!(delete_project_by_id(inbox.id, &pool).await.unwrap_err().kind(), "conflict");
+        delete_project_by_id(side.id, &pool).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn duplicate_name_is_a_conflict() {
+        let pool = migrated_pool().await;
+        project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+
+        let error = create_project(CreateProject { name: " Inbox ".to_string(), description: None }, &pool)
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), "conflict");
+        assert_eq!(error.message(), "A project named 'Inbox' already exists");
+        assert_eq!(update_project(side.id, rename("Inbox"), &pool).await.unwrap_err().kind(), "conflict");
+    }
+
+    #[tokio::test]
+    async fn invalid_fields_are_rejected() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+
+        let empty = create_project(CreateProject { name: "   ".to_string(), description: None }, &pool).await;
+        assert_eq!(empty.unwrap_err().kind(), "validation");
+        for update in [
+            UpdateProject::default(),
+            rename(""),
+            UpdateProject { color: Some("red".to_string()), ..Default::default() },
+            UpdateProject { color: Some("#12345".to_string()), ..Default::default() },
+            UpdateProject { icon: Some("x".repeat(MAX_ICON_CHARS + 1)), ..Default::default() },
+        ] {
+            assert_eq!(update_project(inbox.id, update, &pool).await.unwrap_err().kind(), "validation");
+        }
+
+        let updated = update_project(inbox.id, UpdateProject { color: Some("#ABC".to_string()), ..Default::default() }, &pool)
+            .await
+            .unwrap();
+        assert_eq!(updated.color.as_deref(), Some("#aabbcc"));
+    }
+
+    #[tokio::test]
+    async fn archived_project_is_read_only() {
+        let pool = migrated_pool().await;
+        project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+        archive_project(side.id, &pool).await.unwrap();
+
+        assert_eq!(update_project(side.id, rename("Renamed"), &pool).await.unwrap_err().kind(), "conflict");
+
+        // Unarchiving can carry edits along
+        let update = UpdateProject { archived: Some(false), ..rename("Renamed") };
+        let side = update_project(side.id, update, &pool).await.unwrap();
+        assert_eq!(side.name, "Renamed");
+        assert!(side.archived_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn closed_pool_is_a_database_error() {
+        let pool = migrated_pool().await;
+        pool.close().await;
+
+        let error = get_all_projects(false, &pool).await.unwrap_err();
+        assert_eq!(error.kind(), "database");
+        assert_eq!(error.message(), "Failed to fetch projects");
+    }
+
+    // The project, its todos and its board, with their trash state, and the undo history
+    async fn deletion_state(pool: &SqlitePool) -> (Vec<String>, i64) {
+        let rows = sqlx::query_scalar(
+            "SELECT 'project ' || id || ' ' || IFNULL(deleted_at, 'live') FROM projects
+             UNION ALL
+             SELECT 'todo ' || id || ' ' || IFNULL(deleted_at, 'live') FROM todos
+             UNION ALL
+             SELECT 'board ' || id || ' ' || elements || ' ' || IFNULL(deleted_at, 'live') FROM excalidraw_data
+             ORDER BY 1",
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap();
+        let entries = sqlx::query_scalar("SELECT COUNT(*) FROM undo_entries").fetch_one(pool).await.unwrap();
+        (rows, entries)
+    }
+
+    #[tokio::test]
+    async fn a_failed_deletion_changes_nothing() {
+        let pool = migrated_pool().await;
+        project("Inbox", &pool).await;
+        let launch = project("Launch", &pool).await;
+        let venue = todo("Book the venue", launch.id, None, &pool).await;
+        todo("Compare offers", launch.id, Some(venue.id), &pool).await;
+        excalidraw::save_excalidraw_data("[1]".to_string(), "{}".to_string(), launch.id, &pool).await.unwrap();
+        let before = deletion_state(&pool).await;
+
+        // Fail after the todos were trashed, then after the board was too
+        for (table, trigger) in [("excalidraw_data", "refuse_board_delete"), ("projects", "refuse_project_delete")] {
+            exec(
+                &format!(
+                    "CREATE TRIGGER {} BEFORE UPDATE OF deleted_at ON {} BEGIN SELECT RAISE(ABORT, 'refused'); END;",
+                    trigger, table
+                ),
+                &pool,
+            )
+            .await;
+            assert!(delete_project_by_id(launch.id, &pool).await.is_err());
+            assert_eq!(deletion_state(&pool).await, before);
+            exec(&format!("DROP TRIGGER {}", trigger), &pool).await;
+        }
+
+        delete_project_by_id(launch.id, &pool).await.unwrap();
+        assert_ne!(deletion_state(&pool).await, before);
+    }
+}
@@ -0,0 +1,156 @@
+use chrono::{Local, NaiveDate, Utc};
+use sqlx::{sqlite::SqlitePool, Row, SqliteConnection};
+use crate::error::MissionControlError;
+use crate::recurrence;
+use crate::services::ordering::POSITION_GAP;
+use crate::services::{projects, todos};
+use crate::types::{Recurrence, Todo};
+
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+fn rule_to_json(rule: &Recurrence) -> Result<String, MissionControlError> {
+    serde_json::to_string(rule)
+        .map_err(|e| MissionControlError::Validation(format!("Failed to serialize recurrence: {}", e)))
+}
+
+// Create the next instance of the recurring todo `id`, due on the next occurrence
+// that isn't in the past, and hand the rule over to it. Returns the new todo's ID,
+// or None when `id` doesn't repeat.
+pub async fn materialize_next(id: i64, conn: &mut SqliteConnection) -> Result<Option<i64>, MissionControlError> {
+    let row = sqlx::query("SELECT due_at, recurrence FROM todos WHERE id = ? AND recurrence IS NOT NULL")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to look up recurring todo", e))?;
+
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let rule: Recurrence = match serde_json::from_str(&row.get::<String, _>("recurrence")) {
+        Ok(rule) => rule,
+        Err(_) => return Ok(None),
+    };
+
+    let due_at = row
+        .get::<Option<String>, _>("due_at")
+        .as_deref()
+        .and_then(todos::parse_due_at)
+        .unwrap_or_else(|| Utc::now().naive_utc());
+    let next_due_at = recurrence::next_due(&rule, due_at, today(), &Local).ok_or_else(|| {
+        MissionControlError::Validation(format!("Could not work out when todo {} repeats", id))
+    })?;
+
+    // Copy the todo rather than going through create_new_todo, whose generic
+    // connection type isn't Send inside the scheduler's spawned task
+    let next_id: i64 = sqlx::query_scalar(
+        r#"
+        INSERT INTO todos (text, time, priority, due_at, recurrence, project_id, parent_id, position)
+        SELECT text, time, priority, ?1, recurrence, project_id, parent_id,
+               (SELECT COALESCE(MAX(position), 0) + ?2 FROM todos AS siblings WHERE siblings.project_id = todos.project_id)
+        FROM todos WHERE id = ?3
+        RETURNING id
+        "#,
+    )
+    .bind(todos::format_due_at(next_due_at))
+    .bind(POSITION_GAP)
+    .bind(id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to schedule next instance", e))?;
+
+    sqlx::query("INSERT INTO todo_tags (todo_id, tag_id) SELECT ?, tag_id FROM todo_tags WHERE todo_id = ?")
+        .bind(next_id)
+        .bind(id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to copy tags to next instance", e))?;
+
+    sqlx::query("UPDATE todos SET recurrence = NULL WHERE id = ?")
+        .bind(id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to hand over recurrence", e))?;
+
+    Ok(Some(next_id))
+}
+
+// Make a todo repeat, or stop it repeating with None. A todo without a due date
+// gets one at the end of its first occurrence.
+pub async fn set_recurrence(id: i64, rule: Option<Recurrence>, pool: &SqlitePool) -> Result<Todo, MissionControlError> {
+    if let Some(ref rule) = rule {
+        recurrence::validate(rule).map_err(MissionControlError::Validation)?;
+    }
+
+    let (completed, due_at): (bool, Option<String>) =
+        sqlx::query_as("SELECT completed != 0, due_at FROM todos WHERE id = ? AND deleted_at IS NULL")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to look up todo", e))?
+            .ok_or_else(|| MissionControlError::NotFound(format!("Todo {} not found", id)))?;
+
+    projects::ensure_todos_not_archived(&[id], pool).await?;
+    if completed && rule.is_some() {
+        return Err(MissionControlError::Validation(
+            "A completed todo can't be made recurring; reopen it first".to_string(),
+        ));
+    }
+
+    let due_at = match (&rule, due_at) {
+        (Some(rule), None) => recurrence::first_due(rule, today(), &Local).map(todos::format_due_at),
+        (_, due_at) => due_at,
+    };
+
+    sqlx::query("UPDATE todos SET recurrence = ?, due_at = ? WHERE id = ?")
+        .bind(rule.as_ref().map(rule_to_json).transpose()?)
+        .bind(due_at)
+        .bind(id)
+        .execute(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to update recurrence", e))?;
+
+    todos::get_todo_by_id(id, pool).await
+}
+
+// Create the next instance of every open recurring todo whose day has passed. The
+// missed one stays behind as an overdue todo without the rule. Archived projects
+// are left alone.
+pub async fn roll_over(pool: &SqlitePool) -> Result<usize, MissionControlError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    let open: Vec<(i64, Option<String>)> =
+        sqlx::query_as(
+            r#"
+            SELECT id, due_at FROM todos
+            WHERE recurrence IS NOT NULL AND completed = 0 AND deleted_at IS NULL
+              AND project_id IN (SELECT id FROM projects WHERE archived_at IS NULL)
+            "#,
+        )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to read recurring todos", e))?;
+
+    let today = today();
+    let mut created = 0;
+    for (id, due_at) in open {
+        let missed = due_at
+            .as_deref()
+            .and_then(todos::parse_due_at)
+            .is_some_and(|due_at| recurrence::utc_to_local(due_at, &Local).date() < today);
+        if missed && materialize_next(id, &mut tx).await?.is_some() {
+            created += 1;
+        }
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit recurring todos", e))?;
+
+    Ok(created)
+}
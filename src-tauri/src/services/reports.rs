@@ -0,0 +1,267 @@
+use sqlx::{sqlite::SqlitePool, Row};
+use crate::error::MissionControlError;
+use crate::services::{projects, sessions};
+use crate::types::{ProjectReport, TodoReport};
+
+// actual / estimated, e.g. 1.5 means the todo took 50% longer than planned
+pub fn overrun_ratio(estimated_minutes: f64, actual_minutes: f64) -> Option<f64> {
+    if estimated_minutes > 0.0 {
+        Some(actual_minutes / estimated_minutes)
+    } else {
+        None
+    }
+}
+
+// Mean of min(estimate, actual) / max(estimate, actual) over completed todos that
+// were actually tracked. 1.0 means every estimate was spot on; over- and
+// under-estimating by the same factor score the same.
+pub fn accuracy_score(todos: &[TodoReport]) -> Option<f64> {
+    let scores: Vec<f64> = todos
+        .iter()
+        .filter(|todo| todo.completed && todo.actual_minutes > 0.0 && todo.estimated_minutes > 0)
+        .map(|todo| {
+            let estimated = f64::from(todo.estimated_minutes);
+            estimated.min(todo.actual_minutes) / estimated.max(todo.actual_minutes)
+        })
+        .collect();
+
+    if scores.is_empty() {
+        None
+    } else {
+        Some(scores.iter().sum::<f64>() / scores.len() as f64)
+    }
+}
+
+// Estimate-vs-actual report for one project, counting focus sessions started
+// between `from` and `to` (inclusive `YYYY-MM-DD` dates; either may be open-ended).
+// A todo's sessions count wherever the todo is now, including time logged before
+// it moved projects, so its estimate is always compared with all the work on it.
+pub async fn get_project_report(
+    project_id: i64,
+    from: Option<String>,
+    to: Option<String>,
+    pool: &SqlitePool,
+) -> Result<ProjectReport, MissionControlError> {
+    let from_date = from.as_deref().map(sessions::parse_date).transpose()?;
+    let to_date = to.as_deref().map(sessions::parse_date).transpose()?;
+    if let (Some(from_date), Some(to_date)) = (from_date, to_date) {
+        if from_date > to_date {
+            return Err(MissionControlError::Validation("Start date must not be after end date".to_string()));
+        }
+    }
+
+    let project = projects::get_project_by_id(project_id, pool).await?;
+
+    // Worked time is wall-clock time minus pauses; a still-open session counts up to now
+    let rows = sqlx::query(
+        r#"
+        SELECT t.id, t.text, t.completed, t.time,
+               COUNT(s.id) AS session_count,
+               COALESCE(SUM(MAX(0,
+                   CAST(strftime('%s', COALESCE(s.ended_at, CURRENT_TIMESTAMP)) AS INTEGER)
+                   - CAST(strftime('%s', s.started_at) AS INTEGER)
+                   - s.paused_seconds
+               )), 0) AS actual_seconds
+        FROM todos t
+        LEFT JOIN focus_sessions s
+            ON s.todo_id = t.id
+            AND (?1 IS NULL OR date(s.started_at) >= ?1)
+            AND (?2 IS NULL OR date(s.started_at) <= ?2)
+        WHERE t.project_id = ?3 AND t.deleted_at IS NULL
+        GROUP BY t.id
+        ORDER BY t.created_at ASC, t.id ASC
+        "#,
+    )
+    .bind(from_date.map(|date| date.to_string()))
+    .bind(to_date.map(|date| date.to_string()))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to build project report", e))?;
+
+    let todos: Vec<TodoReport> = rows
+        .into_iter()
+        .map(|row| {
+            let completed_int: i64 = row.get("completed");
+            let estimated_minutes: i32 = row.get("time");
+            let actual_seconds: i64 = row.get("actual_seconds");
+            let actual_minutes = actual_seconds as f64 / 60.0;
+            let session_count: i64 = row.get("session_count");
+            TodoReport {
+                todo_id: row.get("id"),
+                text: row.get("text"),
+                completed: completed_int != 0,
+                estimated_minutes,
+                actual_minutes,
+                session_count,
+                overrun_ratio: if session_count > 0 {
+                    overrun_ratio(f64::from(estimated_minutes), actual_minutes)
+                } else {
+                    None
+                },
+            }
+        })
+        .collect();
+
+    let estimated_minutes: i64 = todos.iter().map(|todo| i64::from(todo.estimated_minutes)).sum();
+    let actual_minutes: f64 = todos.iter().map(|todo| todo.actual_minutes).sum();
+    // Only todos that were worked on in the window say anything about overruns
+    let tracked_estimate: i64 = todos
+        .iter()
+        .filter(|todo| todo.session_count > 0)
+        .map(|todo| i64::from(todo.estimated_minutes))
+        .sum();
+
+    Ok(ProjectReport {
+        project_id: project.id,
+        project_name: project.name,
+        from: from_date.map(|date| date.to_string()),
+        to: to_date.map(|date| date.to_string()),
+        total_todos: todos.len() as i64,
+        completed_todos: todos.iter().filter(|todo| todo.completed).count() as i64,
+        session_count: todos.iter().map(|todo| todo.session_count).sum(),
+        estimated_minutes,
+        actual_minutes,
+        overrun_ratio: overrun_ratio(tracked_estimate as f64, actual_minutes),
+        accuracy_score: accuracy_score(&todos),
+        todos,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{ordering, todos};
+    use crate::types::UpdateTodo;
+    use crate::test_support::{exec, migrated_pool, project, todo};
+
+    fn report(estimated_minutes: i32, actual_minutes: f64, completed: bool) -> TodoReport {
+        TodoReport {
+            todo_id: 1,
+            text: String::new(),
+            completed,
+            estimated_minutes,
+            actual_minutes,
+            session_count: 1,
+            overrun_ratio: None,
+        }
+    }
+
+    // A finished session on `todo_id` started at `started_at`, lasting `minutes`
+    // of which `paused_minutes` were spent paused
+    async fn log(todo_id: i64, project_id: i64, started_at: &str, minutes: i64, paused_minutes: i64, pool: &SqlitePool) {
+        exec(
+            &format!(
+                "INSERT INTO focus_sessions (todo_id, project_id, started_at, ended_at, paused_seconds, outcome) \
+                 VALUES ({}, {}, '{}', datetime('{}', '+{} minutes'), {}, 'completed')",
+                todo_id, project_id, started_at, started_at, minutes, paused_minutes * 60
+            ),
+            pool,
+        )
+        .await;
+    }
+
+    #[test]
+    fn overrun_is_actual_over_estimate() {
+        assert_eq!(overrun_ratio(20.0, 30.0), Some(1.5));
+        assert_eq!(overrun_ratio(20.0, 10.0), Some(0.5));
+        assert_eq!(overrun_ratio(20.0, 0.0), Some(0.0));
+        assert_eq!(overrun_ratio(0.0, 30.0), None);
+    }
+
+    #[test]
+    fn accuracy_treats_over_and_under_estimates_alike() {
+        assert_eq!(accuracy_score(&[report(30, 30.0, true)]), Some(1.0));
+        assert_eq!(accuracy_score(&[report(30, 60.0, true)]), Some(0.5));
+        assert_eq!(accuracy_score(&[report(30, 15.0, true)]), Some(0.5));
+        assert_eq!(accuracy_score(&[report(30, 60.0, true), report(30, 30.0, true)]), Some(0.75));
+
+        // Open, untracked and unestimated todos say nothing about accuracy
+        let ignored = [report(30, 60.0, false), report(30, 0.0, true), report(0, 60.0, true)];
+        assert_eq!(accuracy_score(&ignored), None);
+        assert_eq!(accuracy_score(&[ignored[0].clone(), report(30, 45.0, true)]), Some(30.0 / 45.0));
+        assert_eq!(accuracy_score(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn report_sums_worked_time_in_the_window() {
+        let pool = migrated_pool().await;
+        let launch = project("Launch", &pool).await;
+        let spec = todo("Spec", launch.id, None, &pool).await;
+        let build = todo("Build", launch.id, None, &pool).await;
+        let idle = todo("Idle", launch.id, None, &pool).await;
+        todos::update_todo_by_id(spec.id, UpdateTodo { completed: Some(true), ..Default::default() }, &pool)
+            .await
+            .unwrap();
+
+        // Spec: 30 minutes with 5 paused, then 10 more; Build: 50 minutes, and one before the window
+        log(spec.id, launch.id, "2024-03-01 09:00:00", 30, 5, &pool).await;
+        log(spec.id, launch.id, "2024-03-02 09:00:00", 10, 0, &pool).await;
+        log(build.id, launch.id, "2024-03-02 10:00:00", 50, 0, &pool).await;
+        log(build.id, launch.id, "2024-02-20 10:00:00", 90, 0, &pool).await;
+
+        let result = get_project_report(launch.id, Some("2024-03-01".to_string()), Some("2024-03-31".to_string()), &pool)
+            .await
+            .unwrap();
+        let rows: Vec<(i64, f64, i64, Option<f64>)> = result
+            .todos
+            .iter()
+            .map(|todo| (todo.todo_id, todo.actual_minutes, todo.session_count, todo.overrun_ratio))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![(spec.id, 35.0, 2, Some(35.0 / 25.0)), (build.id, 50.0, 1, Some(2.0)), (idle.id, 0.0, 0, None)]
+        );
+        assert_eq!(result.total_todos, 3);
+        assert_eq!(result.completed_todos, 1);
+        assert_eq!(result.session_count, 3);
+        assert_eq!(result.estimated_minutes, 75);
+        assert_eq!(result.actual_minutes, 85.0);
+        // Only tracked todos count towards the overrun: 85 minutes against 50 planned
+        assert_eq!(result.overrun_ratio, Some(85.0 / 50.0));
+        // Only the completed Spec counts towards accuracy
+        assert_eq!(result.accuracy_score, Some(25.0 / 35.0));
+
+        let all_time = get_project_report(launch.id, None, None, &pool).await.unwrap();
+        assert_eq!(all_time.actual_minutes, 175.0);
+    }
+
+    #[tokio::test]
+    async fn moved_todos_take_their_time_along() {
+        let pool = migrated_pool().await;
+        let launch = project("Launch", &pool).await;
+        let ops = project("Ops", &pool).await;
+        let spec = todo("Spec", launch.id, None, &pool).await;
+        log(spec.id, launch.id, "2024-03-01 09:00:00", 20, 0, &pool).await;
+
+        ordering::move_todos(&[spec.id], Some(ops.id), None, None, &pool).await.unwrap();
+        log(spec.id, ops.id, "2024-03-02 09:00:00", 10, 0, &pool).await;
+
+        let before = get_project_report(launch.id, None, None, &pool).await.unwrap();
+        assert!(before.todos.is_empty());
+        assert_eq!(before.actual_minutes, 0.0);
+
+        let after = get_project_report(ops.id, None, None, &pool).await.unwrap();
+        assert_eq!(after.todos.len(), 1);
+        assert_eq!(after.todos[0].actual_minutes, 30.0);
+        assert_eq!(after.todos[0].session_count, 2);
+    }
+
+    #[tokio::test]
+    async fn trashed_todos_are_left_out() {
+        let pool = migrated_pool().await;
+        let launch = project("Launch", &pool).await;
+        let spec = todo("Spec", launch.id, None, &pool).await;
+        let gone = todo("Gone", launch.id, None, &pool).await;
+        log(gone.id, launch.id, "2024-03-01 09:00:00", 20, 0, &pool).await;
+        todos::delete_todo_by_id(gone.id, None, &pool).await.unwrap();
+
+        let result = get_project_report(launch.id, None, None, &pool).await.unwrap();
+        assert_eq!(result.todos.iter().map(|todo| todo.todo_id).collect::<Vec<_>>(), vec![spec.id]);
+        assert_eq!(result.session_count, 0);
+
+        let reversed = get_project_report(launch.id, Some("2024-03-02".to_string()), Some("2024-03-01".to_string()), &pool).await;
+        assert_eq!(reversed.unwrap_err().kind(), "validation");
+        assert_eq!(get_project_report(42, None, None, &pool).await.unwrap_err().kind(), "not_found");
+    }
+}
@@ -0,0 +1,273 @@
+use sqlx::{sqlite::SqlitePool, Row};
+use crate::error::MissionControlError;
+use crate::types::{SearchFilters, SearchHit, SearchKind};
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 200;
+// Words of context around the matches in a snippet
+const SNIPPET_TOKENS: i64 = 12;
+// Control characters can't come out of the tokenizer as part of a match, so they
+// mark matches in snippets until the text is escaped
+const MATCH_START: &str = "\u{1}";
+const MATCH_END: &str = "\u{2}";
+
+// Turn what the user typed into an FTS5 query in which every word must appear,
+// as a prefix. Words are quoted so quotes, `-`, `OR` and the like are searched for
+// rather than parsed; words without letters or digits can't match and are dropped.
+pub fn fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn highlight(snippet: &str) -> String {
+    escape_html(snippet)
+        .replace(MATCH_START, "<mark>")
+        .replace(MATCH_END, "</mark>")
+}
+
+// Ranked hits for `query`, best first. Todo and project titles weigh more than
+// project descriptions and whiteboard text.
+pub async fn search(query: &str, filters: &SearchFilters, pool: &SqlitePool) -> Result<Vec<SearchHit>, MissionControlError> {
+    let fts = match fts_query(query) {
+        Some(fts) => fts,
+        None => return Ok(Vec::new()),
+    };
+    let limit = filters.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let mut query_builder = sqlx::QueryBuilder::new(
+        r#"
+        SELECT search_index.kind, search_index.ref_id, search_index.project_id, search_index.title,
+               snippet(search_index, -1, "#,
+    );
+    query_builder.push_bind(MATCH_START);
+    query_builder.push(", ");
+    query_builder.push_bind(MATCH_END);
+    query_builder.push(", '…', ");
+    query_builder.push_bind(SNIPPET_TOKENS);
+    query_builder.push(
+        r#") AS snippet,
+               bm25(search_index, 10.0, 1.0) AS rank,
+               projects.name AS project_name,
+               todos.completed
+        FROM search_index
+        JOIN projects ON projects.id = search_index.project_id AND projects.deleted_at IS NULL
+        LEFT JOIN todos ON search_index.kind = 'todo' AND todos.id = search_index.ref_id
+        WHERE search_index MATCH "#,
+    );
+    query_builder.push_bind(fts);
+    query_builder.push(" AND (search_index.kind != 'todo' OR todos.deleted_at IS NULL)");
+
+    if let Some(project_id) = filters.project_id {
+        query_builder.push(" AND search_index.project_id = ");
+        query_builder.push_bind(project_id);
+    }
+    if !filters.kinds.is_empty() {
+        query_builder.push(" AND search_index.kind IN (");
+        let mut separated = query_builder.separated(", ");
+        for kind in &filters.kinds {
+            separated.push_bind(kind.as_str());
+        }
+        separated.push_unseparated(")");
+    }
+    if filters.exclude_completed {
+        query_builder.push(" AND (search_index.kind != 'todo' OR todos.completed = 0)");
+    }
+    query_builder.push(" ORDER BY rank ASC LIMIT ");
+    query_builder.push_bind(i64::from(limit));
+
+    let rows = query_builder
+        .build()
+        .fetch_all(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to search", e))?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let kind = SearchKind::parse(row.get::<&str, _>("kind"))?;
+            let completed: Option<i64> = row.get("completed");
+            Some(SearchHit {
+                kind,
+                id: row.get("ref_id"),
+                project_id: row.get("project_id"),
+                project_name: row.get("project_name"),
+                title: row.get("title"),
+                snippet: highlight(row.get("snippet")),
+                completed: completed.map(|completed| completed != 0),
+                rank: row.get("rank"),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{excalidraw, projects, todos, trash};
+    use crate::test_support::{exec, migrated_pool, project, todo};
+    use crate::types::{SubtaskPolicy, TrashKind, UpdateProject, UpdateTodo};
+
+    // (kind, id, project id) of every hit for `query`, best first
+    async fn hits(query: &str, pool: &SqlitePool) -> Vec<(SearchKind, i64, i64)> {
+        search(query, &SearchFilters::default(), pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|hit| (hit.kind, hit.id, hit.project_id))
+            .collect()
+    }
+
+    #[test]
+    fn user_input_is_quoted_word_by_word() {
+        assert_eq!(fts_query("launch  plan").as_deref(), Some(r#""launch"* "plan"*"#));
+        assert_eq!(fts_query(r#"say "hi""#).as_deref(), Some(r#""say"* """hi"""*"#));
+        assert_eq!(fts_query("draft OR final").as_deref(), Some(r#""draft"* "OR"* "final"*"#));
+        assert_eq!(fts_query("-draft NOT").as_deref(), Some(r#""-draft"* "NOT"*"#));
+        assert_eq!(fts_query(" -- !! "), None);
+        assert_eq!(fts_query(""), None);
+    }
+
+    #[test]
+    fn snippets_are_escaped_around_the_marks() {
+        let snippet = format!("{}<b>{} & \"Tom's\"", MATCH_START, MATCH_END);
+        assert_eq!(highlight(&snippet), "<mark>&lt;b&gt;</mark> &amp; &quot;Tom&#39;s&quot;");
+    }
+
+    #[tokio::test]
+    async fn query_syntax_is_searched_for_not_parsed() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let draft = todo("Draft the <script> & notes", inbox.id, None, &pool).await;
+        todo("Final copy", inbox.id, None, &pool).await;
+
+        // OR is a word that must appear, not an operator
+        assert!(hits("draft OR final", &pool).await.is_empty());
+        assert_eq!(hits("-draft", &pool).await, vec![(SearchKind::Todo, draft.id, inbox.id)]);
+        for input in [r#""unbalanced"#, "NEAR(", "draft*", "AND", "title:draft"] {
+            assert!(search(input, &SearchFilters::default(), &pool).await.is_ok(), "{}", input);
+        }
+
+        let hit = search("scr", &SearchFilters::default(), &pool).await.unwrap().remove(0);
+        assert_eq!(hit.snippet, "Draft the &lt;<mark>script</mark>&gt; &amp; notes");
+    }
+
+    #[tokio::test]
+    async fn the_index_follows_todo_changes() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+        let task = todo("Order badges", inbox.id, None, &pool).await;
+        assert_eq!(hits("badges", &pool).await, vec![(SearchKind::Todo, task.id, inbox.id)]);
+
+        let rename = UpdateTodo { text: Some("Order lanyards".to_string()), ..Default::default() };
+        todos::update_todo_by_id(task.id, rename, &pool).await.unwrap();
+        assert!(hits("badges", &pool).await.is_empty());
+        assert_eq!(hits("lanyards", &pool).await, vec![(SearchKind::Todo, task.id, inbox.id)]);
+
+        let moving = UpdateTodo { project_id: Some(side.id), ..Default::default() };
+        todos::update_todo_by_id(task.id, moving, &pool).await.unwrap();
+        assert_eq!(hits("lanyards", &pool).await, vec![(SearchKind::Todo, task.id, side.id)]);
+
+        exec(&format!("DELETE FROM todos WHERE id = {}", task.id), &pool).await;
+        assert!(hits("lanyards", &pool).await.is_empty());
+        let indexed: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM search_index WHERE kind = 'todo'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(indexed, 0);
+    }
+
+    #[tokio::test]
+    async fn the_index_follows_projects_and_whiteboards() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let launch = project("Launch", &pool).await;
+        let details = UpdateProject { description: Some("Venue and catering".to_string()), ..Default::default() };
+        projects::update_project(launch.id, details, &pool).await.unwrap();
+        assert_eq!(hits("catering", &pool).await, vec![(SearchKind::Project, launch.id, launch.id)]);
+
+        let rename = UpdateProject { name: Some("Kickoff".to_string()), ..Default::default() };
+        projects::update_project(launch.id, rename, &pool).await.unwrap();
+        assert!(hits("launch", &pool).await.is_empty());
+        assert_eq!(hits("kickoff", &pool).await, vec![(SearchKind::Project, launch.id, launch.id)]);
+
+        // Only live text elements are indexed
+        let elements = r#"[
+            {"type": "text", "text": "Seating chart"},
+            {"type": "text", "text": "Erased idea", "isDeleted": true},
+            {"type": "rectangle", "text": "Shape label"}
+        ]"#;
+        excalidraw::save_excalidraw_data(elements.to_string(), "{}".to_string(), inbox.id, &pool).await.unwrap();
+        let board = excalidraw::get_excalidraw_data(inbox.id, &pool).await.unwrap().unwrap();
+        assert_eq!(hits("seating", &pool).await, vec![(SearchKind::Whiteboard, board.id, inbox.id)]);
+        assert!(hits("erased", &pool).await.is_empty());
+        assert!(hits("shape", &pool).await.is_empty());
+
+        // Saving again replaces the board's text, and invalid JSON indexes as empty
+        excalidraw::save_excalidraw_data("not json".to_string(), "{}".to_string(), inbox.id, &pool).await.unwrap();
+        assert!(hits("seating", &pool).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn trashed_rows_are_left_out_until_restored() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let launch = project("Launch", &pool).await;
+        let plan = todo("Plan launch", inbox.id, None, &pool).await;
+        let venue = todo("Book launch venue", launch.id, None, &pool).await;
+        let elements = r#"[{"type": "text", "text": "launch checklist"}]"#;
+        excalidraw::save_excalidraw_data(elements.to_string(), "{}".to_string(), launch.id, &pool).await.unwrap();
+        assert_eq!(hits("launch", &pool).await.len(), 4);
+
+        todos::delete_todo_by_id(plan.id, Some(SubtaskPolicy::Delete), &pool).await.unwrap();
+        assert!(!hits("launch", &pool).await.iter().any(|(kind, id, _)| *kind == SearchKind::Todo && *id == plan.id));
+        projects::delete_project_by_id(launch.id, &pool).await.unwrap();
+        assert!(hits("launch", &pool).await.is_empty());
+
+        trash::restore_from_trash(TrashKind::Project, launch.id, &pool).await.unwrap();
+        trash::restore_from_trash(TrashKind::Todo, plan.id, &pool).await.unwrap();
+        let restored = hits("launch", &pool).await;
+        assert_eq!(restored.len(), 4);
+        assert!(restored.contains(&(SearchKind::Todo, venue.id, launch.id)));
+    }
+
+    #[tokio::test]
+    async fn titles_outrank_descriptions_and_boards() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let other = project("Other", &pool).await;
+        let details = UpdateProject { description: Some("roadmap".to_string()), ..Default::default() };
+        projects::update_project(other.id, details, &pool).await.unwrap();
+        let elements = r#"[{"type": "text", "text": "roadmap"}]"#;
+        excalidraw::save_excalidraw_data(elements.to_string(), "{}".to_string(), other.id, &pool).await.unwrap();
+        let task = todo("Roadmap", inbox.id, None, &pool).await;
+
+        let found = search("roadmap", &SearchFilters::default(), &pool).await.unwrap();
+        assert_eq!(found.len(), 3);
+        assert_eq!((found[0].kind, found[0].id), (SearchKind::Todo, task.id));
+        assert!(found[0].rank < found[1].rank);
+    }
+}
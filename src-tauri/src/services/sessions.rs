@@ -0,0 +1,282 @@
+use chrono::NaiveDate;
+use sqlx::{sqlite::{SqlitePool, SqliteRow}, Row};
+use crate::error::MissionControlError;
+use crate::services::projects;
+use crate::types::{FocusSession, SessionOutcome};
+
+const SESSION_COLUMNS: &str = "id, todo_id, project_id, started_at, ended_at, paused_seconds, outcome";
+
+// Sessions whose todo, and the project it is in now, are outside the trash
+const LIVE_SESSIONS: &str = "SELECT focus_sessions.id, focus_sessions.todo_id, focus_sessions.project_id, \
+     focus_sessions.started_at, focus_sessions.ended_at, focus_sessions.paused_seconds, focus_sessions.outcome \
+     FROM focus_sessions \
+     JOIN todos ON todos.id = focus_sessions.todo_id AND todos.deleted_at IS NULL \
+     JOIN projects ON projects.id = todos.project_id AND projects.deleted_at IS NULL";
+
+fn session_from_row(row: &SqliteRow) -> FocusSession {
+    let outcome: Option<String> = row.get("outcome");
+    FocusSession {
+        id: row.get("id"),
+        todo_id: row.get("todo_id"),
+        project_id: row.get("project_id"),
+        started_at: row.get("started_at"),
+        ended_at: row.get("ended_at"),
+        paused_seconds: row.get("paused_seconds"),
+        outcome: outcome.as_deref().and_then(SessionOutcome::parse),
+    }
+}
+
+pub async fn start_session(todo_id: i64, project_id: i64, pool: &SqlitePool) -> Result<FocusSession, MissionControlError> {
+    projects::ensure_not_archived(project_id, pool).await?;
+    let row = sqlx::query(&format!(
+        "INSERT INTO focus_sessions (todo_id, project_id) VALUES (?, ?) RETURNING {}",
+        SESSION_COLUMNS
+    ))
+    .bind(todo_id)
+    .bind(project_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to start focus session", e))?;
+
+    Ok(session_from_row(&row))
+}
+
+pub async fn pause_session(id: i64, pool: &SqlitePool) -> Result<(), MissionControlError> {
+    sqlx::query("UPDATE focus_sessions SET paused_at = CURRENT_TIMESTAMP WHERE id = ? AND ended_at IS NULL AND paused_at IS NULL")
+        .bind(id)
+        .execute(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to pause focus session", e))?;
+
+    Ok(())
+}
+
+pub async fn resume_session(id: i64, pool: &SqlitePool) -> Result<(), MissionControlError> {
+    sqlx::query(
+        r#"
+        UPDATE focus_sessions
+        SET paused_seconds = paused_seconds + (strftime('%s', 'now') - strftime('%s', paused_at)),
+            paused_at = NULL
+        WHERE id = ? AND ended_at IS NULL AND paused_at IS NOT NULL
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to resume focus session", e))?;
+
+    Ok(())
+}
+
+pub async fn end_session(id: i64, outcome: SessionOutcome, pool: &SqlitePool) -> Result<FocusSession, MissionControlError> {
+    // A session ended while paused still counts the time it sat paused
+    let row = sqlx::query(&format!(
+        r#"
+        UPDATE focus_sessions
+        SET ended_at = CURRENT_TIMESTAMP,
+            paused_seconds = paused_seconds + COALESCE(strftime('%s', 'now') - strftime('%s', paused_at), 0),
+            paused_at = NULL,
+            outcome = ?
+        WHERE id = ? AND ended_at IS NULL
+        RETURNING {}
+        "#,
+        SESSION_COLUMNS
+    ))
+    .bind(outcome.as_str())
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to end focus session", e))?
+    .ok_or_else(|| MissionControlError::NotFound(format!("No open focus session {}", id)))?;
+
+    Ok(session_from_row(&row))
+}
+
+// Sessions left open by a crash or forced quit can't be timed any more; close them
+// as abandoned with no elapsed time so they don't inflate reports.
+pub async fn abandon_open_sessions(pool: &SqlitePool) -> Result<u64, MissionControlError> {
+    let result = sqlx::query(
+        "UPDATE focus_sessions SET ended_at = started_at, paused_at = NULL, outcome = 'abandoned' WHERE ended_at IS NULL"
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to close stale focus sessions", e))?;
+
+    Ok(result.rows_affected())
+}
+
+// Sessions of todos in the trash are left out by every read below, and come back
+// when the todo is restored
+pub async fn get_sessions_by_todo(todo_id: i64, pool: &SqlitePool) -> Result<Vec<FocusSession>, MissionControlError> {
+    let rows = sqlx::query(&format!(
+        "{} WHERE focus_sessions.todo_id = ? ORDER BY focus_sessions.started_at ASC, focus_sessions.id ASC",
+        LIVE_SESSIONS
+    ))
+    .bind(todo_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to fetch focus sessions", e))?;
+
+    Ok(rows.iter().map(session_from_row).collect())
+}
+
+pub async fn get_sessions_by_project(project_id: i64, pool: &SqlitePool) -> Result<Vec<FocusSession>, MissionControlError> {
+    let rows = sqlx::query(&format!(
+        "{} WHERE focus_sessions.project_id = ? ORDER BY focus_sessions.started_at ASC, focus_sessions.id ASC",
+        LIVE_SESSIONS
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to fetch focus sessions", e))?;
+
+    Ok(rows.iter().map(session_from_row).collect())
+}
+
+// Parse an inclusive `YYYY-MM-DD` range bound
+pub fn parse_date(value: &str) -> Result<NaiveDate, MissionControlError> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").map_err(|_| {
+        MissionControlError::Validation(format!("Invalid date '{}', expected YYYY-MM-DD", value))
+    })
+}
+
+// Sessions started between `from` and `to` (inclusive `YYYY-MM-DD` dates, UTC),
+// optionally limited to one project
+pub async fn get_sessions_by_date_range(
+    from: &str,
+    to: &str,
+    project_id: Option<i64>,
+    pool: &SqlitePool,
+) -> Result<Vec<FocusSession>, MissionControlError> {
+    let (from, to) = (parse_date(from)?, parse_date(to)?);
+    if from > to {
+        return Err(MissionControlError::Validation("Start date must not be after end date".to_string()));
+    }
+
+    let mut query_builder = sqlx::QueryBuilder::new(format!(
+        "{} WHERE date(focus_sessions.started_at) BETWEEN ",
+        LIVE_SESSIONS
+    ));
+    query_builder.push_bind(from.to_string());
+    query_builder.push(" AND ");
+    query_builder.push_bind(to.to_string());
+
+    if let Some(project_id) = project_id {
+        query_builder.push(" AND focus_sessions.project_id = ");
+        query_builder.push_bind(project_id);
+    }
+
+    query_builder.push(" ORDER BY focus_sessions.started_at ASC, focus_sessions.id ASC");
+
+    let rows = query_builder
+        .build()
+        .fetch_all(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to fetch focus sessions", e))?;
+
+    Ok(rows.iter().map(session_from_row).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{projects, todos, trash};
+    use crate::test_support::{exec, migrated_pool, project, todo};
+    use crate::types::TrashKind;
+
+    async fn session_count(todo_id: i64, pool: &SqlitePool) -> usize {
+        get_sessions_by_todo(todo_id, pool).await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn session_errors_have_the_right_kind() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let task = todo("Task", inbox.id, None, &pool).await;
+
+        let session = start_session(task.id, inbox.id, &pool).await.unwrap();
+        end_session(session.id, SessionOutcome::Completed, &pool).await.unwrap();
+        // Only an open session can be ended
+        assert_eq!(end_session(session.id, SessionOutcome::Skipped, &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!(end_session(42, SessionOutcome::Skipped, &pool).await.unwrap_err().kind(), "not_found");
+
+        assert_eq!(parse_date("2024-13-01").unwrap_err().kind(), "validation");
+        let reversed = get_sessions_by_date_range("2024-02-01", "2024-01-01", None, &pool).await;
+        assert_eq!(reversed.unwrap_err().kind(), "validation");
+    }
+
+    #[tokio::test]
+    async fn deleting_a_todo_takes_its_sessions_along() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let task = todo("Task", inbox.id, None, &pool).await;
+        let other = todo("Other", inbox.id, None, &pool).await;
+        for todo_id in [task.id, task.id, other.id] {
+            let session = start_session(todo_id, inbox.id, &pool).await.unwrap();
+            end_session(session.id, SessionOutcome::Completed, &pool).await.unwrap();
+        }
+
+        sqlx::query("DELETE FROM todos WHERE id = ?").bind(task.id).execute(&pool).await.unwrap();
+        assert_eq!(session_count(task.id, &pool).await, 0);
+        assert_eq!(session_count(other.id, &pool).await, 1);
+    }
+
+    #[tokio::test]
+    async fn purging_the_trash_removes_sessions() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+        let task = todo("Task", inbox.id, None, &pool).await;
+        let side_task = todo("Side task", side.id, None, &pool).await;
+        start_session(task.id, inbox.id, &pool).await.unwrap();
+        start_session(side_task.id, side.id, &pool).await.unwrap();
+        abandon_open_sessions(&pool).await.unwrap();
+
+        todos::delete_todo_by_id(task.id, None, &pool).await.unwrap();
+        projects::delete_project_by_id(side.id, &pool).await.unwrap();
+        exec("UPDATE todos SET deleted_at = '2000-01-01 00:00:00' WHERE deleted_at IS NOT NULL", &pool).await;
+        exec("UPDATE projects SET deleted_at = '2000-01-01 00:00:00' WHERE deleted_at IS NOT NULL", &pool).await;
+
+        assert_eq!(trash::purge_expired(&pool).await.unwrap(), 3);
+        assert_eq!(session_count(task.id, &pool).await, 0);
+        assert_eq!(get_sessions_by_project(side.id, &pool).await.unwrap().len(), 0);
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM focus_sessions").fetch_one(&pool).await.unwrap();
+        assert_eq!(left, 0);
+    }
+
+    async fn started_today(project_id: Option<i64>, pool: &SqlitePool) -> usize {
+        let today = chrono::Utc::now().date_naive().to_string();
+        get_sessions_by_date_range(&today, &today, project_id, pool).await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn sessions_in_the_trash_are_hidden_until_restored() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+        let task = todo("Task", inbox.id, None, &pool).await;
+        let other = todo("Other", inbox.id, None, &pool).await;
+        let side_task = todo("Side task", side.id, None, &pool).await;
+        for (todo_id, project_id) in [(task.id, inbox.id), (other.id, inbox.id), (side_task.id, side.id)] {
+            start_session(todo_id, project_id, &pool).await.unwrap();
+        }
+        abandon_open_sessions(&pool).await.unwrap();
+        assert_eq!(started_today(None, &pool).await, 3);
+
+        todos::delete_todo_by_id(task.id, None, &pool).await.unwrap();
+        projects::delete_project_by_id(side.id, &pool).await.unwrap();
+        assert_eq!(session_count(task.id, &pool).await, 0);
+        assert_eq!(get_sessions_by_project(inbox.id, &pool).await.unwrap().len(), 1);
+        assert_eq!(get_sessions_by_project(side.id, &pool).await.unwrap().len(), 0);
+        assert_eq!(session_count(side_task.id, &pool).await, 0);
+        assert_eq!(started_today(None, &pool).await, 1);
+        assert_eq!(started_today(Some(side.id), &pool).await, 0);
+
+        trash::restore_from_trash(TrashKind::Todo, task.id, &pool).await.unwrap();
+        trash::restore_from_trash(TrashKind::Project, side.id, &pool).await.unwrap();
+        assert_eq!(session_count(task.id, &pool).await, 1);
+        assert_eq!(get_sessions_by_project(inbox.id, &pool).await.unwrap().len(), 2);
+        assert_eq!(get_sessions_by_project(side.id, &pool).await.unwrap().len(), 1);
+        assert_eq!(started_today(None, &pool).await, 3);
+    }
+}
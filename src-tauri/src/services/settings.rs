@@ -0,0 +1,36 @@
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::sqlite::SqlitePool;
+use crate::error::MissionControlError;
+
+// Read a setting, falling back to `T::default()` when it was never saved
+pub async fn get_setting<T: DeserializeOwned + Default>(key: &str, pool: &SqlitePool) -> Result<T, MissionControlError> {
+    let value: Option<String> = sqlx::query_scalar("SELECT value FROM settings WHERE key = ?")
+        .bind(key)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read setting", e))?;
+
+    match value {
+        Some(value) => serde_json::from_str(&value).map_err(|e| {
+            MissionControlError::Validation(format!("Stored setting '{}' is invalid: {}", key, e))
+        }),
+        None => Ok(T::default()),
+    }
+}
+
+pub async fn set_setting<T: Serialize>(key: &str, value: &T, pool: &SqlitePool) -> Result<(), MissionControlError> {
+    let value = serde_json::to_string(value).map_err(|e| {
+        MissionControlError::Validation(format!("Failed to serialize setting '{}': {}", key, e))
+    })?;
+
+    sqlx::query(
+        "INSERT INTO settings (key, value) VALUES (?, ?) ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP"
+    )
+    .bind(key)
+    .bind(value)
+    .execute(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to save setting", e))?;
+
+    Ok(())
+}
@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use chrono::{Datelike, NaiveDateTime, Timelike, Utc};
+use sqlx::sqlite::SqlitePool;
+use crate::error::MissionControlError;
+use crate::services::settings;
+use crate::types::{SnapshotInfo, SnapshotPolicy};
+
+pub const POLICY_KEY: &str = "snapshot_policy";
+
+const FILE_PREFIX: &str = "snapshot-";
+const FILE_EXTENSION: &str = "db";
+const ID_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+// Snapshots live in a `backups/` directory next to the database file
+pub fn snapshot_dir(database_path: &Path) -> PathBuf {
+    database_path
+        .parent()
+        .map(|parent| parent.join("backups"))
+        .unwrap_or_else(|| PathBuf::from("backups"))
+}
+
+pub fn snapshot_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}{}.{}", FILE_PREFIX, id, FILE_EXTENSION))
+}
+
+// Snapshot IDs are UTC timestamps; this also rejects anything that could escape the directory
+pub fn parse_snapshot_id(id: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(id, ID_FORMAT).ok()
+}
+
+pub async fn get_policy(pool: &SqlitePool) -> Result<SnapshotPolicy, MissionControlError> {
+    settings::get_setting(POLICY_KEY, pool).await
+}
+
+pub async fn set_policy(policy: SnapshotPolicy, pool: &SqlitePool) -> Result<SnapshotPolicy, MissionControlError> {
+    if policy.keep_hourly + policy.keep_daily + policy.keep_weekly == 0 {
+        return Err(MissionControlError::Validation("Retention policy must keep at least one snapshot".to_string()));
+    }
+    settings::set_setting(POLICY_KEY, &policy, pool).await?;
+    Ok(policy)
+}
+
+// All snapshots in `dir`, newest first
+pub async fn list_snapshots(dir: &Path) -> Result<Vec<SnapshotInfo>, MissionControlError> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(MissionControlError::from_io("Failed to read backups directory", e)),
+    };
+
+    let mut snapshots = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| MissionControlError::from_io("Failed to read backups directory", e))?
+    {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let id = match file_name
+            .strip_prefix(FILE_PREFIX)
+            .and_then(|rest| rest.strip_suffix(&format!(".{}", FILE_EXTENSION)))
+        {
+            Some(id) => id.to_string(),
+            None => continue,
+        };
+        let created_at = match parse_snapshot_id(&id) {
+            Some(created_at) => created_at,
+            None => continue,
+        };
+        let size_bytes = entry.metadata().await.map(|metadata| metadata.len()).unwrap_or(0);
+
+        snapshots.push(SnapshotInfo {
+            id,
+            created_at: created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            size_bytes,
+        });
+    }
+
+    snapshots.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(snapshots)
+}
+
+// Write a consistent copy of the live database with VACUUM INTO
+pub async fn take_snapshot(pool: &SqlitePool, dir: &Path) -> Result<SnapshotInfo, MissionControlError> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| MissionControlError::from_io("Failed to create backups directory", e))?;
+
+    let mut now = Utc::now().naive_utc();
+    // Two snapshots in the same second would share an ID; move to the next free second
+    while tokio::fs::try_exists(snapshot_path(dir, &now.format(ID_FORMAT).to_string()))
+        .await
+        .unwrap_or(false)
+    {
+        now += chrono::Duration::seconds(1);
+    }
+    let id = now.format(ID_FORMAT).to_string();
+    let path = snapshot_path(dir, &id);
+
+    sqlx::query("VACUUM INTO ?")
+        .bind(path.to_string_lossy().to_string())
+        .execute(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to write database snapshot", e))?;
+
+    let size_bytes = tokio::fs::metadata(&path).await.map(|metadata| metadata.len()).unwrap_or(0);
+    println!("Database snapshot written to {:?}", path);
+
+    Ok(SnapshotInfo {
+        id,
+        created_at: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+        size_bytes,
+    })
+}
+
+// Grandfather-father-son retention: keep the newest snapshot of each of the last
+// `keep_hourly` hours, `keep_daily` days and `keep_weekly` ISO weeks that have one.
+// The newest snapshot overall is always kept. `snapshots` must be newest first.
+pub fn retained_ids(snapshots: &[SnapshotInfo], policy: &SnapshotPolicy) -> HashSet<String> {
+    let timestamps: Vec<(&str, NaiveDateTime)> = snapshots
+        .iter()
+        .filter_map(|snapshot| parse_snapshot_id(&snapshot.id).map(|at| (snapshot.id.as_str(), at)))
+        .collect();
+
+    let mut keep: HashSet<String> = HashSet::new();
+    if let Some((newest, _)) = timestamps.first() {
+        keep.insert(newest.to_string());
+    }
+
+    let mut keep_per_bucket = |limit: u32, bucket: &dyn Fn(&NaiveDateTime) -> (i32, u32, u32, u32)| {
+        let mut seen = HashSet::new();
+        for (id, at) in &timestamps {
+            if seen.len() >= limit as usize {
+                break;
+            }
+            if seen.insert(bucket(at)) {
+                keep.insert(id.to_string());
+            }
+        }
+    };
+
+    keep_per_bucket(policy.keep_hourly, &|at| (at.year(), at.month(), at.day(), at.hour()));
+    keep_per_bucket(policy.keep_daily, &|at| (at.year(), at.month(), at.day(), 0));
+    keep_per_bucket(policy.keep_weekly, &|at| (at.iso_week().year(), at.iso_week().week(), 0, 0));
+
+    keep
+}
+
+// Delete snapshots the policy no longer retains; returns how many were removed
+pub async fn prune_snapshots(dir: &Path, policy: &SnapshotPolicy) -> Result<usize, MissionControlError> {
+    let snapshots = list_snapshots(dir).await?;
+    let keep = retained_ids(&snapshots, policy);
+
+    let mut removed = 0;
+    for snapshot in snapshots.iter().filter(|snapshot| !keep.contains(&snapshot.id)) {
+        tokio::fs::remove_file(snapshot_path(dir, &snapshot.id))
+            .await
+            .map_err(|e| MissionControlError::from_io("Failed to remove old snapshot", e))?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+// Take a snapshot if the newest one is older than the policy interval, then prune
+pub async fn run_scheduled_snapshot(pool: &SqlitePool, dir: &Path) -> Result<Option<SnapshotInfo>, MissionControlError> {
+    let policy = get_policy(pool).await?;
+    if policy.interval_minutes == 0 {
+        return Ok(None);
+    }
+
+    let newest = list_snapshots(dir)
+        .await?
+        .first()
+        .and_then(|snapshot| parse_snapshot_id(&snapshot.id));
+    let due = match newest {
+        Some(newest) => Utc::now().naive_utc() - newest >= chrono::Duration::minutes(i64::from(policy.interval_minutes)),
+        None => true,
+    };
+    if !due {
+        return Ok(None);
+    }
+
+    let snapshot = take_snapshot(pool, dir).await?;
+    prune_snapshots(dir, &policy).await?;
+    Ok(Some(snapshot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Snapshots at the given "YYYY-MM-DD HH:MM:SS" times, newest first
+    fn snapshots(times: &[&str]) -> Vec<SnapshotInfo> {
+        let mut snapshots: Vec<SnapshotInfo> = times
+            .iter()
+            .map(|time| {
+                let at = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap();
+                SnapshotInfo { id: at.format(ID_FORMAT).to_string(), created_at: time.to_string(), size_bytes: 0 }
+            })
+            .collect();
+        snapshots.sort_by(|a, b| b.id.cmp(&a.id));
+        snapshots
+    }
+
+    fn policy(keep_hourly: u32, keep_daily: u32, keep_weekly: u32) -> SnapshotPolicy {
+        SnapshotPolicy { interval_minutes: 60, keep_hourly, keep_daily, keep_weekly }
+    }
+
+    // The kept snapshots as times, newest first
+    fn kept(snapshots: &[SnapshotInfo], policy: &SnapshotPolicy) -> Vec<String> {
+        let keep = retained_ids(snapshots, policy);
+        snapshots
+            .iter()
+            .filter(|snapshot| keep.contains(&snapshot.id))
+            .map(|snapshot| snapshot.created_at.clone())
+            .collect()
+    }
+
+    #[test]
+    fn buckets_split_on_the_hour_and_at_midnight() {
+        let all = snapshots(&[
+            "2024-03-05 00:00:00",
+            "2024-03-04 23:59:59",
+            "2024-03-04 23:10:00",
+            "2024-03-04 22:59:59",
+        ]);
+
+        // The newest of each hour, and only as many hours as asked for
+        assert_eq!(kept(&all, &policy(2, 0, 0)), vec!["2024-03-05 00:00:00", "2024-03-04 23:59:59"]);
+        assert_eq!(
+            kept(&all, &policy(3, 0, 0)),
+            vec!["2024-03-05 00:00:00", "2024-03-04 23:59:59", "2024-03-04 22:59:59"]
+        );
+        // One second apart across midnight is two days
+        assert_eq!(kept(&all, &policy(0, 2, 0)), vec!["2024-03-05 00:00:00", "2024-03-04 23:59:59"]);
+    }
+
+    #[test]
+    fn weeks_follow_the_iso_year() {
+        // 2020-12-28 to 2021-01-03 is week 53 of 2020; 2021-01-04 starts week 1 of 2021
+        let all = snapshots(&[
+            "2021-01-04 09:00:00",
+            "2021-01-01 09:00:00",
+            "2020-12-31 09:00:00",
+            "2020-12-28 09:00:00",
+            "2020-12-27 09:00:00",
+        ]);
+        assert_eq!(kept(&all, &policy(0, 0, 2)), vec!["2021-01-04 09:00:00", "2021-01-01 09:00:00"]);
+        assert_eq!(
+            kept(&all, &policy(0, 0, 3)),
+            vec!["2021-01-04 09:00:00", "2021-01-01 09:00:00", "2020-12-27 09:00:00"]
+        );
+
+        // 2019-12-30 already belongs to week 1 of 2020
+        let all = snapshots(&["2020-01-02 09:00:00", "2019-12-30 09:00:00", "2019-12-29 09:00:00"]);
+        assert_eq!(kept(&all, &policy(0, 0, 2)), vec!["2020-01-02 09:00:00", "2019-12-29 09:00:00"]);
+    }
+
+    #[test]
+    fn the_newest_snapshot_is_always_kept() {
+        let all = snapshots(&["2024-03-04 12:00:00", "2024-03-04 11:00:00", "2024-02-01 08:00:00"]);
+        assert_eq!(kept(&all, &policy(0, 0, 0)), vec!["2024-03-04 12:00:00"]);
+        assert!(retained_ids(&[], &policy(24, 7, 4)).is_empty());
+
+        // A limit of 0 turns its bucket off; the others still apply
+        assert_eq!(kept(&all, &policy(0, 0, 2)), vec!["2024-03-04 12:00:00", "2024-02-01 08:00:00"]);
+        assert_eq!(kept(&all, &policy(1, 0, 0)), vec!["2024-03-04 12:00:00"]);
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::HashSet;
+use sqlx::{sqlite::{SqlitePool, SqliteRow}, Row, SqliteConnection};
+use crate::error::MissionControlError;
+use crate::services::{projects, todos};
+use crate::types::{Tag, Todo};
+
+const MAX_TAG_LENGTH: usize = 50;
+
+const TAG_QUERY: &str = r#"
+    SELECT tags.id, tags.name, tags.created_at, COUNT(todos.id) AS todo_count
+    FROM tags
+    LEFT JOIN todo_tags ON todo_tags.tag_id = tags.id
+    LEFT JOIN todos ON todos.id = todo_tags.todo_id AND todos.deleted_at IS NULL
+"#;
+
+fn tag_from_row(row: &SqliteRow) -> Tag {
+    Tag {
+        id: row.get("id"),
+        name: row.get("name"),
+        created_at: row.get("created_at"),
+        todo_count: row.get("todo_count"),
+    }
+}
+
+// Tag names are trimmed, and compared without regard to case
+fn normalize_name(name: &str) -> Result<String, MissionControlError> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        return Err(MissionControlError::Validation("Tag name cannot be empty".to_string()));
+    }
+    if name.chars().count() > MAX_TAG_LENGTH {
+        return Err(MissionControlError::Validation(format!(
+            "Tag names can be at most {} characters long",
+            MAX_TAG_LENGTH
+        )));
+    }
+    Ok(name)
+}
+
+async fn get_tag(id: i64, conn: &mut SqliteConnection) -> Result<Tag, MissionControlError> {
+    let row = sqlx::query(&format!("{} WHERE tags.id = ? GROUP BY tags.id", TAG_QUERY))
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to fetch tag", e))?
+        .ok_or_else(|| MissionControlError::NotFound(format!("Tag {} not found", id)))?;
+    Ok(tag_from_row(&row))
+}
+
+async fn ensure_todo_exists(todo_id: i64, conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    let exists: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM todos WHERE id = ? AND deleted_at IS NULL")
+        .bind(todo_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to look up todo", e))?;
+    if exists == 0 {
+        return Err(MissionControlError::NotFound(format!("Todo {} not found", todo_id)));
+    }
+    Ok(())
+}
+
+// Tag a todo, creating the tag on first use. Tagging twice is harmless.
+pub async fn attach_tag(todo_id: i64, name: &str, conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    let name = normalize_name(name)?;
+
+    sqlx::query("INSERT INTO tags (name) VALUES (?) ON CONFLICT(name) DO NOTHING")
+        .bind(&name)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to create tag", e))?;
+
+    sqlx::query("INSERT OR IGNORE INTO todo_tags (todo_id, tag_id) SELECT ?, id FROM tags WHERE name = ?")
+        .bind(todo_id)
+        .bind(&name)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to tag todo", e))?;
+
+    Ok(())
+}
+
+pub async fn list_tags(pool: &SqlitePool) -> Result<Vec<Tag>, MissionControlError> {
+    let rows = sqlx::query(&format!("{} GROUP BY tags.id ORDER BY tags.name COLLATE NOCASE ASC", TAG_QUERY))
+        .fetch_all(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to fetch tags", e))?;
+
+    Ok(rows.iter().map(tag_from_row).collect())
+}
+
+pub async fn add_tag(todo_id: i64, name: &str, pool: &SqlitePool) -> Result<Todo, MissionControlError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    ensure_todo_exists(todo_id, &mut tx).await?;
+    projects::ensure_todos_not_archived(&[todo_id], &mut *tx).await?;
+    attach_tag(todo_id, name, &mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit tag", e))?;
+
+    todos::get_todo_by_id(todo_id, pool).await
+}
+
+// Untag a todo; the tag itself stays, even when nothing uses it any more
+pub async fn remove_tag(todo_id: i64, name: &str, pool: &SqlitePool) -> Result<Todo, MissionControlError> {
+    let name = normalize_name(name)?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    ensure_todo_exists(todo_id, &mut tx).await?;
+    projects::ensure_todos_not_archived(&[todo_id], &mut *tx).await?;
+
+    let removed = sqlx::query("DELETE FROM todo_tags WHERE todo_id = ? AND tag_id = (SELECT id FROM tags WHERE name = ?)")
+        .bind(todo_id)
+        .bind(&name)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to untag todo", e))?;
+    if removed.rows_affected() == 0 {
+        return Err(MissionControlError::NotFound(format!("Todo {} is not tagged '{}'", todo_id, name)));
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit untagging", e))?;
+
+    todos::get_todo_by_id(todo_id, pool).await
+}
+
+// Renaming to another tag's name is refused; merge the tags instead
+pub async fn rename_tag(id: i64, name: &str, pool: &SqlitePool) -> Result<Tag, MissionControlError> {
+    let name = normalize_name(name)?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    get_tag(id, &mut tx).await?;
+
+    let taken: Option<String> = sqlx::query_scalar("SELECT name FROM tags WHERE name = ? AND id != ?")
+        .bind(&name)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to check tag name", e))?;
+    if let Some(existing) = taken {
+        return Err(MissionControlError::Conflict(format!(
+            "Tag '{}' already exists; merge the tags instead",
+            existing
+        )));
+    }
+
+    sqlx::query("UPDATE tags SET name = ? WHERE id = ?")
+        .bind(&name)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to rename tag", e))?;
+
+    let tag = get_tag(id, &mut tx).await?;
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit tag rename", e))?;
+
+    Ok(tag)
+}
+
+// Move every todo tagged with one of `source_ids` over to `target_id` and delete the
+// source tags, all in one transaction
+pub async fn merge_tags(source_ids: &[i64], target_id: i64, pool: &SqlitePool) -> Result<Tag, MissionControlError> {
+    let sources: Vec<i64> = source_ids
+        .iter()
+        .copied()
+        .filter(|id| *id != target_id)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    if sources.is_empty() {
+        return Err(MissionControlError::Validation("No tags to merge".to_string()));
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    get_tag(target_id, &mut tx).await?;
+    for id in &sources {
+        get_tag(*id, &mut tx).await?;
+    }
+
+    let mut relink = sqlx::QueryBuilder::new("INSERT OR IGNORE INTO todo_tags (todo_id, tag_id) SELECT todo_id, ");
+    relink.push_bind(target_id);
+    relink.push(" FROM todo_tags WHERE tag_id IN (");
+    let mut separated = relink.separated(", ");
+    for id in &sources {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(")");
+    relink
+        .build()
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to merge tags", e))?;
+
+    for query in [
+        "DELETE FROM todo_tags WHERE tag_id IN (",
+        "DELETE FROM tags WHERE id IN (",
+    ] {
+        let mut delete = sqlx::QueryBuilder::new(query);
+        let mut separated = delete.separated(", ");
+        for id in &sources {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+        delete
+            .build()
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to remove merged tags", e))?;
+    }
+
+    let tag = get_tag(target_id, &mut tx).await?;
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit tag merge", e))?;
+
+    Ok(tag)
+}
+
+pub async fn delete_tag(id: i64, pool: &SqlitePool) -> Result<(), MissionControlError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    get_tag(id, &mut tx).await?;
+    for query in ["DELETE FROM todo_tags WHERE tag_id = ?", "DELETE FROM tags WHERE id = ?"] {
+        sqlx::query(query)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to delete tag", e))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit tag deletion", e))?;
+
+    Ok(())
+}
+
+// Todos tagged `name`, in every project or just `project_id`. Each keeps the depth
+// and rolled-up time it has in its own project's list; projects come in ID order.
+pub async fn get_todos_by_tag(name: &str, project_id: Option<i64>, pool: &SqlitePool) -> Result<Vec<Todo>, MissionControlError> {
+    let name = normalize_name(name)?;
+
+    let tagged: Vec<(i64, i64)> = sqlx::query_as(
+        r#"
+        SELECT todos.id, todos.project_id
+        FROM todos
+        JOIN todo_tags ON todo_tags.todo_id = todos.id
+        JOIN tags ON tags.id = todo_tags.tag_id
+        WHERE tags.name = ?1 AND todos.deleted_at IS NULL AND (?2 IS NULL OR todos.project_id = ?2)
+        ORDER BY todos.project_id ASC
+        "#,
+    )
+    .bind(&name)
+    .bind(project_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to fetch tagged todos", e))?;
+
+    let ids: HashSet<i64> = tagged.iter().map(|(id, _)| *id).collect();
+    let mut project_ids: Vec<i64> = tagged.iter().map(|(_, project_id)| *project_id).collect();
+    project_ids.dedup();
+
+    let mut result = Vec::with_capacity(ids.len());
+    for project_id in project_ids {
+        result.extend(
+            todos::get_todos_by_project(project_id, pool)
+                .await?
+                .into_iter()
+                .filter(|todo| ids.contains(&todo.id)),
+        );
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{exec, migrated_pool, project, todo};
+
+    // (todo text, tag name) for every link, in a stable order
+    async fn links(pool: &SqlitePool) -> Vec<(String, String)> {
+        sqlx::query_as(
+            "SELECT todos.text, tags.name FROM todo_tags JOIN todos ON todos.id = todo_tags.todo_id JOIN tags ON tags.id = todo_tags.tag_id ORDER BY todos.id, tags.name"
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn tag_id(name: &str, pool: &SqlitePool) -> i64 {
+        sqlx::query_scalar("SELECT id FROM tags WHERE name = ?").bind(name).fetch_one(pool).await.unwrap()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected.iter().map(|(todo, tag)| (todo.to_string(), tag.to_string())).collect()
+    }
+
+    #[tokio::test]
+    async fn tag_errors_have_the_right_kind() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let task = todo("Task", inbox.id, None, &pool).await;
+        add_tag(task.id, "home", &pool).await.unwrap();
+        add_tag(task.id, "work", &pool).await.unwrap();
+        let tags = list_tags(&pool).await.unwrap();
+        let home = tags.iter().find(|tag| tag.name == "home").unwrap();
+
+        assert_eq!(add_tag(task.id, "   ", &pool).await.unwrap_err().kind(), "validation");
+        assert_eq!(add_tag(task.id, &"x".repeat(MAX_TAG_LENGTH + 1), &pool).await.unwrap_err().kind(), "validation");
+        assert_eq!(merge_tags(&[], home.id, &pool).await.unwrap_err().kind(), "validation");
+
+        assert_eq!(add_tag(42, "home", &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!(remove_tag(task.id, "errands", &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!(rename_tag(42, "garden", &pool).await.unwrap_err().kind(), "not_found");
+
+        assert_eq!(rename_tag(home.id, "Work", &pool).await.unwrap_err().kind(), "conflict");
+    }
+
+    #[tokio::test]
+    async fn merging_moves_links_without_duplicates() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let both = todo("Both", inbox.id, None, &pool).await;
+        let old_only = todo("Old only", inbox.id, None, &pool).await;
+        let other = todo("Other", inbox.id, None, &pool).await;
+        add_tag(both.id, "work", &pool).await.unwrap();
+        add_tag(both.id, "job", &pool).await.unwrap();
+        add_tag(old_only.id, "office", &pool).await.unwrap();
+        add_tag(other.id, "home", &pool).await.unwrap();
+
+        let (work, job, office) = (tag_id("work", &pool).await, tag_id("job", &pool).await, tag_id("office", &pool).await);
+        let merged = merge_tags(&[job, office, job, work], work, &pool).await.unwrap();
+        assert_eq!((merged.name.as_str(), merged.todo_count), ("work", 2));
+
+        assert_eq!(links(&pool).await, pairs(&[("Both", "work"), ("Old only", "work"), ("Other", "home")]));
+        let names: Vec<String> = list_tags(&pool).await.unwrap().into_iter().map(|tag| tag.name).collect();
+        assert_eq!(names, vec!["home", "work"]);
+    }
+
+    #[tokio::test]
+    async fn a_failed_merge_changes_nothing() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let task = todo("Task", inbox.id, None, &pool).await;
+        let other = todo("Other", inbox.id, None, &pool).await;
+        add_tag(task.id, "job", &pool).await.unwrap();
+        add_tag(task.id, "chores", &pool).await.unwrap();
+        add_tag(other.id, "work", &pool).await.unwrap();
+        let (job, work) = (tag_id("job", &pool).await, tag_id("work", &pool).await);
+        let before = (links(&pool).await, list_tags(&pool).await.unwrap().len());
+
+        // A missing tag is noticed before anything moves
+        assert_eq!(merge_tags(&[job, 42], work, &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!((links(&pool).await, list_tags(&pool).await.unwrap().len()), before);
+
+        // A failure after the links were moved rolls the move back too
+        exec(
+            "CREATE TRIGGER refuse_tag_delete BEFORE DELETE ON tags BEGIN SELECT RAISE(ABORT, 'refused'); END;",
+            &pool,
+        )
+        .await;
+        assert!(merge_tags(&[job], work, &pool).await.is_err());
+        assert_eq!((links(&pool).await, list_tags(&pool).await.unwrap().len()), before);
+    }
+
+    #[tokio::test]
+    async fn untagging_keeps_the_tag_and_respects_the_archive() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+        let task = todo("Task", inbox.id, None, &pool).await;
+        let filed = todo("Filed", side.id, None, &pool).await;
+        add_tag(task.id, "home", &pool).await.unwrap();
+        add_tag(filed.id, "home", &pool).await.unwrap();
+
+        assert!(remove_tag(task.id, " HOME ", &pool).await.unwrap().tags.is_empty());
+        assert_eq!(list_tags(&pool).await.unwrap()[0].todo_count, 1);
+
+        projects::archive_project(side.id, &pool).await.unwrap();
+        assert_eq!(remove_tag(filed.id, "home", &pool).await.unwrap_err().kind(), "conflict");
+        assert_eq!(remove_tag(42, "home", &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!(links(&pool).await, pairs(&[("Filed", "home")]));
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use chrono::Local;
+use sqlx::{sqlite::{SqlitePool, SqliteRow}, Row};
+use crate::error::MissionControlError;
+use crate::services::ordering::POSITION_GAP;
+use crate::services::{journal, projects};
+use crate::types::{CreateProject, Project, ProjectTemplate, SaveTemplateOptions};
+
+// Filled in with today's local date (YYYY-MM-DD) unless a value is given for it
+const DATE_PLACEHOLDER: &str = "date";
+
+const TEMPLATE_COLUMNS: &str = "id, name, description, color, icon, elements IS NOT NULL AS has_whiteboard, created_at";
+
+// The `{{name}}` placeholders in `text` as (byte range, name). Names are letters,
+// digits, `_` and `-`, optionally padded with spaces; anything else in braces is
+// left as written.
+fn find_placeholders(text: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(start) = text[from..].find("{{").map(|offset| from + offset) {
+        let end = match text[start + 2..].find("}}") {
+            Some(offset) => start + 2 + offset,
+            None => break,
+        };
+        let name = text[start + 2..end].trim();
+        if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+            found.push((start..end + 2, name));
+            from = end + 2;
+        } else {
+            from = start + 1;
+        }
+    }
+    found
+}
+
+fn fill(text: &str, values: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (range, name) in find_placeholders(text) {
+        if let Some(value) = values.get(name) {
+            out.push_str(&text[copied..range.start]);
+            out.push_str(value);
+            copied = range.end;
+        }
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+// Placeholders used across `texts` that have to be supplied, in order of first use
+fn required_placeholders<'a>(texts: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for text in texts {
+        for (_, name) in find_placeholders(text) {
+            if name != DATE_PLACEHOLDER && !names.iter().any(|known| known == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+fn template_from_row(row: &SqliteRow, texts: &[String]) -> ProjectTemplate {
+    let description: Option<String> = row.get("description");
+    ProjectTemplate {
+        id: row.get("id"),
+        name: row.get("name"),
+        placeholders: required_placeholders(description.as_deref().into_iter().chain(texts.iter().map(String::as_str))),
+        description,
+        color: row.get("color"),
+        icon: row.get("icon"),
+        todo_count: texts.len() as i64,
+        has_whiteboard: row.get("has_whiteboard"),
+        created_at: row.get("created_at"),
+    }
+}
+
+pub async fn list_templates(pool: &SqlitePool) -> Result<Vec<ProjectTemplate>, MissionControlError> {
+    let rows = sqlx::query(&format!("SELECT {} FROM project_templates ORDER BY name COLLATE NOCASE ASC", TEMPLATE_COLUMNS))
+        .fetch_all(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read templates", e))?;
+
+    let todo_rows: Vec<(i64, String)> = sqlx::query_as("SELECT template_id, text FROM template_todos ORDER BY template_id, position")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read template todos", e))?;
+    let mut texts: HashMap<i64, Vec<String>> = HashMap::new();
+    for (template_id, text) in todo_rows {
+        texts.entry(template_id).or_default().push(text);
+    }
+
+    Ok(rows
+        .iter()
+        .map(|row| template_from_row(row, texts.get(&row.get("id")).map(Vec::as_slice).unwrap_or_default()))
+        .collect())
+}
+
+pub async fn get_template(id: i64, pool: &SqlitePool) -> Result<ProjectTemplate, MissionControlError> {
+    let row = sqlx::query(&format!("SELECT {} FROM project_templates WHERE id = ?", TEMPLATE_COLUMNS))
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read template", e))?
+        .ok_or_else(|| MissionControlError::NotFound(format!("Template {} not found", id)))?;
+
+    let texts: Vec<String> = sqlx::query_scalar("SELECT text FROM template_todos WHERE template_id = ? ORDER BY position")
+        .bind(id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read template todos", e))?;
+
+    Ok(template_from_row(&row, &texts))
+}
+
+// Keep a project's todo list, and optionally its whiteboard, as a template. Todos are
+// stored open whether or not they were done; due dates, tags and sessions are not kept.
+pub async fn save_project_as_template(
+    project_id: i64,
+    options: &SaveTemplateOptions,
+    pool: &SqlitePool,
+) -> Result<ProjectTemplate, MissionControlError> {
+    let project = projects::get_project_by_id(project_id, pool).await?;
+    let name = options.name.as_deref().unwrap_or(&project.name).trim().to_string();
+    if name.is_empty() {
+        return Err(MissionControlError::Validation("Template name cannot be empty".to_string()));
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    let scene: Option<(String, String)> = if options.include_whiteboard {
+        sqlx::query_as("SELECT elements, app_state FROM excalidraw_data WHERE project_id = ? AND deleted_at IS NULL ORDER BY id DESC LIMIT 1")
+            .bind(project_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to read whiteboard", e))?
+    } else {
+        None
+    };
+    let (elements, app_state) = scene.unzip();
+
+    let template_id: i64 = sqlx::query_scalar(
+        "INSERT INTO project_templates (name, description, color, icon, elements, app_state) VALUES (?, ?, ?, ?, ?, ?) RETURNING id"
+    )
+    .bind(&name)
+    .bind(&project.description)
+    .bind(&project.color)
+    .bind(&project.icon)
+    .bind(elements)
+    .bind(app_state)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match MissionControlError::from_sqlx("Failed to save template", e) {
+        MissionControlError::Conflict(_) => MissionControlError::Conflict(format!("A template named '{}' already exists", name)),
+        other => other,
+    })?;
+
+    let todos: Vec<(i64, Option<i64>, String, i64)> = sqlx::query_as(
+        "SELECT id, parent_id, text, time FROM todos WHERE project_id = ? AND deleted_at IS NULL ORDER BY position ASC, id ASC"
+    )
+    .bind(project_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to read todos for template", e))?;
+
+    // Parents are linked once every todo has its template row
+    let mut copies: HashMap<i64, i64> = HashMap::new();
+    for (index, (id, _, text, time)) in todos.iter().enumerate() {
+        let copy_id: i64 = sqlx::query_scalar(
+            "INSERT INTO template_todos (template_id, text, time, position) VALUES (?, ?, ?, ?) RETURNING id"
+        )
+        .bind(template_id)
+        .bind(text)
+        .bind(time)
+        .bind(index as i64)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to save template todo", e))?;
+        copies.insert(*id, copy_id);
+    }
+    for (id, parent_id, _, _) in &todos {
+        if let Some(parent_copy) = parent_id.and_then(|parent_id| copies.get(&parent_id)) {
+            sqlx::query("UPDATE template_todos SET parent_id = ? WHERE id = ?")
+                .bind(parent_copy)
+                .bind(copies[id])
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| MissionControlError::from_sqlx("Failed to save template subtasks", e))?;
+        }
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit template", e))?;
+
+    get_template(template_id, pool).await
+}
+
+// Start a project called `name` from a template, as one undoable operation.
+// `values` fills the template's placeholders; each must be given, except `date`.
+pub async fn create_project_from_template(
+    template_id: i64,
+    name: String,
+    values: HashMap<String, String>,
+    pool: &SqlitePool,
+) -> Result<Project, MissionControlError> {
+    let template = sqlx::query("SELECT description, color, icon, elements, app_state FROM project_templates WHERE id = ?")
+        .bind(template_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to read template", e))?
+        .ok_or_else(|| MissionControlError::NotFound(format!("Template {} not found", template_id)))?;
+
+    let todos: Vec<(i64, Option<i64>, String, i64)> =
+        sqlx::query_as("SELECT id, parent_id, text, time FROM template_todos WHERE template_id = ? ORDER BY position")
+            .bind(template_id)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to read template todos", e))?;
+
+    let description: Option<String> = template.get("description");
+    let mut values = values;
+    values
+        .entry(DATE_PLACEHOLDER.to_string())
+        .or_insert_with(|| Local::now().date_naive().format("%Y-%m-%d").to_string());
+    let missing: Vec<String> = required_placeholders(description.as_deref().into_iter().chain(todos.iter().map(|todo| todo.2.as_str())))
+        .into_iter()
+        .filter(|name| !values.contains_key(name))
+        .map(|name| format!("{{{{{}}}}}", name))
+        .collect();
+    if !missing.is_empty() {
+        return Err(MissionControlError::Validation(format!("Fill in {} to use this template", missing.join(", "))));
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    journal::begin("Create project from template", &mut tx).await?;
+    let project = projects::create_new_project(
+        CreateProject { name, description: description.as_deref().map(|description| fill(description, &values)) },
+        &mut *tx,
+    )
+    .await?;
+
+    sqlx::query("UPDATE projects SET color = ?, icon = ? WHERE id = ?")
+        .bind(template.get::<Option<String>, _>("color"))
+        .bind(template.get::<Option<String>, _>("icon"))
+        .bind(project.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to style project", e))?;
+
+    let mut copies: HashMap<i64, i64> = HashMap::new();
+    for (index, (id, _, text, time)) in todos.iter().enumerate() {
+        let todo_id: i64 = sqlx::query_scalar(
+            "INSERT INTO todos (text, time, project_id, position) VALUES (?, ?, ?, ?) RETURNING id"
+        )
+        .bind(fill(text, &values))
+        .bind(time)
+        .bind(project.id)
+        .bind((index + 1) as f64 * POSITION_GAP)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to create todo from template", e))?;
+        copies.insert(*id, todo_id);
+    }
+    for (id, parent_id, _, _) in &todos {
+        if let Some(parent_copy) = parent_id.and_then(|parent_id| copies.get(&parent_id)) {
+            sqlx::query("UPDATE todos SET parent_id = ? WHERE id = ?")
+                .bind(parent_copy)
+                .bind(copies[id])
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| MissionControlError::from_sqlx("Failed to nest todo from template", e))?;
+        }
+    }
+
+    let elements: Option<String> = template.get("elements");
+    if let Some(elements) = elements {
+        sqlx::query("INSERT INTO excalidraw_data (elements, app_state, project_id) VALUES (?, ?, ?)")
+            .bind(elements)
+            .bind(template.get::<Option<String>, _>("app_state").unwrap_or_else(|| "{}".to_string()))
+            .bind(project.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to create whiteboard from template", e))?;
+    }
+    journal::finish(&mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit project", e))?;
+
+    projects::get_project_by_id(project.id, pool).await
+}
+
+pub async fn delete_template(id: i64, pool: &SqlitePool) -> Result<(), MissionControlError> {
+    let result = sqlx::query("DELETE FROM project_templates WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to delete template", e))?;
+
+    if result.rows_affected() == 0 {
+        return Err(MissionControlError::NotFound(format!("Template {} not found", id)));
+    }
+    Ok(())
+}
@@ -1,68 +1,397 @@
-use sqlx::{sqlite::SqlitePool, Row};
-use crate::types::{Todo, UpdateTodo};
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use sqlx::{sqlite::{SqlitePool, SqliteRow}, Acquire, Row, Sqlite, SqliteConnection};
+use crate::error::MissionControlError;
+use crate::services::ordering::{self, POSITION_GAP};
+use crate::services::{journal, projects, recurring, trash};
+use crate::types::{SubtaskPolicy, Todo, UpdateTodo};
 
-pub async fn get_todos_by_project(project_id: i64, pool: &SqlitePool) -> Result<Vec<Todo>, String> {
-    let rows = sqlx::query("SELECT id, text, completed, time, created_at, project_id FROM todos WHERE project_id = ? ORDER BY created_at ASC, id ASC")
-        .bind(project_id)
-        .fetch_all(pool)
-        .await
-        .map_err(|e| {
-            let error_msg = format!("Failed to fetch todos: {}", e);
-            println!("{}", error_msg);
-            error_msg
-        })?;
+// Planned minutes for a todo when none is given, matching the column default
+pub const DEFAULT_TODO_MINUTES: i32 = 25;
+
+// Priorities run from P0 (most urgent) to P3; new todos are P2
+pub const DEFAULT_PRIORITY: u8 = 2;
+pub const LOWEST_PRIORITY: u8 = 3;
+
+const DUE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const TODO_COLUMNS: &str = "id, text, completed, time, created_at, project_id, parent_id, priority, due_at, recurrence, position";
+
+// The todo's tag names as a JSON array, for queries that list todos
+const TAGS_COLUMN: &str = "(SELECT json_group_array(name) FROM (\
+     SELECT tags.name FROM todo_tags JOIN tags ON tags.id = todo_tags.tag_id \
+     WHERE todo_tags.todo_id = todos.id ORDER BY tags.name COLLATE NOCASE\
+     )) AS tags";
+
+fn todo_from_row(row: &SqliteRow) -> Todo {
+    let completed_int: i64 = row.get("completed");
+    let time: i32 = row.get("time");
+    Todo {
+        id: row.get("id"),
+        text: row.get("text"),
+        completed: completed_int != 0, // Convert INTEGER to bool
+        time,
+        created_at: row.get("created_at"),
+        project_id: row.get("project_id"),
+        parent_id: row.get("parent_id"),
+        priority: u8::try_from(row.get::<i64, _>("priority")).unwrap_or(DEFAULT_PRIORITY),
+        due_at: row.get("due_at"),
+        recurrence: row
+            .get::<Option<String>, _>("recurrence")
+            .and_then(|rule| serde_json::from_str(&rule).ok()),
+        tags: row
+            .try_get::<Option<String>, _>("tags")
+            .ok()
+            .flatten()
+            .and_then(|tags| serde_json::from_str(&tags).ok())
+            .unwrap_or_default(),
+        position: row.get::<Option<f64>, _>("position").unwrap_or_default(),
+        depth: 0,
+        total_time: time,
+    }
+}
+
+// Due time in UTC from RFC 3339, the stored `YYYY-MM-DD HH:MM:SS` (already UTC), or
+// a bare `YYYY-MM-DD`, which means the end of that day in local time
+pub fn parse_due_at(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Some(at.naive_utc());
+    }
+    if let Ok(at) = NaiveDateTime::parse_from_str(value, DUE_FORMAT) {
+        return Some(at);
+    }
+    let end_of_day = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?.and_hms_opt(23, 59, 59)?;
+    Local
+        .from_local_datetime(&end_of_day)
+        .earliest()
+        .map(|at| at.naive_utc())
+}
+
+pub fn format_due_at(at: NaiveDateTime) -> String {
+    at.format(DUE_FORMAT).to_string()
+}
 
-    let todos: Vec<Todo> = rows
+// Incomplete todos without subtasks, in the order the focus timer works through
+// them: overdue todos first, then by priority, then in list order
+pub fn focus_order(todos: Vec<Todo>, now: NaiveDateTime) -> Vec<Todo> {
+    let parents = parent_ids(&todos);
+    let mut queue: Vec<Todo> = todos
         .into_iter()
-        .map(|row| {
-            let completed_int: i64 = row.get("completed");
-            Todo {
-                id: row.get("id"),
-                text: row.get("text"),
-                completed: completed_int != 0,
-                time: row.get("time"),
-                created_at: row.get("created_at"),
-                project_id: row.get("project_id"),
-            }
-        })
+        .filter(|todo| !todo.completed && !parents.contains(&todo.id))
         .collect();
-    Ok(todos)
+
+    let overdue = |todo: &Todo| {
+        todo.due_at
+            .as_deref()
+            .and_then(parse_due_at)
+            .is_some_and(|due_at| due_at < now)
+    };
+    // Stable, so todos that tie keep their list order
+    queue.sort_by_key(|todo| (!overdue(todo), todo.priority));
+    queue
+}
+
+// Order todos depth-first, each parent followed by its subtasks in list order,
+// and fill in `depth` and `total_time`. A parent's total is its own time plus its
+// children's totals; a todo whose parent isn't in the list is treated as top-level.
+pub fn arrange_tree(todos: Vec<Todo>) -> Vec<Todo> {
+    let ids: HashSet<i64> = todos.iter().map(|todo| todo.id).collect();
+    let mut children: HashMap<i64, Vec<usize>> = HashMap::new();
+    let mut roots = Vec::new();
+    for (index, todo) in todos.iter().enumerate() {
+        match todo.parent_id.filter(|parent_id| ids.contains(parent_id) && *parent_id != todo.id) {
+            Some(parent_id) => children.entry(parent_id).or_default().push(index),
+            None => roots.push(index),
+        }
+    }
+
+    fn visit(
+        index: usize,
+        depth: u32,
+        todos: &[Todo],
+        children: &HashMap<i64, Vec<usize>>,
+        visited: &mut HashSet<usize>,
+        out: &mut Vec<Todo>,
+    ) -> i32 {
+        if !visited.insert(index) {
+            return 0;
+        }
+        let position = out.len();
+        let mut todo = todos[index].clone();
+        todo.depth = depth;
+        out.push(todo);
+
+        let total = todos[index].time
+            + children
+                .get(&todos[index].id)
+                .map(|child_indexes| {
+                    child_indexes
+                        .iter()
+                        .map(|&child| visit(child, depth + 1, todos, children, visited, out))
+                        .sum::<i32>()
+                })
+                .unwrap_or_default();
+        out[position].total_time = total;
+        total
+    }
+
+    let mut out = Vec::with_capacity(todos.len());
+    let mut visited = HashSet::new();
+    for index in roots {
+        visit(index, 0, &todos, &children, &mut visited, &mut out);
+    }
+    out
+}
+
+// IDs of todos that have subtasks
+pub fn parent_ids(todos: &[Todo]) -> HashSet<i64> {
+    todos.iter().filter_map(|todo| todo.parent_id).collect()
+}
+
+// All live subtasks below `ids` that aren't listed in `ids` themselves, in list order
+pub async fn descendant_ids(ids: &[i64], conn: &mut SqliteConnection) -> Result<Vec<i64>, MissionControlError> {
+    let mut query_builder = sqlx::QueryBuilder::new(
+        "WITH RECURSIVE descendants(id) AS (SELECT id FROM todos WHERE deleted_at IS NULL AND parent_id IN ("
+    );
+    let mut separated = query_builder.separated(", ");
+    for id in ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(
+        ") UNION SELECT t.id FROM todos t JOIN descendants d ON t.parent_id = d.id WHERE t.deleted_at IS NULL) \
+         SELECT t.id FROM todos t JOIN descendants d ON d.id = t.id ORDER BY t.position ASC, t.id ASC",
+    );
+
+    let listed: HashSet<i64> = ids.iter().copied().collect();
+    let descendants: Vec<i64> = query_builder
+        .build_query_scalar()
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to look up subtasks", e))?;
+    Ok(descendants.into_iter().filter(|id| !listed.contains(id)).collect())
+}
+
+pub async fn get_todos_by_project(project_id: i64, pool: &SqlitePool) -> Result<Vec<Todo>, MissionControlError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {}, {} FROM todos WHERE project_id = ? AND deleted_at IS NULL ORDER BY position ASC, id ASC",
+        TODO_COLUMNS, TAGS_COLUMN
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to fetch todos", e))?;
+
+    Ok(arrange_tree(rows.iter().map(todo_from_row).collect()))
+}
+
+pub async fn focus_queue(project_id: i64, pool: &SqlitePool) -> Result<Vec<Todo>, MissionControlError> {
+    let todos = get_todos_by_project(project_id, pool).await?;
+    Ok(focus_order(todos, Utc::now().naive_utc()))
+}
+
+// What the focus timer would pick next in the project, if anything is left
+pub async fn next_focus_candidate(project_id: i64, pool: &SqlitePool) -> Result<Option<Todo>, MissionControlError> {
+    Ok(focus_queue(project_id, pool).await?.into_iter().next())
+}
+
+// A single todo with its depth and rolled-up time
+pub async fn get_todo_by_id(id: i64, pool: &SqlitePool) -> Result<Todo, MissionControlError> {
+    let project_id: i64 = sqlx::query_scalar("SELECT project_id FROM todos WHERE id = ? AND deleted_at IS NULL")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to fetch todo", e))?
+        .ok_or_else(|| MissionControlError::NotFound(format!("Todo {} not found", id)))?;
+
+    get_todos_by_project(project_id, pool)
+        .await?
+        .into_iter()
+        .find(|todo| todo.id == id)
+        .ok_or_else(|| MissionControlError::NotFound(format!("Todo {} not found", id)))
+}
+
+// Walk up from `parent_id`, marking each todo done once all of its subtasks are done
+pub async fn complete_finished_parents(mut parent_id: Option<i64>, conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    let mut visited = HashSet::new();
+    while let Some(id) = parent_id.filter(|id| visited.insert(*id)) {
+        let (child_count, open_count): (i64, i64) = sqlx::query_as(
+            "SELECT COUNT(*), COALESCE(SUM(completed = 0), 0) FROM todos WHERE parent_id = ? AND deleted_at IS NULL"
+        )
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to count subtasks", e))?;
+        if child_count == 0 || open_count > 0 {
+            break;
+        }
+
+        parent_id = sqlx::query_scalar("UPDATE todos SET completed = 1 WHERE id = ? RETURNING parent_id")
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to complete parent todo", e))?
+            .flatten();
+    }
+    Ok(())
 }
 
-pub async fn create_new_todo(
+// Reopen every finished todo above `parent_id`, since a parent only stays done while
+// all of its subtasks are
+pub async fn reopen_parents(parent_id: Option<i64>, conn: &mut SqliteConnection) -> Result<(), MissionControlError> {
+    let Some(parent_id) = parent_id else {
+        return Ok(());
+    };
+    sqlx::query(
+        r#"
+        WITH RECURSIVE chain(id, parent_id) AS (
+            SELECT id, parent_id FROM todos WHERE id = ?
+            UNION
+            SELECT t.id, t.parent_id FROM todos t JOIN chain c ON t.id = c.parent_id
+        )
+        UPDATE todos SET completed = 0 WHERE completed != 0 AND id IN (SELECT id FROM chain)
+        "#,
+    )
+    .bind(parent_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to reopen parent todo", e))?;
+    Ok(())
+}
+
+// Accepts a pool or an open transaction, so bulk imports can create todos atomically.
+// A subtask must belong to the same project as its parent.
+pub async fn create_new_todo<'a, A>(
     text: String,
     time: i32,
     project_id: i64,
-    pool: &SqlitePool,
-) -> Result<Todo, String> {
-    let result = sqlx::query(
-        "INSERT INTO todos (text, time, completed, project_id) VALUES (?, ?, 0, ?) RETURNING id, text, completed, time, created_at, project_id"
-    )
+    parent_id: Option<i64>,
+    db: A,
+) -> Result<Todo, MissionControlError>
+where
+    A: Acquire<'a, Database = Sqlite>,
+{
+    if text.trim().is_empty() {
+        return Err(MissionControlError::Validation("Todo text cannot be empty".to_string()));
+    }
+    if time <= 0 {
+        return Err(MissionControlError::Validation("Todo time must be a positive number of minutes".to_string()));
+    }
+
+    let mut conn = db
+        .acquire()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to acquire database connection", e))?;
+    projects::ensure_not_archived(project_id, &mut *conn).await?;
+
+    let mut depth = 0;
+    if let Some(parent_id) = parent_id {
+        let parent_project: i64 = sqlx::query_scalar("SELECT project_id FROM todos WHERE id = ? AND deleted_at IS NULL")
+            .bind(parent_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to look up parent todo", e))?
+            .ok_or_else(|| MissionControlError::NotFound(format!("Parent todo {} not found", parent_id)))?;
+        if parent_project != project_id {
+            return Err(MissionControlError::Validation(
+                "A subtask must be in the same project as its parent".to_string(),
+            ));
+        }
+
+        // Depth is the number of ancestors, i.e. the length of the parent's chain
+        let ancestors: i64 = sqlx::query_scalar(
+            r#"
+            WITH RECURSIVE chain(id, parent_id) AS (
+                SELECT id, parent_id FROM todos WHERE id = ?
+                UNION
+                SELECT t.id, t.parent_id FROM todos t JOIN chain c ON t.id = c.parent_id
+            )
+            SELECT COUNT(*) FROM chain
+            "#,
+        )
+        .bind(parent_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to look up parent todo", e))?;
+        depth = ancestors as u32;
+    }
+
+    // New todos go to the end of the project's list
+    let result = sqlx::query(&format!(
+        r#"
+        INSERT INTO todos (text, time, completed, project_id, parent_id, position)
+        VALUES (?1, ?2, 0, ?3, ?4, (SELECT COALESCE(MAX(position), 0) + ?5 FROM todos WHERE project_id = ?3))
+        RETURNING {}
+        "#,
+        TODO_COLUMNS
+    ))
     .bind(&text)
     .bind(time)
     .bind(project_id)
-    .fetch_one(pool)
+    .bind(parent_id)
+    .bind(POSITION_GAP)
+    .fetch_one(&mut *conn)
     .await
-    .map_err(|e| {
-        let error_msg = format!("Failed to create todo: {}", e);
-        println!("{}", error_msg);
-        error_msg
-    })?;
-
-    let completed_int: i64 = result.get("completed");
-    let new_todo = Todo {
-        id: result.get("id"),
-        text: result.get("text"),
-        completed: completed_int != 0,
-        time: result.get("time"),
-        created_at: result.get("created_at"),
-        project_id: result.get("project_id"),
-    };
+    .map_err(|e| MissionControlError::from_sqlx("Failed to create todo", e))?;
+
+    let mut new_todo = todo_from_row(&result);
+    new_todo.depth = depth;
 
     Ok(new_todo)
 }
 
-pub async fn update_todo_by_id(id: i64, update: UpdateTodo, pool: &SqlitePool) -> Result<Todo, String> {
+// Create a todo as an undoable operation
+pub async fn create_todo(
+    text: String,
+    time: i32,
+    project_id: i64,
+    parent_id: Option<i64>,
+    pool: &SqlitePool,
+) -> Result<Todo, MissionControlError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    journal::begin("Create todo", &mut tx).await?;
+    let todo = create_new_todo(text, time, project_id, parent_id, &mut *tx).await?;
+    journal::finish(&mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit todo", e))?;
+
+    Ok(todo)
+}
+
+pub async fn update_todo_by_id(id: i64, update: UpdateTodo, pool: &SqlitePool) -> Result<Todo, MissionControlError> {
+    if let Some(ref text) = update.text {
+        if text.trim().is_empty() {
+            return Err(MissionControlError::Validation("Todo text cannot be empty".to_string()));
+        }
+    }
+    if let Some(time) = update.time {
+        if time <= 0 {
+            return Err(MissionControlError::Validation("Todo time must be a positive number of minutes".to_string()));
+        }
+    }
+    if let Some(priority) = update.priority {
+        if priority > LOWEST_PRIORITY {
+            return Err(MissionControlError::Validation(format!(
+                "Priority must be between P0 and P{}",
+                LOWEST_PRIORITY
+            )));
+        }
+    }
+    // An empty due date clears it
+    let due_at = match update.due_at.as_deref().map(str::trim) {
+        Some("") => Some(None),
+        Some(value) => Some(Some(format_due_at(parse_due_at(value).ok_or_else(|| {
+            MissionControlError::Validation(format!("'{}' is not a valid due date", value))
+        })?))),
+        None => None,
+    };
+
     // Build dynamic query based on what fields are being updated
     let mut query_builder = sqlx::QueryBuilder::new("UPDATE todos SET ");
     let mut first = true;
@@ -75,7 +404,7 @@ pub async fn update_todo_by_id(id: i64, update: UpdateTodo, pool: &SqlitePool) -
         query_builder.push_bind(text);
         first = false;
     }
-    
+
     if let Some(completed) = update.completed {
         if !first {
             query_builder.push(", ");
@@ -84,7 +413,7 @@ pub async fn update_todo_by_id(id: i64, update: UpdateTodo, pool: &SqlitePool) -
         query_builder.push_bind(if completed { 1i64 } else { 0i64 }); // Convert bool to INTEGER
         first = false;
     }
-    
+
     if let Some(time) = update.time {
         if !first {
             query_builder.push(", ");
@@ -94,47 +423,557 @@ pub async fn update_todo_by_id(id: i64, update: UpdateTodo, pool: &SqlitePool) -
         first = false;
     }
 
-    if first {
-        return Err("No fields to update".to_string());
+    if let Some(priority) = update.priority {
+        if !first {
+            query_builder.push(", ");
+        }
+        query_builder.push("priority = ");
+        query_builder.push_bind(i64::from(priority));
+        first = false;
+    }
+
+    if let Some(due_at) = due_at {
+        if !first {
+            query_builder.push(", ");
+        }
+        query_builder.push("due_at = ");
+        query_builder.push_bind(due_at);
+        first = false;
+    }
+
+    if first && update.project_id.is_none() {
+        return Err(MissionControlError::Validation("No fields to update".to_string()));
     }
 
     query_builder.push(" WHERE id = ");
     query_builder.push_bind(id);
-    query_builder.push(" RETURNING id, text, completed, time, created_at, project_id");
-
-    let query = query_builder.build();
-    let result = query
-        .fetch_one(pool)
-        .await
-        .map_err(|e| {
-            let error_msg = format!("Failed to update todo: {}", e);
-            println!("{}", error_msg);
-            error_msg
-        })?;
-
-    let completed_int: i64 = result.get("completed");
-    let updated_todo = Todo {
-        id: result.get("id"),
-        text: result.get("text"),
-        completed: completed_int != 0, // Convert INTEGER to bool
-        time: result.get("time"),
-        created_at: result.get("created_at"),
-        project_id: result.get("project_id"),
+    query_builder.push(" AND deleted_at IS NULL RETURNING parent_id");
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    let label = match (update.completed, update.project_id) {
+        (Some(true), _) => "Complete todo",
+        (Some(false), _) => "Reopen todo",
+        (None, Some(_)) => "Move todo",
+        (None, None) => "Edit todo",
     };
+    journal::begin(label, &mut tx).await?;
+
+    // Completing a recurring todo creates its next instance, so note whether it was open
+    let (was_completed, current_project): (bool, i64) =
+        sqlx::query_as("SELECT completed != 0, project_id FROM todos WHERE id = ? AND deleted_at IS NULL")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to look up todo", e))?
+            .ok_or_else(|| MissionControlError::NotFound(format!("Todo {} not found", id)))?;
+    projects::ensure_todos_not_archived(&[id], &mut *tx).await?;
+    // Naming the project the todo is already in leaves it where it is
+    let target_project = update.project_id.filter(|&project_id| project_id != current_project);
+
+    let parent_id: Option<i64> = if first {
+        sqlx::query_scalar("SELECT parent_id FROM todos WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to look up todo", e))?
+    } else {
+        query_builder
+            .build_query_scalar()
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to update todo", e))?
+    }
+    .ok_or_else(|| MissionControlError::NotFound(format!("Todo {} not found", id)))?;
+
+    // A project change moves the todo, with its subtasks, to the end of that project
+    if let Some(project_id) = target_project {
+        ordering::move_in(&[id], Some(project_id), None, None, &mut tx).await?;
+    }
+
+    if update.completed == Some(true) && update.complete_children == Some(true) {
+        sqlx::query(
+            r#"
+            WITH RECURSIVE descendants(id) AS (
+                SELECT id FROM todos WHERE parent_id = ? AND deleted_at IS NULL
+                UNION
+                SELECT t.id FROM todos t JOIN descendants d ON t.parent_id = d.id WHERE t.deleted_at IS NULL
+            )
+            UPDATE todos SET completed = 1 WHERE id IN (SELECT id FROM descendants)
+            "#,
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to complete subtasks", e))?;
+    }
+    if update.completed == Some(true) && !was_completed {
+        recurring::materialize_next(id, &mut tx).await?;
+    }
+    // Both completing a todo and moving it away can leave its parent with only finished subtasks
+    if update.completed == Some(true) || target_project.is_some() {
+        complete_finished_parents(parent_id, &mut tx).await?;
+    }
+    if update.completed == Some(false) {
+        reopen_parents(parent_id, &mut tx).await?;
+    }
+    journal::finish(&mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit todo update", e))?;
 
-    Ok(updated_todo)
+    get_todo_by_id(id, pool).await
 }
 
-pub async fn delete_todo_by_id(id: i64, pool: &SqlitePool) -> Result<(), String> {
-    sqlx::query("DELETE FROM todos WHERE id = ?")
+// Move a todo to the trash. A todo with subtasks is only deleted when `subtasks`
+// says what happens to them: trash the whole subtree along with it, or promote the
+// children to the deleted todo's parent. Trashed todos leave the list order, and
+// come back at the end of it when restored.
+pub async fn delete_todo_by_id(id: i64, subtasks: Option<SubtaskPolicy>, pool: &SqlitePool) -> Result<(), MissionControlError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    journal::begin("Delete todo", &mut tx).await?;
+
+    let parent_id: Option<i64> = sqlx::query_scalar("SELECT parent_id FROM todos WHERE id = ? AND deleted_at IS NULL")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to look up todo", e))?
+        .ok_or_else(|| MissionControlError::NotFound(format!("Todo {} not found", id)))?;
+    projects::ensure_todos_not_archived(&[id], &mut *tx).await?;
+
+    let child_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM todos WHERE parent_id = ? AND deleted_at IS NULL")
         .bind(id)
-        .execute(pool)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to count subtasks", e))?;
+
+    let mut trashed = vec![id];
+    match (child_count, subtasks) {
+        (0, _) => {}
+        (_, None) => {
+            return Err(MissionControlError::Conflict(format!(
+                "Todo {} has {} subtask(s); choose whether to delete or promote them",
+                id, child_count
+            )));
+        }
+        (_, Some(SubtaskPolicy::Delete)) => {
+            trashed.extend(descendant_ids(&[id], &mut tx).await?);
+        }
+        (_, Some(SubtaskPolicy::Promote)) => {
+            sqlx::query("UPDATE todos SET parent_id = ? WHERE parent_id = ? AND deleted_at IS NULL")
+                .bind(parent_id)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| MissionControlError::from_sqlx("Failed to promote subtasks", e))?;
+        }
+    }
+
+    let mut trash_query = sqlx::QueryBuilder::new("UPDATE todos SET position = NULL, deleted_at = ");
+    trash_query.push_bind(trash::deletion_stamp());
+    trash_query.push(" WHERE id IN (");
+    let mut separated = trash_query.separated(", ");
+    for id in &trashed {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(")");
+    trash_query
+        .build()
+        .execute(&mut *tx)
         .await
-        .map_err(|e| {
-            let error_msg = format!("Failed to delete todo: {}", e);
-            println!("{}", error_msg);
-            error_msg
-        })?;
+        .map_err(|e| MissionControlError::from_sqlx("Failed to delete todo", e))?;
+
+    // Removing an open subtask can leave the parent with only finished ones
+    complete_finished_parents(parent_id, &mut tx).await?;
+    journal::finish(&mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit todo deletion", e))?;
 
     Ok(())
-} 
\ No newline at end of file
+}
+
+// Copy todos, with their subtasks, to the end of `target_project_id`. Focus sessions
+// are not copied, and neither is a repeat rule, so a copy never spawns instances. A copied subtask whose parent isn't copied keeps that parent when
+// copied within the same project and becomes top-level otherwise.
+pub async fn copy_todos(ids: &[i64], target_project_id: i64, pool: &SqlitePool) -> Result<Vec<Todo>, MissionControlError> {
+    if ids.is_empty() {
+        return Err(MissionControlError::Validation("No todos to copy".to_string()));
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    journal::begin(if ids.len() == 1 { "Copy todo" } else { "Copy todos" }, &mut tx).await?;
+    ordering::ensure_project_exists(target_project_id, &mut tx).await?;
+    projects::ensure_not_archived(target_project_id, &mut *tx).await?;
+
+    let mut block: Vec<i64> = Vec::new();
+    for id in ids {
+        if !block.contains(id) {
+            block.push(*id);
+        }
+    }
+    block.extend(descendant_ids(&block, &mut tx).await?);
+
+    let mut copies: HashMap<i64, i64> = HashMap::new();
+    let mut parents: Vec<(i64, Option<i64>, i64)> = Vec::new();
+    for id in &block {
+        let (source_parent, source_project): (Option<i64>, i64) =
+            sqlx::query_as("SELECT parent_id, project_id FROM todos WHERE id = ? AND deleted_at IS NULL")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| MissionControlError::from_sqlx("Failed to look up todo", e))?
+                .ok_or_else(|| MissionControlError::NotFound(format!("Todo {} not found", id)))?;
+
+        let copy_id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO todos (text, time, completed, priority, due_at, project_id, position)
+            SELECT text, time, completed, priority, due_at, ?1, (SELECT COALESCE(MAX(position), 0) + ?2 FROM todos WHERE project_id = ?1)
+            FROM todos WHERE id = ?3
+            RETURNING id
+            "#,
+        )
+        .bind(target_project_id)
+        .bind(POSITION_GAP)
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to copy todo", e))?;
+
+        sqlx::query("INSERT INTO todo_tags (todo_id, tag_id) SELECT ?, tag_id FROM todo_tags WHERE todo_id = ?")
+            .bind(copy_id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to copy todo tags", e))?;
+
+        copies.insert(*id, copy_id);
+        parents.push((copy_id, source_parent, source_project));
+    }
+
+    // Link parents once every copy exists, since a subtask may sort before its parent
+    for (copy_id, source_parent, source_project) in parents {
+        let parent_id = match source_parent {
+            Some(parent) if copies.contains_key(&parent) => Some(copies[&parent]),
+            Some(parent) if source_project == target_project_id => Some(parent),
+            _ => None,
+        };
+        if parent_id.is_some() {
+            sqlx::query("UPDATE todos SET parent_id = ? WHERE id = ?")
+                .bind(parent_id)
+                .bind(copy_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| MissionControlError::from_sqlx("Failed to link copied subtask", e))?;
+        }
+    }
+
+    journal::finish(&mut tx).await?;
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit todo copy", e))?;
+
+    let copied: HashSet<i64> = copies.values().copied().collect();
+    Ok(get_todos_by_project(target_project_id, pool)
+        .await?
+        .into_iter()
+        .filter(|todo| copied.contains(&todo.id))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{sessions, tags};
+    use crate::test_support::{migrated_pool, project, todo};
+    use crate::types::Recurrence;
+
+    #[tokio::test]
+    async fn missing_todo_is_not_found() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let update = UpdateTodo { text: Some("Renamed".to_string()), ..Default::default() };
+
+        assert_eq!(get_todo_by_id(42, &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!(update_todo_by_id(42, update, &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!(delete_todo_by_id(42, None, &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!(copy_todos(&[42], inbox.id, &pool).await.unwrap_err().kind(), "not_found");
+
+        let orphan = create_todo("Subtask".to_string(), 25, inbox.id, Some(42), &pool).await;
+        assert_eq!(orphan.unwrap_err().kind(), "not_found");
+    }
+
+    #[tokio::test]
+    async fn invalid_fields_are_rejected() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+        let parent = todo("Parent", inbox.id, None, &pool).await;
+
+        assert_eq!(create_todo("  ".to_string(), 25, inbox.id, None, &pool).await.unwrap_err().kind(), "validation");
+        assert_eq!(create_todo("Plan".to_string(), 0, inbox.id, None, &pool).await.unwrap_err().kind(), "validation");
+        let elsewhere = create_todo("Subtask".to_string(), 25, side.id, Some(parent.id), &pool).await;
+        assert_eq!(elsewhere.unwrap_err().kind(), "validation");
+        assert_eq!(copy_todos(&[], side.id, &pool).await.unwrap_err().kind(), "validation");
+
+        for update in [
+            UpdateTodo::default(),
+            UpdateTodo { text: Some(String::new()), ..Default::default() },
+            UpdateTodo { time: Some(-5), ..Default::default() },
+            UpdateTodo { priority: Some(LOWEST_PRIORITY + 1), ..Default::default() },
+            UpdateTodo { due_at: Some("someday".to_string()), ..Default::default() },
+        ] {
+            assert_eq!(update_todo_by_id(parent.id, update, &pool).await.unwrap_err().kind(), "validation");
+        }
+    }
+
+    #[tokio::test]
+    async fn conflicting_changes_are_refused() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+        let parent = todo("Parent", inbox.id, None, &pool).await;
+        todo("Child", inbox.id, Some(parent.id), &pool).await;
+
+        // Deleting a parent needs a decision about its subtasks
+        assert_eq!(delete_todo_by_id(parent.id, None, &pool).await.unwrap_err().kind(), "conflict");
+
+        let task = todo("Task", side.id, None, &pool).await;
+        projects::archive_project(side.id, &pool).await.unwrap();
+        let archived = create_todo("Another".to_string(), 25, side.id, None, &pool).await;
+        assert_eq!(archived.unwrap_err().kind(), "conflict");
+        assert_eq!(delete_todo_by_id(task.id, None, &pool).await.unwrap_err().kind(), "conflict");
+    }
+
+    async fn completed(id: i64, pool: &SqlitePool) -> bool {
+        sqlx::query_scalar("SELECT completed != 0 FROM todos WHERE id = ?").bind(id).fetch_one(pool).await.unwrap()
+    }
+
+    async fn set_completed(id: i64, done: bool, pool: &SqlitePool) {
+        update_todo_by_id(id, UpdateTodo { completed: Some(done), ..Default::default() }, pool).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn subtasks_nest_and_roll_up_their_time() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let plan = create_todo("Plan".to_string(), 10, inbox.id, None, &pool).await.unwrap();
+        let draft = create_todo("Draft".to_string(), 20, inbox.id, Some(plan.id), &pool).await.unwrap();
+        let outline = create_todo("Outline".to_string(), 5, inbox.id, Some(draft.id), &pool).await.unwrap();
+        create_todo("Review".to_string(), 30, inbox.id, Some(plan.id), &pool).await.unwrap();
+        create_todo("Ship".to_string(), 15, inbox.id, None, &pool).await.unwrap();
+        assert_eq!(outline.depth, 2);
+
+        let listed: Vec<(String, u32, i32)> = get_todos_by_project(inbox.id, &pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|todo| (todo.text, todo.depth, todo.total_time))
+            .collect();
+        let expected = [("Plan", 0, 65), ("Draft", 1, 25), ("Outline", 2, 5), ("Review", 1, 30), ("Ship", 0, 15)];
+        assert_eq!(listed, expected.map(|(text, depth, total)| (text.to_string(), depth, total)));
+        assert_eq!(get_todo_by_id(draft.id, &pool).await.unwrap().total_time, 25);
+    }
+
+    #[tokio::test]
+    async fn parents_follow_their_subtasks() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let plan = todo("Plan", inbox.id, None, &pool).await;
+        let draft = todo("Draft", inbox.id, Some(plan.id), &pool).await;
+        let outline = todo("Outline", inbox.id, Some(draft.id), &pool).await;
+        let review = todo("Review", inbox.id, Some(plan.id), &pool).await;
+
+        // Finishing the last open subtask finishes every parent above it
+        set_completed(outline.id, true, &pool).await;
+        assert!(completed(draft.id, &pool).await);
+        assert!(!completed(plan.id, &pool).await);
+        set_completed(review.id, true, &pool).await;
+        assert!(completed(plan.id, &pool).await);
+
+        // Reopening one reopens them again, but not its finished siblings
+        set_completed(outline.id, false, &pool).await;
+        assert!(!completed(draft.id, &pool).await);
+        assert!(!completed(plan.id, &pool).await);
+        assert!(completed(review.id, &pool).await);
+
+        // Trashing the only open subtask leaves the parent done
+        set_completed(outline.id, true, &pool).await;
+        set_completed(review.id, false, &pool).await;
+        assert!(!completed(plan.id, &pool).await);
+        delete_todo_by_id(review.id, None, &pool).await.unwrap();
+        assert!(completed(plan.id, &pool).await);
+
+        // Completing a parent with complete_children finishes the whole subtree
+        let launch = todo("Launch", inbox.id, None, &pool).await;
+        let invite = todo("Invite", inbox.id, Some(launch.id), &pool).await;
+        update_todo_by_id(
+            launch.id,
+            UpdateTodo { completed: Some(true), complete_children: Some(true), ..Default::default() },
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert!(completed(invite.id, &pool).await);
+    }
+
+    // Text and depth of the project's todos in list order
+    async fn outline(project_id: i64, pool: &SqlitePool) -> Vec<(String, u32)> {
+        get_todos_by_project(project_id, pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|todo| (todo.text, todo.depth))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn moving_takes_subtasks_along_and_staying_put_is_a_no_op() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+        let plan = todo("Plan", inbox.id, None, &pool).await;
+        todo("Draft", inbox.id, Some(plan.id), &pool).await;
+        todo("Ship", inbox.id, None, &pool).await;
+        todo("Existing", side.id, None, &pool).await;
+
+        // Naming the current project neither moves the todo nor records a change
+        let history = journal::get_history(&pool).await.unwrap().undo.len();
+        let stay = UpdateTodo { project_id: Some(inbox.id), ..Default::default() };
+        let unmoved = update_todo_by_id(plan.id, stay, &pool).await.unwrap();
+        assert_eq!(unmoved.position, plan.position);
+        assert_eq!(outline(inbox.id, &pool).await[0].0, "Plan");
+        assert_eq!(journal::get_history(&pool).await.unwrap().undo.len(), history);
+
+        let moved = update_todo_by_id(plan.id, UpdateTodo { project_id: Some(side.id), ..Default::default() }, &pool)
+            .await
+            .unwrap();
+        assert_eq!(moved.project_id, side.id);
+        assert_eq!(outline(inbox.id, &pool).await, vec![("Ship".to_string(), 0)]);
+        assert_eq!(
+            outline(side.id, &pool).await,
+            vec![("Existing".to_string(), 0), ("Plan".to_string(), 0), ("Draft".to_string(), 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn copies_keep_subtasks_and_tags_but_not_sessions_or_repeats() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+        let standup = todo("Standup", inbox.id, None, &pool).await;
+        let notes = todo("Notes", inbox.id, Some(standup.id), &pool).await;
+        update_todo_by_id(
+            standup.id,
+            UpdateTodo { priority: Some(0), due_at: Some("2024-03-01".to_string()), ..Default::default() },
+            &pool,
+        )
+        .await
+        .unwrap();
+        recurring::set_recurrence(standup.id, Some(Recurrence::Daily), &pool).await.unwrap();
+        tags::add_tag(notes.id, "meetings", &pool).await.unwrap();
+        sessions::start_session(notes.id, inbox.id, &pool).await.unwrap();
+
+        let copies = copy_todos(&[standup.id], side.id, &pool).await.unwrap();
+        assert_eq!(copies.len(), 2);
+        let (standup_copy, notes_copy) = (&copies[0], &copies[1]);
+        assert_eq!((standup_copy.text.as_str(), standup_copy.depth), ("Standup", 0));
+        assert_eq!(notes_copy.parent_id, Some(standup_copy.id));
+        assert_eq!(standup_copy.priority, 0);
+        assert!(standup_copy.due_at.is_some());
+        assert!(standup_copy.recurrence.is_none());
+        assert_eq!(notes_copy.tags, vec!["meetings"]);
+        assert!(sessions::get_sessions_by_todo(notes_copy.id, &pool).await.unwrap().is_empty());
+
+        // The original keeps its rule and stays where it was
+        assert!(get_todo_by_id(standup.id, &pool).await.unwrap().recurrence.is_some());
+        assert_eq!(outline(inbox.id, &pool).await, vec![("Standup".to_string(), 0), ("Notes".to_string(), 1)]);
+
+        // Copying a subtask within its project keeps its parent
+        let again = copy_todos(&[notes.id], inbox.id, &pool).await.unwrap();
+        assert_eq!(again[0].parent_id, Some(standup.id));
+    }
+
+    fn queued(id: i64, priority: u8, due_at: Option<&str>) -> Todo {
+        Todo {
+            id,
+            text: format!("Todo {}", id),
+            completed: false,
+            time: 25,
+            created_at: "2024-03-01 09:00:00".to_string(),
+            project_id: 1,
+            parent_id: None,
+            priority,
+            due_at: due_at.map(str::to_string),
+            recurrence: None,
+            tags: Vec::new(),
+            position: id as f64 * POSITION_GAP,
+            depth: 0,
+            total_time: 25,
+        }
+    }
+
+    fn ids(todos: &[Todo]) -> Vec<i64> {
+        todos.iter().map(|todo| todo.id).collect()
+    }
+
+    #[test]
+    fn focus_order_puts_overdue_first_then_priority_then_list_order() {
+        let now = NaiveDateTime::parse_from_str("2024-03-10 12:00:00", DUE_FORMAT).unwrap();
+        let todos = vec![
+            queued(1, 3, None),
+            queued(2, 0, Some("2024-03-11 09:00:00")), // due later
+            queued(3, 3, Some("2024-03-09 09:00:00")), // overdue
+            queued(4, 1, Some("2024-03-10 12:00:00")), // due right now, not overdue yet
+            queued(5, 2, Some("2024-03-01 09:00:00")), // overdue
+            queued(6, 0, None),
+            queued(7, 3, Some("2024-03-08 09:00:00")), // overdue, ties with 3
+            queued(8, 0, None),                        // ties with 6
+        ];
+        assert_eq!(ids(&focus_order(todos, now)), vec![5, 3, 7, 2, 6, 8, 4, 1]);
+    }
+
+    #[test]
+    fn focus_order_skips_done_todos_and_parents() {
+        let now = NaiveDateTime::parse_from_str("2024-03-10 12:00:00", DUE_FORMAT).unwrap();
+        let mut done = queued(1, 0, Some("2024-03-01 09:00:00"));
+        done.completed = true;
+        let parent = queued(2, 0, None);
+        let mut child = queued(3, 2, None);
+        child.parent_id = Some(parent.id);
+        let mut finished_child = queued(4, 0, None);
+        finished_child.parent_id = Some(parent.id);
+        finished_child.completed = true;
+
+        assert_eq!(ids(&focus_order(vec![done, parent, child, finished_child, queued(5, 3, None)], now)), vec![3, 5]);
+    }
+
+    #[tokio::test]
+    async fn focus_queue_follows_focus_order() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let later = todo("Later", inbox.id, None, &pool).await;
+        let urgent = todo("Urgent", inbox.id, None, &pool).await;
+        let late = todo("Late", inbox.id, None, &pool).await;
+        update_todo_by_id(urgent.id, UpdateTodo { priority: Some(0), ..Default::default() }, &pool).await.unwrap();
+        update_todo_by_id(late.id, UpdateTodo { due_at: Some("2000-01-01".to_string()), ..Default::default() }, &pool)
+            .await
+            .unwrap();
+
+        assert_eq!(ids(&focus_queue(inbox.id, &pool).await.unwrap()), vec![late.id, urgent.id, later.id]);
+        assert_eq!(next_focus_candidate(inbox.id, &pool).await.unwrap().unwrap().id, late.id);
+    }
+}
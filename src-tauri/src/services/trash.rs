@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use chrono::{Duration, NaiveDateTime, Utc};
+use sqlx::{sqlite::SqlitePool, Row};
+use crate::error::MissionControlError;
+use crate::services::ordering::POSITION_GAP;
+use crate::services::{projects, settings};
+use crate::types::{TrashItem, TrashKind, TrashPolicy};
+
+pub const POLICY_KEY: &str = "trash_policy";
+
+// Longest time `retention_days` accepts, about ten years
+const MAX_RETENTION_DAYS: u32 = 3650;
+
+const STAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// `deleted_at` for everything deleted in one go. Rows sharing a stamp are restored
+// together, so it is taken once and bound to every statement.
+pub fn deletion_stamp() -> String {
+    Utc::now().naive_utc().format(STAMP_FORMAT).to_string()
+}
+
+fn purge_at(deleted_at: &str, policy: &TrashPolicy) -> String {
+    NaiveDateTime::parse_from_str(deleted_at, STAMP_FORMAT)
+        .map(|at| (at + Duration::days(i64::from(policy.retention_days))).format(STAMP_FORMAT).to_string())
+        .unwrap_or_else(|_| deleted_at.to_string())
+}
+
+pub async fn get_policy(pool: &SqlitePool) -> Result<TrashPolicy, MissionControlError> {
+    settings::get_setting(POLICY_KEY, pool).await
+}
+
+pub async fn set_policy(policy: TrashPolicy, pool: &SqlitePool) -> Result<TrashPolicy, MissionControlError> {
+    if policy.retention_days == 0 || policy.retention_days > MAX_RETENTION_DAYS {
+        return Err(MissionControlError::Validation(format!(
+            "Items must stay in the trash for between 1 and {} days",
+            MAX_RETENTION_DAYS
+        )));
+    }
+    settings::set_setting(POLICY_KEY, &policy, pool).await?;
+    Ok(policy)
+}
+
+// Deleted projects, and deleted todos of projects that aren't, newest first. Subtasks
+// deleted along with their parent are counted under it rather than listed.
+pub async fn list_trash(pool: &SqlitePool) -> Result<Vec<TrashItem>, MissionControlError> {
+    let policy = get_policy(pool).await?;
+
+    let project_rows = sqlx::query(
+        r#"
+        SELECT projects.id, projects.name, projects.deleted_at,
+               (SELECT COUNT(*) FROM todos WHERE todos.project_id = projects.id AND todos.deleted_at = projects.deleted_at) AS todo_count
+        FROM projects
+        WHERE projects.deleted_at IS NOT NULL
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to read deleted projects", e))?;
+
+    let todo_rows = sqlx::query(
+        r#"
+        SELECT todos.id, todos.text, todos.parent_id, todos.project_id, todos.deleted_at, projects.name AS project_name
+        FROM todos
+        JOIN projects ON projects.id = todos.project_id AND projects.deleted_at IS NULL
+        WHERE todos.deleted_at IS NOT NULL
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| MissionControlError::from_sqlx("Failed to read deleted todos", e))?;
+
+    let mut items: Vec<TrashItem> = project_rows
+        .iter()
+        .map(|row| {
+            let deleted_at: String = row.get("deleted_at");
+            TrashItem {
+                kind: TrashKind::Project,
+                id: row.get("id"),
+                title: row.get("name"),
+                project_id: row.get("id"),
+                project_name: row.get("name"),
+                todo_count: row.get("todo_count"),
+                purge_at: purge_at(&deleted_at, &policy),
+                deleted_at,
+            }
+        })
+        .collect();
+
+    // A deleted todo is its own entry unless its parent was deleted at the same time
+    let deleted: HashMap<i64, (Option<i64>, String)> = todo_rows
+        .iter()
+        .map(|row| (row.get("id"), (row.get("parent_id"), row.get("deleted_at"))))
+        .collect();
+    let batch_root = |mut id: i64| {
+        for _ in 0..deleted.len() {
+            let (parent_id, deleted_at) = &deleted[&id];
+            match parent_id.and_then(|parent_id| deleted.get(&parent_id).map(|parent| (parent_id, parent))) {
+                Some((parent_id, (_, parent_deleted_at))) if parent_deleted_at == deleted_at => id = parent_id,
+                _ => break,
+            }
+        }
+        id
+    };
+    let mut todo_counts: HashMap<i64, i64> = HashMap::new();
+    for id in deleted.keys() {
+        *todo_counts.entry(batch_root(*id)).or_default() += 1;
+    }
+
+    for row in &todo_rows {
+        let id: i64 = row.get("id");
+        let todo_count = match todo_counts.get(&id) {
+            Some(count) if batch_root(id) == id => *count,
+            _ => continue,
+        };
+        let deleted_at: String = row.get("deleted_at");
+        items.push(TrashItem {
+            kind: TrashKind::Todo,
+            id,
+            title: row.get("text"),
+            project_id: row.get("project_id"),
+            project_name: row.get("project_name"),
+            todo_count,
+            purge_at: purge_at(&deleted_at, &policy),
+            deleted_at,
+        });
+    }
+
+    items.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then(b.id.cmp(&a.id)));
+    Ok(items)
+}
+
+// Bring back a trash entry with everything that was deleted along with it. A restored
+// todo goes to the end of its project's list, and becomes top-level when its parent
+// is still deleted or has moved to another project.
+pub async fn restore_from_trash(kind: TrashKind, id: i64, pool: &SqlitePool) -> Result<(), MissionControlError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    match kind {
+        TrashKind::Project => {
+            let deleted_at: String = sqlx::query_scalar::<_, Option<String>>("SELECT deleted_at FROM projects WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| MissionControlError::from_sqlx("Failed to look up project", e))?
+                .ok_or_else(|| MissionControlError::NotFound(format!("Project {} not found", id)))?
+                .ok_or_else(|| MissionControlError::Validation(format!("Project {} is not in the trash", id)))?;
+
+            for (query, context) in [
+                ("UPDATE todos SET deleted_at = NULL WHERE project_id = ?1 AND deleted_at = ?2", "Failed to restore todos"),
+                ("UPDATE excalidraw_data SET deleted_at = NULL WHERE project_id = ?1 AND deleted_at = ?2", "Failed to restore excalidraw data"),
+                ("UPDATE projects SET deleted_at = NULL WHERE id = ?1", "Failed to restore project"),
+            ] {
+                sqlx::query(query)
+                    .bind(id)
+                    .bind(&deleted_at)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| MissionControlError::from_sqlx(context, e))?;
+            }
+        }
+        TrashKind::Todo => {
+            let row = sqlx::query(
+                r#"
+                SELECT todos.deleted_at, todos.project_id, projects.name AS project_name, projects.deleted_at AS project_deleted_at
+                FROM todos JOIN projects ON projects.id = todos.project_id
+                WHERE todos.id = ?
+                "#,
+            )
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to look up todo", e))?
+            .ok_or_else(|| MissionControlError::NotFound(format!("Todo {} not found", id)))?;
+
+            let deleted_at: String = row
+                .get::<Option<String>, _>("deleted_at")
+                .ok_or_else(|| MissionControlError::Validation(format!("Todo {} is not in the trash", id)))?;
+            if row.get::<Option<String>, _>("project_deleted_at").is_some() {
+                return Err(MissionControlError::Conflict(format!(
+                    "Todo {} belongs to the deleted project '{}'; restore the project first",
+                    id,
+                    row.get::<String, _>("project_name")
+                )));
+            }
+            let project_id: i64 = row.get("project_id");
+            projects::ensure_not_archived(project_id, &mut *tx).await?;
+
+            let batch: Vec<i64> = sqlx::query_scalar(
+                r#"
+                WITH RECURSIVE batch(id) AS (
+                    SELECT ?1
+                    UNION
+                    SELECT t.id FROM todos t JOIN batch b ON t.parent_id = b.id WHERE t.deleted_at = ?2
+                )
+                SELECT id FROM batch ORDER BY id ASC
+                "#,
+            )
+            .bind(id)
+            .bind(&deleted_at)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to look up deleted subtasks", e))?;
+
+            for todo_id in &batch {
+                sqlx::query(
+                    "UPDATE todos SET deleted_at = NULL, position = (SELECT COALESCE(MAX(position), 0) + ?1 FROM todos WHERE project_id = ?2) WHERE id = ?3"
+                )
+                .bind(POSITION_GAP)
+                .bind(project_id)
+                .bind(todo_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| MissionControlError::from_sqlx("Failed to restore todo", e))?;
+            }
+
+            sqlx::query(
+                r#"
+                UPDATE todos SET parent_id = NULL
+                WHERE id = ?1 AND parent_id NOT IN (SELECT id FROM todos WHERE project_id = ?2 AND deleted_at IS NULL)
+                "#,
+            )
+            .bind(id)
+            .bind(project_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx("Failed to detach restored todo", e))?;
+        }
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit restore", e))?;
+
+    Ok(())
+}
+
+// Delete for good whatever has been in the trash longer than the policy allows.
+// A purged project takes the rest of its todos, whiteboard and focus sessions with
+// it through the schema's cascades. Returns the number of projects and todos
+// removed, not counting those cascades.
+pub async fn purge_expired(pool: &SqlitePool) -> Result<u64, MissionControlError> {
+    let policy = get_policy(pool).await?;
+    let cutoff = (Utc::now().naive_utc() - Duration::days(i64::from(policy.retention_days)))
+        .format(STAMP_FORMAT)
+        .to_string();
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to start transaction", e))?;
+
+    let mut purged = 0;
+    for (query, context, counted) in [
+        ("DELETE FROM todos WHERE deleted_at <= ?", "Failed to purge todos", true),
+        ("DELETE FROM excalidraw_data WHERE deleted_at <= ?", "Failed to purge excalidraw data", false),
+        ("DELETE FROM projects WHERE deleted_at <= ?", "Failed to purge projects", true),
+    ] {
+        let result = sqlx::query(query)
+            .bind(&cutoff)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MissionControlError::from_sqlx(context, e))?;
+        if counted {
+            purged += result.rows_affected();
+        }
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| MissionControlError::from_sqlx("Failed to commit trash purge", e))?;
+
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::todos;
+    use crate::test_support::{migrated_pool, project, todo};
+
+    #[tokio::test]
+    async fn trash_errors_have_the_right_kind() {
+        let pool = migrated_pool().await;
+        let inbox = project("Inbox", &pool).await;
+        let side = project("Side", &pool).await;
+        let task = todo("Task", side.id, None, &pool).await;
+
+        assert_eq!(restore_from_trash(TrashKind::Project, 42, &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!(restore_from_trash(TrashKind::Todo, 42, &pool).await.unwrap_err().kind(), "not_found");
+        assert_eq!(restore_from_trash(TrashKind::Project, inbox.id, &pool).await.unwrap_err().kind(), "validation");
+        assert_eq!(restore_from_trash(TrashKind::Todo, task.id, &pool).await.unwrap_err().kind(), "validation");
+
+        // A todo trashed on its own can't come back into a project that is in the trash
+        todos::delete_todo_by_id(task.id, None, &pool).await.unwrap();
+        projects::delete_project_by_id(side.id, &pool).await.unwrap();
+        assert_eq!(restore_from_trash(TrashKind::Todo, task.id, &pool).await.unwrap_err().kind(), "conflict");
+
+        assert_eq!(set_policy(TrashPolicy { retention_days: 0 }, &pool).await.unwrap_err().kind(), "validation");
+        assert_eq!(
+            set_policy(TrashPolicy { retention_days: MAX_RETENTION_DAYS + 1 }, &pool).await.unwrap_err().kind(),
+            "validation"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::Executor;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::migrations::run_migrations;
+use crate::services::{projects, todos};
+use crate::types::{CreateProject, Project, Todo};
+
+// Empty in-memory database. Every connection to `sqlite::memory:` opens a database
+// of its own, so the pool holds exactly one.
+pub async fn empty_pool() -> SqlitePool {
+    let options = SqliteConnectOptions::from_str("sqlite::memory:").unwrap().foreign_keys(true);
+    SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(options)
+        .await
+        .unwrap()
+}
+
+// In-memory database at the latest schema
+pub async fn migrated_pool() -> SqlitePool {
+    let pool = empty_pool().await;
+    run_migrations(&pool).await.unwrap();
+    pool
+}
+
+// Migrated database in a file of its own, for tests that need several connections
+// at once. The file is removed when this is dropped.
+pub struct TempDatabase {
+    pub path: PathBuf,
+    pub pool: SqlitePool,
+}
+
+impl TempDatabase {
+    pub async fn new(max_connections: u32) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "missioncontrol-test-{}-{}.db",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let options = SqliteConnectOptions::new().filename(&path).create_if_missing(true).foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .connect_with(options)
+            .await
+            .unwrap();
+        run_migrations(&pool).await.unwrap();
+        TempDatabase { path, pool }
+    }
+}
+
+impl Drop for TempDatabase {
+    fn drop(&mut self) {
+        for suffix in ["", "-journal", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", self.path.display(), suffix));
+        }
+    }
+}
+
+// Run a batch of statements, for seeding fixtures
+pub async fn exec(sql: &str, pool: &SqlitePool) {
+    pool.execute(sql).await.unwrap();
+}
+
+pub async fn project(name: &str, pool: &SqlitePool) -> Project {
+    projects::create_project(CreateProject { name: name.to_string(), description: None }, pool)
+        .await
+        .unwrap()
+}
+
+pub async fn todo(text: &str, project_id: i64, parent_id: Option<i64>, pool: &SqlitePool) -> Todo {
+    todos::create_todo(text.to_string(), 25, project_id, parent_id, pool).await.unwrap()
+}
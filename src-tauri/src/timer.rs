@@ -0,0 +1,578 @@
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+use crate::database::{Database, get_pool};
+use crate::error::MissionControlError;
+use crate::services::{sessions, todos};
+use crate::types::{SessionOutcome, Todo};
+
+pub const TICK_EVENT: &str = "timer://tick";
+pub const STATE_EVENT: &str = "timer://state";
+
+// Source of "now" for the state machine, so transitions can be driven by a fake clock
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimerPhase {
+    Idle,
+    Running,
+    Paused,
+    Break,
+}
+
+// What the webview renders; emitted on every transition and every tick
+#[derive(Debug, Serialize, Clone)]
+pub struct TimerSnapshot {
+    pub phase: TimerPhase,
+    pub project_id: Option<i64>,
+    pub todo: Option<Todo>,
+    pub remaining_seconds: u64,
+    pub total_seconds: u64,
+    pub break_minutes: u32,
+}
+
+// Result of advancing the clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TickOutcome {
+    Idle,
+    Counting,
+    FocusElapsed,
+    BreakElapsed,
+}
+
+// Focus/Pomodoro state machine. Running and Break count down towards a deadline
+// rather than decrementing per tick, so a late or missed tick never loses time.
+pub struct FocusTimer<C: Clock = SystemClock> {
+    clock: C,
+    phase: TimerPhase,
+    project_id: Option<i64>,
+    todo: Option<Todo>,
+    // Todo to focus on once the current break is over
+    pending: Option<Todo>,
+    total: Duration,
+    deadline: Option<Instant>,
+    // Time left when paused
+    remaining: Duration,
+    break_minutes: u32,
+    // Open row in focus_sessions for the current run, if any
+    session_id: Option<i64>,
+}
+
+impl<C: Clock> FocusTimer<C> {
+    pub fn new(clock: C) -> Self {
+        Self {
+            clock,
+            phase: TimerPhase::Idle,
+            project_id: None,
+            todo: None,
+            pending: None,
+            total: Duration::ZERO,
+            deadline: None,
+            remaining: Duration::ZERO,
+            break_minutes: 0,
+            session_id: None,
+        }
+    }
+
+    pub fn phase(&self) -> TimerPhase {
+        self.phase
+    }
+
+    pub fn project_id(&self) -> Option<i64> {
+        self.project_id
+    }
+
+    pub fn current_todo_id(&self) -> Option<i64> {
+        self.todo.as_ref().map(|todo| todo.id)
+    }
+
+    pub fn session_id(&self) -> Option<i64> {
+        self.session_id
+    }
+
+    pub fn set_session_id(&mut self, session_id: Option<i64>) {
+        self.session_id = session_id;
+    }
+
+    pub fn take_session_id(&mut self) -> Option<i64> {
+        self.session_id.take()
+    }
+
+    pub fn set_break_minutes(&mut self, minutes: u32) {
+        self.break_minutes = minutes;
+    }
+
+    pub fn remaining(&self) -> Duration {
+        match (self.phase, self.deadline) {
+            (TimerPhase::Running, Some(deadline)) | (TimerPhase::Break, Some(deadline)) => {
+                deadline.saturating_duration_since(self.clock.now())
+            }
+            (TimerPhase::Paused, _) => self.remaining,
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn snapshot(&self) -> TimerSnapshot {
+        let remaining = self.remaining();
+        TimerSnapshot {
+            phase: self.phase,
+            project_id: self.project_id,
+            todo: self.todo.clone(),
+            // Round up so a countdown shows 25:00 right after starting, not 24:59
+            remaining_seconds: remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0),
+            total_seconds: self.total.as_secs(),
+            break_minutes: self.break_minutes,
+        }
+    }
+
+    // Begin a focus run on `todo`, replacing whatever was running before
+    pub fn start(&mut self, project_id: i64, todo: Todo) {
+        self.project_id = Some(project_id);
+        self.pending = None;
+        self.run(todo);
+    }
+
+    fn run(&mut self, todo: Todo) {
+        self.total = Duration::from_secs(todo.time.max(0) as u64 * 60);
+        self.deadline = Some(self.clock.now() + self.total);
+        self.remaining = Duration::ZERO;
+        self.todo = Some(todo);
+        self.phase = TimerPhase::Running;
+    }
+
+    // Whether `pause` would succeed, for callers that persist the change first
+    pub fn check_pause(&self) -> Result<(), MissionControlError> {
+        if self.phase != TimerPhase::Running {
+            return Err(MissionControlError::Validation("Focus timer is not running".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn check_resume(&self) -> Result<(), MissionControlError> {
+        if self.phase != TimerPhase::Paused {
+            return Err(MissionControlError::Validation("Focus timer is not paused".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn pause(&mut self) -> Result<(), MissionControlError> {
+        self.check_pause()?;
+        self.remaining = self.remaining();
+        self.deadline = None;
+        self.phase = TimerPhase::Paused;
+        Ok(())
+    }
+
+    pub fn resume(&mut self) -> Result<(), MissionControlError> {
+        self.check_resume()?;
+        self.deadline = Some(self.clock.now() + self.remaining);
+        self.remaining = Duration::ZERO;
+        self.phase = TimerPhase::Running;
+        Ok(())
+    }
+
+    // Move on to `next`. With `take_break` and a configured break length the timer
+    // rests first and focuses on `next` when the break is over.
+    pub fn advance(&mut self, next: Option<Todo>, take_break: bool) {
+        match next {
+            Some(next) if take_break && self.break_minutes > 0 => {
+                self.total = Duration::from_secs(u64::from(self.break_minutes) * 60);
+                self.deadline = Some(self.clock.now() + self.total);
+                self.remaining = Duration::ZERO;
+                self.todo = None;
+                self.pending = Some(next);
+                self.phase = TimerPhase::Break;
+            }
+            Some(next) => {
+                self.pending = None;
+                self.run(next);
+            }
+            None => self.reset(),
+        }
+    }
+
+    // End the break early (or on time) and focus on the todo queued behind it
+    pub fn finish_break(&mut self) {
+        let pending = self.pending.take();
+        self.advance(pending, false);
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = TimerPhase::Idle;
+        self.project_id = None;
+        self.todo = None;
+        self.pending = None;
+        self.total = Duration::ZERO;
+        self.deadline = None;
+        self.remaining = Duration::ZERO;
+    }
+
+    pub fn tick(&self) -> TickOutcome {
+        match self.phase {
+            TimerPhase::Idle => TickOutcome::Idle,
+            TimerPhase::Paused => TickOutcome::Counting,
+            TimerPhase::Running if self.remaining().is_zero() => TickOutcome::FocusElapsed,
+            TimerPhase::Break if self.remaining().is_zero() => TickOutcome::BreakElapsed,
+            TimerPhase::Running | TimerPhase::Break => TickOutcome::Counting,
+        }
+    }
+}
+
+// Next todo after skipping `current_id`: the following incomplete todo, wrapping
+// around to the first one, or None when it is the only one left
+pub fn next_on_skip(incomplete: &[Todo], current_id: Option<i64>) -> Option<Todo> {
+    let current_index = incomplete.iter().position(|todo| Some(todo.id) == current_id);
+
+    match current_index {
+        Some(index) if index + 1 < incomplete.len() => Some(incomplete[index + 1].clone()),
+        None if !incomplete.is_empty() => Some(incomplete[0].clone()),
+        _ if incomplete.len() > 1 => Some(incomplete[0].clone()),
+        _ => None,
+    }
+}
+
+// Next todo after completing `current_id`: the first remaining incomplete todo
+pub fn next_on_done(incomplete: &[Todo], current_id: i64) -> Option<Todo> {
+    incomplete.iter().find(|todo| todo.id != current_id).cloned()
+}
+
+// Close the session of the run that is ending, if one is open
+pub async fn close_session(timer: &mut FocusTimer, outcome: SessionOutcome, pool: &sqlx::SqlitePool) -> Result<(), MissionControlError> {
+    if let Some(session_id) = timer.take_session_id() {
+        sessions::end_session(session_id, outcome, pool).await?;
+    }
+    Ok(())
+}
+
+// Open a session when the timer has just started running on a todo
+pub async fn open_session(timer: &mut FocusTimer, pool: &sqlx::SqlitePool) -> Result<(), MissionControlError> {
+    if timer.phase() != TimerPhase::Running || timer.session_id().is_some() {
+        return Ok(());
+    }
+    if let (Some(todo_id), Some(project_id)) = (timer.current_todo_id(), timer.project_id()) {
+        let session = sessions::start_session(todo_id, project_id, pool).await?;
+        timer.set_session_id(Some(session.id));
+    }
+    Ok(())
+}
+
+// Todos the timer can focus on, in focus order (see `todos::focus_order`): starting,
+// skipping and completing all walk this list, so the order matches
+// `next_focus_candidate`. A parent is worked on through its subtasks and completes with them.
+pub async fn incomplete_todos(project_id: i64, pool: &sqlx::SqlitePool) -> Result<Vec<Todo>, MissionControlError> {
+    todos::focus_queue(project_id, pool).await
+}
+
+// Timer state managed by Tauri
+pub struct FocusTimerState {
+    pub timer: Arc<Mutex<FocusTimer>>,
+}
+
+impl FocusTimerState {
+    pub fn new() -> Self {
+        Self {
+            timer: Arc::new(Mutex::new(FocusTimer::new(SystemClock))),
+        }
+    }
+}
+
+pub fn emit_state(app: &AppHandle, snapshot: &TimerSnapshot) {
+    if let Err(e) = app.emit_all(STATE_EVENT, snapshot.clone()) {
+        println!("Failed to emit timer state: {}", e);
+    }
+}
+
+// Drive the timer once per second for the lifetime of the app
+pub fn spawn_ticker(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = tick_once(&app).await {
+                println!("Focus timer tick failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn tick_once(app: &AppHandle) -> Result<(), MissionControlError> {
+    let state = app.state::<FocusTimerState>();
+    let mut timer = state.timer.lock().await;
+
+    match timer.tick() {
+        TickOutcome::Idle => return Ok(()),
+        TickOutcome::Counting => {}
+        TickOutcome::FocusElapsed => {
+            // Running out of time behaves like Skip, followed by a break if configured
+            let database = app.state::<Database>();
+            let pool = get_pool(&database).await?;
+            close_session(&mut timer, SessionOutcome::Skipped, &pool).await?;
+
+            let next = match timer.project_id() {
+                Some(project_id) => next_on_skip(&incomplete_todos(project_id, &pool).await?, timer.current_todo_id()),
+                None => None,
+            };
+            timer.advance(next, true);
+            open_session(&mut timer, &pool).await?;
+            emit_state(app, &timer.snapshot());
+        }
+        TickOutcome::BreakElapsed => {
+            let database = app.state::<Database>();
+            let pool = get_pool(&database).await?;
+            timer.finish_break();
+            open_session(&mut timer, &pool).await?;
+            emit_state(app, &timer.snapshot());
+        }
+    }
+
+    if let Err(e) = app.emit_all(TICK_EVENT, timer.snapshot()) {
+        println!("Failed to emit timer tick: {}", e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+
+    // Clock that only moves when the test says so; clones share the same time
+    #[derive(Clone)]
+    struct ManualClock(Arc<StdMutex<Instant>>);
+
+    impl ManualClock {
+        fn new() -> Self {
+            Self(Arc::new(StdMutex::new(Instant::now())))
+        }
+
+        fn advance(&self, seconds: u64) {
+            self.advance_millis(seconds * 1000);
+        }
+
+        fn advance_millis(&self, millis: u64) {
+            *self.0.lock().unwrap() += Duration::from_millis(millis);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn todo(id: i64, minutes: i32) -> Todo {
+        Todo {
+            id,
+            text: format!("Todo {}", id),
+            completed: false,
+            time: minutes,
+            created_at: String::new(),
+            project_id: 1,
+            parent_id: None,
+            priority: 2,
+            due_at: None,
+            recurrence: None,
+            tags: Vec::new(),
+            position: id as f64,
+            depth: 0,
+            total_time: minutes,
+        }
+    }
+
+    fn timer() -> (FocusTimer<ManualClock>, ManualClock) {
+        let clock = ManualClock::new();
+        (FocusTimer::new(clock.clone()), clock)
+    }
+
+    #[test]
+    fn start_pause_resume_and_done() {
+        let (mut timer, clock) = timer();
+        assert_eq!(timer.tick(), TickOutcome::Idle);
+        assert!(timer.pause().is_err());
+        assert!(timer.resume().is_err());
+
+        timer.start(1, todo(1, 25));
+        assert_eq!(timer.phase(), TimerPhase::Running);
+        assert_eq!(timer.current_todo_id(), Some(1));
+        assert_eq!(timer.snapshot().remaining_seconds, 25 * 60);
+        assert_eq!(timer.snapshot().total_seconds, 25 * 60);
+        assert!(timer.resume().is_err());
+
+        clock.advance(60);
+        timer.pause().unwrap();
+        assert_eq!(timer.phase(), TimerPhase::Paused);
+        assert!(timer.pause().is_err());
+        timer.resume().unwrap();
+        assert_eq!(timer.phase(), TimerPhase::Running);
+
+        // Done with nothing left ends the run
+        let incomplete = vec![todo(1, 25)];
+        timer.advance(next_on_done(&incomplete, 1), true);
+        assert_eq!(timer.phase(), TimerPhase::Idle);
+        assert_eq!(timer.project_id(), None);
+        assert_eq!(timer.tick(), TickOutcome::Idle);
+    }
+
+    #[test]
+    fn remaining_time_freezes_while_paused() {
+        let (mut timer, clock) = timer();
+        timer.start(1, todo(1, 25));
+
+        clock.advance(5 * 60);
+        assert_eq!(timer.remaining(), Duration::from_secs(20 * 60));
+        timer.pause().unwrap();
+
+        // Time spent paused doesn't count
+        clock.advance(60 * 60);
+        assert_eq!(timer.remaining(), Duration::from_secs(20 * 60));
+        assert_eq!(timer.tick(), TickOutcome::Counting);
+
+        timer.resume().unwrap();
+        clock.advance(10 * 60);
+        timer.pause().unwrap();
+        clock.advance(30);
+        timer.resume().unwrap();
+        assert_eq!(timer.remaining(), Duration::from_secs(10 * 60));
+
+        clock.advance(10 * 60 - 1);
+        assert_eq!(timer.tick(), TickOutcome::Counting);
+        assert_eq!(timer.snapshot().remaining_seconds, 1);
+        clock.advance(1);
+        assert_eq!(timer.tick(), TickOutcome::FocusElapsed);
+
+        // A late tick never goes below zero
+        clock.advance(90);
+        assert_eq!(timer.remaining(), Duration::ZERO);
+        assert_eq!(timer.tick(), TickOutcome::FocusElapsed);
+    }
+
+    #[test]
+    fn remaining_seconds_round_up() {
+        let (mut timer, clock) = timer();
+        timer.start(1, todo(1, 1));
+        clock.advance_millis(400);
+        assert_eq!(timer.snapshot().remaining_seconds, 60);
+        clock.advance_millis(600);
+        assert_eq!(timer.snapshot().remaining_seconds, 59);
+    }
+
+    #[test]
+    fn skip_walks_the_list_and_wraps_around() {
+        let incomplete = vec![todo(1, 25), todo(2, 25), todo(3, 25)];
+
+        assert_eq!(next_on_skip(&incomplete, Some(1)).map(|todo| todo.id), Some(2));
+        assert_eq!(next_on_skip(&incomplete, Some(2)).map(|todo| todo.id), Some(3));
+        assert_eq!(next_on_skip(&incomplete, Some(3)).map(|todo| todo.id), Some(1));
+        // A todo that left the list (e.g. completed elsewhere) restarts from the top
+        assert_eq!(next_on_skip(&incomplete, Some(9)).map(|todo| todo.id), Some(1));
+        assert_eq!(next_on_skip(&incomplete, None).map(|todo| todo.id), Some(1));
+
+        // Skipping the only todo has nowhere to go
+        assert!(next_on_skip(&incomplete[..1], Some(1)).is_none());
+        assert!(next_on_skip(&[], None).is_none());
+    }
+
+    #[test]
+    fn done_takes_the_first_other_todo() {
+        let incomplete = vec![todo(1, 25), todo(2, 25), todo(3, 25)];
+
+        assert_eq!(next_on_done(&incomplete, 1).map(|todo| todo.id), Some(2));
+        assert_eq!(next_on_done(&incomplete, 2).map(|todo| todo.id), Some(1));
+        assert_eq!(next_on_done(&incomplete, 3).map(|todo| todo.id), Some(1));
+        assert!(next_on_done(&incomplete[..1], 1).is_none());
+    }
+
+    #[test]
+    fn skip_moves_on_without_a_break() {
+        let (mut timer, _clock) = timer();
+        timer.set_break_minutes(5);
+        let incomplete = vec![todo(1, 25), todo(2, 15)];
+        timer.start(1, incomplete[0].clone());
+
+        timer.advance(next_on_skip(&incomplete, timer.current_todo_id()), false);
+        assert_eq!(timer.phase(), TimerPhase::Running);
+        assert_eq!(timer.current_todo_id(), Some(2));
+        assert_eq!(timer.snapshot().total_seconds, 15 * 60);
+
+        timer.advance(next_on_skip(&incomplete, timer.current_todo_id()), false);
+        assert_eq!(timer.current_todo_id(), Some(1));
+    }
+
+    #[test]
+    fn work_and_break_alternate() {
+        let (mut timer, clock) = timer();
+        timer.set_break_minutes(5);
+        let incomplete = vec![todo(1, 25), todo(2, 20)];
+        timer.start(1, incomplete[0].clone());
+
+        // Runs end by time running out or by Done; both take a break before the next todo
+        let mut phases = Vec::new();
+        for run in 0..4 {
+            let focus = timer.snapshot().total_seconds / 60;
+            if run % 2 == 0 {
+                clock.advance(focus * 60);
+                assert_eq!(timer.tick(), TickOutcome::FocusElapsed);
+                timer.advance(next_on_skip(&incomplete, timer.current_todo_id()), true);
+            } else {
+                timer.advance(next_on_done(&incomplete, timer.current_todo_id().unwrap()), true);
+            }
+            assert_eq!(timer.phase(), TimerPhase::Break);
+            assert_eq!(timer.current_todo_id(), None);
+            let rest = timer.snapshot().total_seconds / 60;
+            phases.push((focus, rest));
+
+            clock.advance(rest * 60);
+            assert_eq!(timer.tick(), TickOutcome::BreakElapsed);
+            timer.finish_break();
+            assert_eq!(timer.phase(), TimerPhase::Running);
+        }
+        assert_eq!(phases, vec![(25, 5), (20, 5), (25, 5), (20, 5)]);
+    }
+
+    #[test]
+    fn break_hands_over_to_the_queued_todo() {
+        let (mut timer, clock) = timer();
+        timer.set_break_minutes(5);
+        let incomplete = vec![todo(1, 25), todo(2, 10)];
+        timer.start(1, incomplete[0].clone());
+
+        timer.advance(next_on_done(&incomplete, 1), true);
+        assert_eq!(timer.phase(), TimerPhase::Break);
+        assert_eq!(timer.snapshot().remaining_seconds, 5 * 60);
+        assert!(timer.pause().is_err());
+
+        // Skipping the break starts the queued todo straight away
+        clock.advance(60);
+        timer.finish_break();
+        assert_eq!(timer.phase(), TimerPhase::Running);
+        assert_eq!(timer.current_todo_id(), Some(2));
+        assert_eq!(timer.snapshot().remaining_seconds, 10 * 60);
+        assert_eq!(timer.project_id(), Some(1));
+    }
+
+    #[test]
+    fn no_break_without_a_break_length() {
+        let (mut timer, _clock) = timer();
+        let incomplete = vec![todo(1, 25), todo(2, 25)];
+        timer.start(1, incomplete[0].clone());
+
+        timer.advance(next_on_done(&incomplete, 1), true);
+        assert_eq!(timer.phase(), TimerPhase::Running);
+        assert_eq!(timer.current_todo_id(), Some(2));
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::Weekday;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -8,6 +9,18 @@ pub struct Todo {
     pub time: i32, // time in minutes
     pub created_at: String,
     pub project_id: i64,
+    pub parent_id: Option<i64>, // None for a top-level todo
+    pub priority: u8, // 0 (P0, most urgent) to 3 (P3)
+    pub due_at: Option<String>, // UTC, "YYYY-MM-DD HH:MM:SS"
+    pub recurrence: Option<Recurrence>,
+    #[serde(default)]
+    pub tags: Vec<String>, // tag names, alphabetical
+    #[serde(default)]
+    pub position: f64, // manual order within the project; only relative values matter
+    #[serde(default)]
+    pub depth: u32, // 0 for top-level todos
+    #[serde(default)]
+    pub total_time: i32, // own time plus, for a parent, the subtasks' totals
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -18,11 +31,44 @@ pub struct CreateTodo {
     pub project_id: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct UpdateTodo {
     pub text: Option<String>,
     pub completed: Option<bool>,
     pub time: Option<i32>,
+    pub priority: Option<u8>,
+    pub due_at: Option<String>, // RFC 3339 or YYYY-MM-DD (end of that local day); empty clears it
+    pub project_id: Option<i64>, // moves the todo, with its subtasks, to the end of that project
+    pub complete_children: Option<bool>, // with completed: true, also complete all subtasks
+}
+
+// A label shared across projects
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub created_at: String,
+    pub todo_count: i64,
+}
+
+// How a todo repeats, on local dates. A recurring todo always has a due date; the
+// next instance is created when it is completed or once its day has passed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Recurrence {
+    Daily,
+    Weekdays, // Monday to Friday
+    Weekly { days: Vec<Weekday> },
+    EveryNDays { interval: u32 },
+    MonthlyByDay { day: u32 }, // the last day of shorter months
+}
+
+// What happens to the subtasks of a deleted todo
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtaskPolicy {
+    Delete,
+    Promote, // move them up to the deleted todo's parent
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -48,10 +94,429 @@ pub struct Project {
     pub name: String,
     pub description: Option<String>,
     pub created_at: String,
+    pub color: Option<String>, // #rrggbb
+    pub icon: Option<String>, // an emoji or icon name
+    pub archived_at: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateProject {
     pub name: String,
     pub description: Option<String>,
-} 
\ No newline at end of file
+}
+
+// Fields left out stay as they are; an empty description, color or icon clears it
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct UpdateProject {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub color: Option<String>, // #rgb or #rrggbb
+    pub icon: Option<String>,
+    pub archived: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionOutcome {
+    Completed,
+    Skipped,
+    Abandoned,
+}
+
+impl SessionOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionOutcome::Completed => "completed",
+            SessionOutcome::Skipped => "skipped",
+            SessionOutcome::Abandoned => "abandoned",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "completed" => Some(SessionOutcome::Completed),
+            "skipped" => Some(SessionOutcome::Skipped),
+            "abandoned" => Some(SessionOutcome::Abandoned),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FocusSession {
+    pub id: i64,
+    pub todo_id: i64,
+    pub project_id: i64,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    pub paused_seconds: i64,
+    pub outcome: Option<SessionOutcome>, // None while the session is still open
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TodoReport {
+    pub todo_id: i64,
+    pub text: String,
+    pub completed: bool,
+    pub estimated_minutes: i32,
+    pub actual_minutes: f64,
+    pub session_count: i64,
+    pub overrun_ratio: Option<f64>, // actual / estimated, None when untracked or unestimated
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProjectReport {
+    pub project_id: i64,
+    pub project_name: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub todos: Vec<TodoReport>,
+    pub total_todos: i64,
+    pub completed_todos: i64,
+    pub session_count: i64,
+    pub estimated_minutes: i64,
+    pub actual_minutes: f64,
+    pub overrun_ratio: Option<f64>,
+    pub accuracy_score: Option<f64>, // 0.0-1.0 over completed, tracked todos
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupSession {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    pub paused_seconds: i64,
+    pub outcome: Option<SessionOutcome>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupTodo {
+    pub id: i64,
+    pub text: String,
+    pub completed: bool,
+    pub time: i32,
+    pub created_at: String,
+    #[serde(default)]
+    pub parent_id: Option<i64>, // `id` of the parent within the same backup
+    #[serde(default)]
+    pub position: Option<f64>,
+    #[serde(default)]
+    pub priority: Option<u8>,
+    #[serde(default)]
+    pub due_at: Option<String>,
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub sessions: Vec<BackupSession>,
+    #[serde(default)]
+    pub deleted_at: Option<String>, // set while the todo is in the trash
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupScene {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub elements: String,
+    pub app_state: String,
+    pub updated_at: String,
+    #[serde(default)]
+    pub deleted_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupProject {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: String,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub archived_at: Option<String>,
+    pub deleted_at: Option<String>,
+    #[serde(default)]
+    pub todos: Vec<BackupTodo>,
+    pub excalidraw: Option<BackupScene>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupTag {
+    pub id: i64,
+    pub name: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupTemplateTodo {
+    pub id: i64,
+    pub parent_id: Option<i64>,
+    pub text: String,
+    pub time: i64,
+    pub position: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupTemplate {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub elements: Option<String>, // starter whiteboard, None for none
+    pub app_state: Option<String>,
+    pub created_at: String,
+    pub todos: Vec<BackupTemplateTodo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupSetting {
+    pub key: String,
+    pub value: String, // JSON, as stored
+    pub updated_at: String,
+}
+
+// Versioned JSON document written by export_backup, trash included. Replacing all
+// data restores every record with its ID; merging assigns fresh ones and leaves
+// the trash out.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupDocument {
+    pub format: String,
+    pub version: u32,
+    pub schema_version: i64,
+    pub exported_at: String,
+    pub projects: Vec<BackupProject>,
+    #[serde(default)]
+    pub tags: Vec<BackupTag>, // every tag, including unused ones
+    #[serde(default)]
+    pub templates: Vec<BackupTemplate>,
+    #[serde(default)]
+    pub settings: Option<Vec<BackupSetting>>, // None in backups made before settings were kept
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    ReplaceAll,
+    MergeAsNewProjects,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BackupSummary {
+    pub projects: usize,
+    pub todos: usize,
+    pub scenes: usize,
+    pub sessions: usize,
+    pub templates: usize,
+}
+
+// How often automatic snapshots are taken and how many are kept per bucket
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotPolicy {
+    pub interval_minutes: u32, // 0 disables automatic snapshots
+    pub keep_hourly: u32,
+    pub keep_daily: u32,
+    pub keep_weekly: u32,
+}
+
+impl Default for SnapshotPolicy {
+    fn default() -> Self {
+        Self {
+            interval_minutes: 60,
+            keep_hourly: 24,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub created_at: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MarkdownExportOptions {
+    pub include_completed: bool,
+    pub include_time_summary: bool,
+    pub include_board: bool,
+}
+
+impl Default for MarkdownExportOptions {
+    fn default() -> Self {
+        Self {
+            include_completed: true,
+            include_time_summary: false,
+            include_board: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MarkdownImportItem {
+    pub line: usize, // 1-based line in the pasted text
+    pub depth: usize,
+    pub text: String,
+    pub time: i32,
+    pub completed: bool,
+    pub duplicate: bool, // same text already exists in the project; skipped on commit
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MarkdownImportResult {
+    pub items: Vec<MarkdownImportItem>,
+    pub committed: bool,
+    pub created: Vec<Todo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TodoTxtExportSummary {
+    pub files: Vec<String>,
+    pub todos: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TodoTxtImportSummary {
+    pub todos: usize,
+    pub created_projects: Vec<Project>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CsvScope {
+    Todos,
+    Sessions,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CsvExportSummary {
+    pub path: String,
+    pub rows: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct IcsExportOptions {
+    pub break_minutes: u32, // gap between consecutive focus blocks
+    pub include_tasks: bool, // also write a VTODO per todo
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchKind {
+    Todo,
+    Project,
+    Whiteboard, // text on a project's Excalidraw board
+}
+
+impl SearchKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchKind::Todo => "todo",
+            SearchKind::Project => "project",
+            SearchKind::Whiteboard => "whiteboard",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "todo" => Some(SearchKind::Todo),
+            "project" => Some(SearchKind::Project),
+            "whiteboard" => Some(SearchKind::Whiteboard),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SearchFilters {
+    pub project_id: Option<i64>,
+    pub kinds: Vec<SearchKind>, // empty searches everything
+    pub exclude_completed: bool, // leave out completed todos
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchHit {
+    pub kind: SearchKind,
+    pub id: i64, // todo, project or excalidraw_data id depending on `kind`
+    pub project_id: i64,
+    pub project_name: String,
+    pub title: String,
+    pub snippet: String, // HTML-escaped, with matches wrapped in <mark>
+    pub completed: Option<bool>, // todos only
+    pub rank: f64, // lower is a better match
+}
+
+// What a trash entry restores: a project with its todos and whiteboard, or a todo
+// with the subtasks deleted along with it
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashKind {
+    Project,
+    Todo,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrashItem {
+    pub kind: TrashKind,
+    pub id: i64,
+    pub title: String, // project name or todo text
+    pub project_id: i64,
+    pub project_name: String,
+    pub todo_count: i64, // todos that come back on restore, the todo itself included
+    pub deleted_at: String, // UTC
+    pub purge_at: String, // UTC; when the scheduler deletes it for good
+}
+
+// How long deleted items stay in the trash
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct TrashPolicy {
+    pub retention_days: u32,
+}
+
+impl Default for TrashPolicy {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
+// A journaled operation that can be undone or redone
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub label: String, // e.g. "Delete project"
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UndoHistory {
+    pub undo: Vec<HistoryEntry>, // newest first; `undo` reverts the first one
+    pub redo: Vec<HistoryEntry>, // newest first; `redo` reapplies the first one
+}
+
+// A saved project layout that new projects can start from
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProjectTemplate {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub todo_count: i64,
+    pub has_whiteboard: bool,
+    pub placeholders: Vec<String>, // {{names}} to fill in, besides the built-in `date`
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SaveTemplateOptions {
+    pub name: Option<String>, // defaults to the project's name
+    pub include_whiteboard: bool, // keep the project's whiteboard as the starter scene
+}
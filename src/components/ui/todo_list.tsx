@@ -34,13 +34,14 @@ const TodoList = ({
       {/* Todo Items */}
       <div className="space-y-2">
         {todos.map(todo => (
-          <TodoItem
-            key={todo.id}
-            todo={todo}
-            onDelete={onDeleteTodo}
-            onToggle={onToggleTodo}
-            onUpdate={onUpdateTodo}
-          />
+          <div key={todo.id} style={{ marginLeft: (todo.depth ?? 0) * 16 }}>
+            <TodoItem
+              todo={todo}
+              onDelete={onDeleteTodo}
+              onToggle={onToggleTodo}
+              onUpdate={onUpdateTodo}
+            />
+          </div>
         ))}
       </div>
 
@@ -1,6 +1,7 @@
 import { useState, useEffect, useCallback } from "react";
 import { appWindow } from "@tauri-apps/api/window";
 import { apiService } from "../services/api";
+import { getErrorMessage } from "../utils/errorUtils";
 import { useProjects } from "./useProjects";
 import { useTodos } from "./useTodos";
 import { useTimer } from "./useTimer";
@@ -36,6 +37,10 @@ export const useApp = () => {
     updateTodo
   } = useTodos();
 
+  const reloadTodos = useCallback(() => {
+    loadTodos(currentProject);
+  }, [loadTodos, currentProject]);
+
   const {
     focusedTodo,
     remainingTime,
@@ -47,7 +52,7 @@ export const useApp = () => {
     getFirstIncompleteTodo,
     hasIncompleteTodos,
     resetTimer
-  } = useTimer(todos, toggleTodo);
+  } = useTimer(todos, currentProject, reloadTodos);
 
   const {
     excalidrawAPI,
@@ -73,7 +78,7 @@ export const useApp = () => {
         await loadProjects();
         setError(null);
       } catch (error) {
-        setError(`Failed to initialize app: ${error}`);
+        setError(`Failed to initialize app: ${getErrorMessage(error)}`);
       } finally {
         setIsLoading(false);
       }
@@ -114,7 +119,7 @@ export const useApp = () => {
     const firstIncompleteTodo = getFirstIncompleteTodo();
     if (firstIncompleteTodo) {
       await saveCurrentData();
-      handleFocus(firstIncompleteTodo);
+      await handleFocus(firstIncompleteTodo);
       setIsCollapsed(true);
     }
   }, [getFirstIncompleteTodo, saveCurrentData, handleFocus]);
@@ -137,25 +142,29 @@ export const useApp = () => {
     updateTodo(id, newText, newTime);
   }, [updateTodo]);
 
-  const handleTimerAction = useCallback((action: 'skip' | 'pause' | 'done') => {
+  const handleTimerAction = useCallback(async (action: 'skip' | 'pause' | 'done') => {
     let result;
-    switch (action) {
-      case 'skip':
-        result = handleSkip();
-        break;
-      case 'pause':
-        handlePause();
-        break;
-      case 'done':
-        result = handleDone();
-        break;
+    try {
+      switch (action) {
+        case 'skip':
+          result = await handleSkip();
+          break;
+        case 'pause':
+          await handlePause();
+          break;
+        case 'done':
+          result = await handleDone();
+          break;
+      }
+    } catch (error) {
+      setError(`Focus timer error: ${getErrorMessage(error)}`);
     }
     
     // If timer actions return 'exit_focus', exit focus mode
     if (result === 'exit_focus') {
       handleCollapse();
     }
-  }, [handleSkip, handlePause, handleDone, handleCollapse]);
+  }, [handleSkip, handlePause, handleDone, handleCollapse, setError]);
 
   return {
     // State
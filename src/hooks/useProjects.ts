@@ -1,6 +1,7 @@
 import { useState, useCallback } from "react";
 import { Project } from "../types";
 import { apiService } from "../services/api";
+import { getErrorMessage } from "../utils/errorUtils";
 
 export const useProjects = () => {
   const [projects, setProjects] = useState<Project[]>([]);
@@ -19,7 +20,7 @@ export const useProjects = () => {
       
       setError(null);
     } catch (error) {
-      setError(`Failed to load projects: ${error}`);
+      setError(`Failed to load projects: ${getErrorMessage(error)}`);
     }
   }, [currentProject]);
 
@@ -31,7 +32,7 @@ export const useProjects = () => {
       setError(null);
       return newProject;
     } catch (error) {
-      setError(`Failed to create project: ${error}`);
+      setError(`Failed to create project: ${getErrorMessage(error)}`);
       throw error;
     }
   }, [loadProjects]);
@@ -51,7 +52,7 @@ export const useProjects = () => {
       
       setError(null);
     } catch (error) {
-      setError(`Failed to delete project: ${error}`);
+      setError(`Failed to delete project: ${getErrorMessage(error)}`);
       throw error;
     }
   }, [loadProjects, projects]);
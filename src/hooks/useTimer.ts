@@ -1,94 +1,63 @@
 import { useState, useEffect, useCallback } from "react";
-import { Todo } from "../types";
-import { minutesToSeconds } from "../utils/timeUtils";
+import { listen } from "@tauri-apps/api/event";
+import { Todo, Project, TimerSnapshot } from "../types";
+import { apiService } from "../services/api";
 
-export const useTimer = (todos: Todo[], onTodoToggle: (id: number) => void) => {
-  const [focusedTodo, setFocusedTodo] = useState<Todo | null>(null);
-  const [remainingTime, setRemainingTime] = useState<number>(0); // in seconds
-  const [isPaused, setIsPaused] = useState(false);
+const idleSnapshot: TimerSnapshot = {
+  phase: 'idle',
+  project_id: null,
+  todo: null,
+  remaining_seconds: 0,
+  total_seconds: 0,
+  break_minutes: 0
+};
 
-  // Timer effect
+// The countdown itself runs in the Rust backend; this hook mirrors its state
+export const useTimer = (todos: Todo[], currentProject: Project | null, onTodosChanged: () => void) => {
+  const [snapshot, setSnapshot] = useState<TimerSnapshot>(idleSnapshot);
+
+  // Follow backend timer events and pick up a session that survived a reload
   useEffect(() => {
-    let interval: NodeJS.Timeout;
+    const unlistenTick = listen<TimerSnapshot>('timer://tick', event => setSnapshot(event.payload));
+    const unlistenState = listen<TimerSnapshot>('timer://state', event => setSnapshot(event.payload));
 
-    if (focusedTodo && remainingTime > 0 && !isPaused) {
-      interval = setInterval(() => {
-        setRemainingTime(prev => {
-          if (prev <= 1) {
-            // Timer finished - automatically move to next task
-            handleSkip();
-            return 0;
-          }
-          return prev - 1;
-        });
-      }, 1000);
-    }
+    apiService.getFocusState().then(setSnapshot).catch(() => setSnapshot(idleSnapshot));
 
     return () => {
-      if (interval) {
-        clearInterval(interval);
-      }
+      unlistenTick.then(unlisten => unlisten());
+      unlistenState.then(unlisten => unlisten());
     };
-  }, [focusedTodo, remainingTime, isPaused]);
-
-  const handleFocus = useCallback((todo: Todo) => {
-    setFocusedTodo(todo);
-    setRemainingTime(minutesToSeconds(todo.time));
-    setIsPaused(false);
   }, []);
 
-  const handleSkip = useCallback(() => {
-    // Find the next incomplete task after the current focused task
-    const incompleteTodos = todos.filter(todo => !todo.completed);
-    const currentIndex = incompleteTodos.findIndex(todo => todo.id === focusedTodo?.id);
-
-    if (currentIndex < incompleteTodos.length - 1) {
-      // Move to next task
-      const nextTodo = incompleteTodos[currentIndex + 1];
-      setFocusedTodo(nextTodo);
-      setRemainingTime(minutesToSeconds(nextTodo.time));
-      setIsPaused(false);
-    } else if (incompleteTodos.length > 1) {
-      // If at the end, go back to first task
-      const firstTodo = incompleteTodos[0];
-      setFocusedTodo(firstTodo);
-      setRemainingTime(minutesToSeconds(firstTodo.time));
-      setIsPaused(false);
-    } else {
-      // No more tasks, clear focus
-      setFocusedTodo(null);
-      setRemainingTime(0);
-      setIsPaused(false);
+  const exitIfIdle = (next: TimerSnapshot) => {
+    setSnapshot(next);
+    if (next.phase === 'idle') {
       return 'exit_focus'; // Signal to exit focus mode
     }
-  }, [todos, focusedTodo]);
-
-  const handlePause = useCallback(() => {
-    setIsPaused(!isPaused);
-  }, [isPaused]);
+  };
 
-  const handleDone = useCallback(() => {
-    if (focusedTodo) {
-      // Mark current task as completed
-      onTodoToggle(focusedTodo.id);
+  const handleFocus = useCallback(async (todo: Todo) => {
+    if (!currentProject) return;
+    setSnapshot(await apiService.startFocus(currentProject.id, todo.id));
+  }, [currentProject]);
 
-      // Move to next incomplete task
-      const incompleteTodos = todos.filter(todo => !todo.completed && todo.id !== focusedTodo.id);
+  const handleSkip = useCallback(async () => {
+    return exitIfIdle(await apiService.skipFocus());
+  }, []);
 
-      if (incompleteTodos.length > 0) {
-        const nextTodo = incompleteTodos[0];
-        setFocusedTodo(nextTodo);
-        setRemainingTime(minutesToSeconds(nextTodo.time));
-        setIsPaused(false);
-      } else {
-        // No more incomplete tasks, clear focus
-        setFocusedTodo(null);
-        setRemainingTime(0);
-        setIsPaused(false);
-        return 'exit_focus'; // Signal to exit focus mode
-      }
+  const handlePause = useCallback(async () => {
+    if (snapshot.phase === 'paused') {
+      setSnapshot(await apiService.resumeFocus());
+    } else if (snapshot.phase === 'running') {
+      setSnapshot(await apiService.pauseFocus());
     }
-  }, [focusedTodo, todos, onTodoToggle]);
+  }, [snapshot.phase]);
+
+  const handleDone = useCallback(async () => {
+    const next = await apiService.completeFocus();
+    onTodosChanged();
+    return exitIfIdle(next);
+  }, [onTodosChanged]);
 
   const getFirstIncompleteTodo = useCallback(() => {
     return todos.find(todo => !todo.completed) || null;
@@ -98,16 +67,14 @@ export const useTimer = (todos: Todo[], onTodoToggle: (id: number) => void) => {
     return todos.some(todo => !todo.completed);
   }, [todos]);
 
-  const resetTimer = useCallback(() => {
-    setFocusedTodo(null);
-    setRemainingTime(0);
-    setIsPaused(false);
+  const resetTimer = useCallback(async () => {
+    setSnapshot(await apiService.stopFocus());
   }, []);
 
   return {
-    focusedTodo,
-    remainingTime,
-    isPaused,
+    focusedTodo: snapshot.todo,
+    remainingTime: snapshot.remaining_seconds,
+    isPaused: snapshot.phase === 'paused',
     handleFocus,
     handleSkip,
     handlePause,
@@ -116,4 +83,4 @@ export const useTimer = (todos: Todo[], onTodoToggle: (id: number) => void) => {
     hasIncompleteTodos,
     resetTimer
   };
-}; 
\ No newline at end of file
+};
@@ -1,6 +1,7 @@
 import { useState, useCallback } from "react";
 import { Todo, Project } from "../types";
 import { apiService } from "../services/api";
+import { getErrorMessage } from "../utils/errorUtils";
 
 export const useTodos = () => {
   const [todos, setTodos] = useState<Todo[]>([]);
@@ -14,7 +15,7 @@ export const useTodos = () => {
       setTodos(dbTodos);
       setError(null);
     } catch (error) {
-      setError(`Failed to load todos: ${error}`);
+      setError(`Failed to load todos: ${getErrorMessage(error)}`);
     }
   }, []);
 
@@ -25,7 +26,7 @@ export const useTodos = () => {
         await loadTodos(currentProject);
         setError(null);
       } catch (error) {
-        setError(`Failed to create todo: ${error}`);
+        setError(`Failed to create todo: ${getErrorMessage(error)}`);
       }
     }
   }, [loadTodos]);
@@ -36,7 +37,7 @@ export const useTodos = () => {
       setTodos(todos => todos.filter(todo => todo.id !== id));
       setError(null);
     } catch (error) {
-      setError(`Failed to delete todo: ${error}`);
+      setError(`Failed to delete todo: ${getErrorMessage(error)}`);
     }
   }, []);
 
@@ -49,7 +50,7 @@ export const useTodos = () => {
         setError(null);
       }
     } catch (error) {
-      setError(`Failed to toggle todo: ${error}`);
+      setError(`Failed to toggle todo: ${getErrorMessage(error)}`);
     }
   }, [todos]);
 
@@ -64,7 +65,7 @@ export const useTodos = () => {
       setTodos(todos => todos.map(todo => todo.id === id ? updatedTodo : todo));
       setError(null);
     } catch (error) {
-      setError(`Failed to update todo: ${error}`);
+      setError(`Failed to update todo: ${getErrorMessage(error)}`);
     }
   }, []);
 
@@ -1,5 +1,5 @@
 import { invoke } from "@tauri-apps/api/tauri";
-import { Todo, Project } from "../types";
+import { Todo, Project, UpdateProject, TimerSnapshot, FocusSession, ProjectReport, ImportMode, BackupSummary, SnapshotInfo, SnapshotPolicy, MarkdownExportOptions, MarkdownImportResult, TodoTxtExportSummary, TodoTxtImportSummary, CsvScope, CsvExportSummary, IcsExportOptions, SubtaskPolicy, Recurrence, Tag, SearchFilters, SearchHit, TrashItem, TrashKind, TrashPolicy, HistoryEntry, UndoHistory, ProjectTemplate, SaveTemplateOptions } from "../types";
 
 export const apiService = {
   // Database initialization
@@ -8,37 +8,132 @@ export const apiService = {
   },
 
   // Project operations
-  getProjects: async (): Promise<Project[]> => {
-    return await invoke<Project[]>('get_projects');
+  getProjects: async (includeArchived?: boolean): Promise<Project[]> => {
+    return await invoke<Project[]>('get_projects', { includeArchived });
   },
 
   createProject: async (project: { name: string; description?: string }): Promise<Project> => {
     return await invoke<Project>('create_project', { project });
   },
 
+  updateProject: async (id: number, update: UpdateProject): Promise<Project> => {
+    return await invoke<Project>('update_project', { id, update });
+  },
+
+  archiveProject: async (id: number): Promise<Project> => {
+    return await invoke<Project>('archive_project', { id });
+  },
+
+  unarchiveProject: async (id: number): Promise<Project> => {
+    return await invoke<Project>('unarchive_project', { id });
+  },
+
   deleteProject: async (id: number): Promise<void> => {
     return await invoke('delete_project', { id });
   },
 
   // Todo operations
-  getTodos: async (projectId: number): Promise<Todo[]> => {
-    return await invoke<Todo[]>('get_todos', { projectId });
+  // With a tag, only todos carrying it; across all projects when projectId is omitted
+  getTodos: async (projectId?: number, tag?: string): Promise<Todo[]> => {
+    return await invoke<Todo[]>('get_todos', { projectId, tag });
   },
 
-  createTodo: async (text: string, time: number, projectId: number): Promise<Todo> => {
+  createTodo: async (text: string, time: number, projectId: number, parentId?: number): Promise<Todo> => {
     return await invoke<Todo>('create_todo', {
       text: text.trim(),
       time,
-      projectId
+      projectId,
+      parentId
     });
   },
 
-  updateTodo: async (id: number, update: Partial<Todo>): Promise<Todo> => {
+  updateTodo: async (id: number, update: Partial<Todo> & { complete_children?: boolean }): Promise<Todo> => {
     return await invoke<Todo>('update_todo', { id, update });
   },
 
-  deleteTodo: async (id: number): Promise<void> => {
-    return await invoke('delete_todo', { id });
+  deleteTodo: async (id: number, subtasks?: SubtaskPolicy): Promise<void> => {
+    return await invoke('delete_todo', { id, subtasks });
+  },
+
+  // Place a todo in front of beforeId and/or after afterId; resolves to the reordered list
+  reorderTodo: async (id: number, beforeId?: number, afterId?: number): Promise<Todo[]> => {
+    return await invoke<Todo[]>('reorder_todo', { id, beforeId, afterId });
+  },
+
+  moveTodos: async (ids: number[], targetProjectId?: number, beforeId?: number, afterId?: number): Promise<Todo[]> => {
+    return await invoke<Todo[]>('move_todos', { ids, targetProjectId, beforeId, afterId });
+  },
+
+  copyTodos: async (ids: number[], targetProjectId: number): Promise<Todo[]> => {
+    return await invoke<Todo[]>('copy_todos', { ids, targetProjectId });
+  },
+
+  setTodoRecurrence: async (id: number, rule: Recurrence | null): Promise<Todo> => {
+    return await invoke<Todo>('set_todo_recurrence', { id, rule });
+  },
+
+  listTags: async (): Promise<Tag[]> => {
+    return await invoke<Tag[]>('list_tags');
+  },
+
+  addTag: async (todoId: number, name: string): Promise<Todo> => {
+    return await invoke<Todo>('add_tag', { todoId, name });
+  },
+
+  removeTag: async (todoId: number, name: string): Promise<Todo> => {
+    return await invoke<Todo>('remove_tag', { todoId, name });
+  },
+
+  renameTag: async (id: number, name: string): Promise<Tag> => {
+    return await invoke<Tag>('rename_tag', { id, name });
+  },
+
+  mergeTags: async (sourceIds: number[], targetId: number): Promise<Tag> => {
+    return await invoke<Tag>('merge_tags', { sourceIds, targetId });
+  },
+
+  deleteTag: async (id: number): Promise<void> => {
+    return await invoke<void>('delete_tag', { id });
+  },
+
+  nextFocusCandidate: async (projectId: number): Promise<Todo | null> => {
+    return await invoke<Todo | null>('next_focus_candidate', { projectId });
+  },
+
+  // Search
+  search: async (query: string, filters?: SearchFilters): Promise<SearchHit[]> => {
+    return await invoke<SearchHit[]>('search', { query, filters });
+  },
+
+  // Undo/redo of todo, project and whiteboard changes; null when there is nothing to undo or redo
+  undo: async (): Promise<HistoryEntry | null> => {
+    return await invoke<HistoryEntry | null>('undo');
+  },
+
+  redo: async (): Promise<HistoryEntry | null> => {
+    return await invoke<HistoryEntry | null>('redo');
+  },
+
+  getUndoHistory: async (): Promise<UndoHistory> => {
+    return await invoke<UndoHistory>('get_undo_history');
+  },
+
+  // Project templates
+  listTemplates: async (): Promise<ProjectTemplate[]> => {
+    return await invoke<ProjectTemplate[]>('list_templates');
+  },
+
+  saveProjectAsTemplate: async (projectId: number, options?: SaveTemplateOptions): Promise<ProjectTemplate> => {
+    return await invoke<ProjectTemplate>('save_project_as_template', { projectId, options });
+  },
+
+  // `values` fills the template's {{placeholders}}; `date` defaults to today
+  createProjectFromTemplate: async (templateId: number, name: string, values?: Record<string, string>): Promise<Project> => {
+    return await invoke<Project>('create_project_from_template', { templateId, name, values });
+  },
+
+  deleteTemplate: async (id: number): Promise<void> => {
+    return await invoke('delete_template', { id });
   },
 
   // Excalidraw operations
@@ -52,5 +147,125 @@ export const apiService = {
 
   getExcalidrawData: async (projectId: number): Promise<any> => {
     return await invoke<any>('get_excalidraw_data', { projectId });
+  },
+
+  // Focus timer operations
+  getFocusState: async (): Promise<TimerSnapshot> => {
+    return await invoke<TimerSnapshot>('get_focus_state');
+  },
+
+  startFocus: async (projectId: number, todoId?: number, breakMinutes?: number): Promise<TimerSnapshot> => {
+    return await invoke<TimerSnapshot>('start_focus', { projectId, todoId, breakMinutes });
+  },
+
+  pauseFocus: async (): Promise<TimerSnapshot> => {
+    return await invoke<TimerSnapshot>('pause_focus');
+  },
+
+  resumeFocus: async (): Promise<TimerSnapshot> => {
+    return await invoke<TimerSnapshot>('resume_focus');
+  },
+
+  skipFocus: async (): Promise<TimerSnapshot> => {
+    return await invoke<TimerSnapshot>('skip_focus');
+  },
+
+  completeFocus: async (): Promise<TimerSnapshot> => {
+    return await invoke<TimerSnapshot>('complete_focus');
+  },
+
+  stopFocus: async (): Promise<TimerSnapshot> => {
+    return await invoke<TimerSnapshot>('stop_focus');
+  },
+
+  // Focus session log
+  listSessionsByTodo: async (todoId: number): Promise<FocusSession[]> => {
+    return await invoke<FocusSession[]>('list_sessions_by_todo', { todoId });
+  },
+
+  listSessionsByProject: async (projectId: number): Promise<FocusSession[]> => {
+    return await invoke<FocusSession[]>('list_sessions_by_project', { projectId });
+  },
+
+  listSessionsByDateRange: async (from: string, to: string, projectId?: number): Promise<FocusSession[]> => {
+    return await invoke<FocusSession[]>('list_sessions_by_date_range', { from, to, projectId });
+  },
+
+  // Reports
+  getProjectReport: async (projectId: number, from?: string, to?: string): Promise<ProjectReport> => {
+    return await invoke<ProjectReport>('get_project_report', { projectId, from, to });
+  },
+
+  // Backup and restore
+  exportBackup: async (path: string): Promise<BackupSummary> => {
+    return await invoke<BackupSummary>('export_backup', { path });
+  },
+
+  importBackup: async (path: string, mode: ImportMode): Promise<BackupSummary> => {
+    return await invoke<BackupSummary>('import_backup', { path, mode });
+  },
+
+  // Automatic database snapshots
+  listBackups: async (): Promise<SnapshotInfo[]> => {
+    return await invoke<SnapshotInfo[]>('list_backups');
+  },
+
+  createBackup: async (): Promise<SnapshotInfo> => {
+    return await invoke<SnapshotInfo>('create_backup');
+  },
+
+  restoreBackup: async (id: string): Promise<SnapshotInfo> => {
+    return await invoke<SnapshotInfo>('restore_backup', { id });
+  },
+
+  getSnapshotPolicy: async (): Promise<SnapshotPolicy> => {
+    return await invoke<SnapshotPolicy>('get_snapshot_policy');
+  },
+
+  setSnapshotPolicy: async (policy: SnapshotPolicy): Promise<SnapshotPolicy> => {
+    return await invoke<SnapshotPolicy>('set_snapshot_policy', { policy });
+  },
+
+  // Trash; deleted projects and todos stay here until purged
+  listTrash: async (): Promise<TrashItem[]> => {
+    return await invoke<TrashItem[]>('list_trash');
+  },
+
+  restoreFromTrash: async (kind: TrashKind, id: number): Promise<void> => {
+    return await invoke('restore_from_trash', { kind, id });
+  },
+
+  getTrashPolicy: async (): Promise<TrashPolicy> => {
+    return await invoke<TrashPolicy>('get_trash_policy');
+  },
+
+  setTrashPolicy: async (policy: TrashPolicy): Promise<TrashPolicy> => {
+    return await invoke<TrashPolicy>('set_trash_policy', { policy });
+  },
+
+  // Exports
+  exportProjectMarkdown: async (projectId: number, options?: MarkdownExportOptions): Promise<string> => {
+    return await invoke<string>('export_project_markdown', { projectId, options });
+  },
+
+  exportTodoTxt: async (path: string, perProject?: boolean): Promise<TodoTxtExportSummary> => {
+    return await invoke<TodoTxtExportSummary>('export_todotxt', { path, perProject });
+  },
+
+  exportCsv: async (scope: CsvScope, path: string, delimiter?: string): Promise<CsvExportSummary> => {
+    return await invoke<CsvExportSummary>('export_csv', { scope, path, delimiter });
+  },
+
+  exportIcs: async (projectId: number, startAt: string, options?: IcsExportOptions): Promise<string> => {
+    return await invoke<string>('export_ics', { projectId, startAt, options });
+  },
+
+  // Imports
+  importMarkdown: async (projectId: number, text: string, commit?: boolean): Promise<MarkdownImportResult> => {
+    return await invoke<MarkdownImportResult>('import_markdown', { projectId, text, commit });
+  },
+
+  importTodoTxt: async (path: string, defaultProjectId?: number): Promise<TodoTxtImportSummary> => {
+    return await invoke<TodoTxtImportSummary>('import_todotxt', { path, defaultProjectId });
   }
 }; 
\ No newline at end of file
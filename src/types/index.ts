@@ -3,6 +3,18 @@ export interface Project {
   name: string;
   description?: string;
   created_at: string;
+  color?: string; // #rrggbb
+  icon?: string; // an emoji or icon name
+  archived_at?: string;
+}
+
+// Fields left out stay as they are; an empty description, color or icon clears it
+export interface UpdateProject {
+  name?: string;
+  description?: string;
+  color?: string; // #rgb or #rrggbb
+  icon?: string;
+  archived?: boolean;
 }
 
 export interface Todo {
@@ -12,6 +24,226 @@ export interface Todo {
   time: number; // time in minutes
   created_at?: string;
   project_id: number;
+  parent_id?: number | null;
+  priority?: number; // 0 (P0, most urgent) to 3 (P3)
+  due_at?: string | null; // UTC "YYYY-MM-DD HH:MM:SS"; send "" to clear it
+  recurrence?: Recurrence | null;
+  tags?: string[]; // tag names, alphabetical
+  position?: number; // manual order within the project
+  depth?: number; // 0 for top-level todos
+  total_time?: number; // own time plus the subtasks' totals
+}
+
+export interface Tag {
+  id: number;
+  name: string;
+  created_at: string;
+  todo_count: number;
+}
+
+export type Weekday = 'Mon' | 'Tue' | 'Wed' | 'Thu' | 'Fri' | 'Sat' | 'Sun';
+
+// How a todo repeats; the next instance appears when it is completed or its day has passed
+export type Recurrence =
+  | { kind: 'daily' }
+  | { kind: 'weekdays' }
+  | { kind: 'weekly'; days: Weekday[] }
+  | { kind: 'every_n_days'; interval: number }
+  | { kind: 'monthly_by_day'; day: number }; // the last day of shorter months
+
+export type SaveStatus = 'idle' | 'saving' | 'success' | 'error'; 
+export type ApiErrorKind =
+  | 'not_found'
+  | 'conflict'
+  | 'validation'
+  | 'database'
+  | 'io'
+  | 'not_initialized';
+
+export interface ApiError {
+  kind: ApiErrorKind;
+  message: string;
+  details?: string | null;
+}
+
+export type TimerPhase = 'idle' | 'running' | 'paused' | 'break';
+
+export interface TimerSnapshot {
+  phase: TimerPhase;
+  project_id: number | null;
+  todo: Todo | null;
+  remaining_seconds: number;
+  total_seconds: number;
+  break_minutes: number;
+}
+
+export type SessionOutcome = 'completed' | 'skipped' | 'abandoned';
+
+export interface FocusSession {
+  id: number;
+  todo_id: number;
+  project_id: number;
+  started_at: string;
+  ended_at: string | null;
+  paused_seconds: number;
+  outcome: SessionOutcome | null;
 }
 
-export type SaveStatus = 'idle' | 'saving' | 'success' | 'error'; 
\ No newline at end of file
+export interface TodoReport {
+  todo_id: number;
+  text: string;
+  completed: boolean;
+  estimated_minutes: number;
+  actual_minutes: number;
+  session_count: number;
+  overrun_ratio: number | null;
+}
+
+export interface ProjectReport {
+  project_id: number;
+  project_name: string;
+  from: string | null;
+  to: string | null;
+  todos: TodoReport[];
+  total_todos: number;
+  completed_todos: number;
+  session_count: number;
+  estimated_minutes: number;
+  actual_minutes: number;
+  overrun_ratio: number | null;
+  accuracy_score: number | null;
+}
+
+export type ImportMode = 'replace_all' | 'merge_as_new_projects';
+
+export interface BackupSummary {
+  projects: number;
+  todos: number;
+  scenes: number;
+  sessions: number;
+  templates: number;
+}
+
+export interface SnapshotPolicy {
+  interval_minutes: number;
+  keep_hourly: number;
+  keep_daily: number;
+  keep_weekly: number;
+}
+
+export interface SnapshotInfo {
+  id: string;
+  created_at: string;
+  size_bytes: number;
+}
+
+export interface MarkdownExportOptions {
+  include_completed?: boolean;
+  include_time_summary?: boolean;
+  include_board?: boolean;
+}
+
+export interface MarkdownImportItem {
+  line: number;
+  depth: number;
+  text: string;
+  time: number;
+  completed: boolean;
+  duplicate: boolean;
+}
+
+export interface MarkdownImportResult {
+  items: MarkdownImportItem[];
+  committed: boolean;
+  created: Todo[];
+}
+
+export interface TodoTxtExportSummary {
+  files: string[];
+  todos: number;
+}
+
+export interface TodoTxtImportSummary {
+  todos: number;
+  created_projects: Project[];
+}
+
+export type CsvScope = 'todos' | 'sessions';
+
+export interface CsvExportSummary {
+  path: string;
+  rows: number;
+}
+
+export interface IcsExportOptions {
+  break_minutes?: number;
+  include_tasks?: boolean;
+}
+
+export type SubtaskPolicy = 'delete' | 'promote';
+
+export type SearchKind = 'todo' | 'project' | 'whiteboard';
+
+export interface SearchFilters {
+  project_id?: number;
+  kinds?: SearchKind[];
+  exclude_completed?: boolean;
+  limit?: number;
+}
+
+export interface SearchHit {
+  kind: SearchKind;
+  id: number;
+  project_id: number;
+  project_name: string;
+  title: string;
+  snippet: string; // HTML-escaped, matches wrapped in <mark>
+  completed: boolean | null;
+  rank: number;
+}
+
+export type TrashKind = 'project' | 'todo';
+
+export interface TrashItem {
+  kind: TrashKind;
+  id: number;
+  title: string;
+  project_id: number;
+  project_name: string;
+  todo_count: number; // todos that come back on restore
+  deleted_at: string; // UTC
+  purge_at: string; // UTC
+}
+
+export interface TrashPolicy {
+  retention_days: number;
+}
+
+export interface HistoryEntry {
+  id: number;
+  label: string;
+  created_at: string;
+}
+
+export interface UndoHistory {
+  undo: HistoryEntry[]; // newest first
+  redo: HistoryEntry[]; // newest first
+}
+
+// A saved project layout that new projects can start from
+export interface ProjectTemplate {
+  id: number;
+  name: string;
+  description?: string;
+  color?: string;
+  icon?: string;
+  todo_count: number;
+  has_whiteboard: boolean;
+  placeholders: string[]; // {{names}} to fill in, besides the built-in `date`
+  created_at: string;
+}
+
+export interface SaveTemplateOptions {
+  name?: string; // defaults to the project's name
+  include_whiteboard?: boolean;
+}
@@ -0,0 +1,7 @@
+import { ApiError } from "../types";
+
+export const isApiError = (error: unknown): error is ApiError =>
+  typeof error === 'object' && error !== null && 'kind' in error && 'message' in error;
+
+export const getErrorMessage = (error: unknown): string =>
+  isApiError(error) ? error.message : String(error);